@@ -0,0 +1,600 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::input::InputSystem;
+use bevy::input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::ArenaGrid;
+use crate::cli;
+use crate::collision::raycast_tiles;
+
+const INPUT_CONFIG_ARG: &str = "--input-config";
+const DEFAULT_INPUT_CONFIG_PATH: &str = "input.ron";
+
+/// Stick aim bends towards enemies within this angle of where the stick points.
+const AIM_ASSIST_CONE_DEGREES: f32 = 12.0;
+const AIM_ASSIST_RANGE: f32 = 400.0;
+/// How far aim bends towards the target, from 0 (not at all) to 1 (straight at it).
+const AIM_ASSIST_STRENGTH: f32 = 0.8;
+
+/// Something the player can do, bound to any number of keys and buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Dodge,
+    Reload,
+    /// Picks up the nearest weapon.
+    Interact,
+    Blank,
+    DropWeapon,
+    NextWeapon,
+    PreviousWeapon,
+    /// Switches straight to a weapon slot, counted from 1.
+    Slot(u8),
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 22] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Fire,
+        Action::Dodge,
+        Action::Reload,
+        Action::Interact,
+        Action::Blank,
+        Action::DropWeapon,
+        Action::NextWeapon,
+        Action::PreviousWeapon,
+        Action::Slot(1),
+        Action::Slot(2),
+        Action::Slot(3),
+        Action::Slot(4),
+        Action::Slot(5),
+        Action::Slot(6),
+        Action::Slot(7),
+        Action::Slot(8),
+        Action::Slot(9),
+        Action::Pause,
+    ];
+
+    pub fn label(self) -> String {
+        match self {
+            Action::MoveUp => "Move up".to_string(),
+            Action::MoveDown => "Move down".to_string(),
+            Action::MoveLeft => "Move left".to_string(),
+            Action::MoveRight => "Move right".to_string(),
+            Action::Fire => "Fire".to_string(),
+            Action::Dodge => "Dodge".to_string(),
+            Action::Reload => "Reload".to_string(),
+            Action::Interact => "Pick up".to_string(),
+            Action::Blank => "Blank".to_string(),
+            Action::DropWeapon => "Drop weapon".to_string(),
+            Action::NextWeapon => "Next weapon".to_string(),
+            Action::PreviousWeapon => "Previous weapon".to_string(),
+            Action::Slot(slot) => format!("Weapon {slot}"),
+            Action::Pause => "Pause".to_string(),
+        }
+    }
+}
+
+/// A key or button that can trigger an [`Action`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+    /// The mouse wheel scrolled up, counted as a press for that frame.
+    WheelUp,
+    WheelDown,
+}
+
+impl Binding {
+    fn is_gamepad(self) -> bool {
+        matches!(self, Binding::Gamepad(_))
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Binding::Key(key) => {
+                let name = format!("{key:?}");
+                match name.strip_prefix("Key").or(name.strip_prefix("Digit")) {
+                    Some(short) => short.to_string(),
+                    None => name,
+                }
+            }
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::Gamepad(button) => format!("Pad {button:?}"),
+            Binding::WheelUp => "Wheel up".to_string(),
+            Binding::WheelDown => "Wheel down".to_string(),
+        }
+    }
+}
+
+/// Which bindings trigger each action, plus the gamepad options. Saved to the input config file
+/// by [`InputConfigPlugin`].
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub actions: BTreeMap<Action, Vec<Binding>>,
+    /// Bends stick aim towards nearby enemies.
+    pub aim_assist: bool,
+    /// How far a stick has to be pushed before it counts, from 0 to 1.
+    pub stick_deadzone: f32,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use Binding::{Gamepad as Pad, Key, Mouse};
+
+        let slot_keys = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+        let mut actions = BTreeMap::from([
+            (
+                Action::MoveUp,
+                vec![
+                    Key(KeyCode::KeyW),
+                    Key(KeyCode::ArrowUp),
+                    Pad(GamepadButton::DPadUp),
+                ],
+            ),
+            (
+                Action::MoveDown,
+                vec![
+                    Key(KeyCode::KeyS),
+                    Key(KeyCode::ArrowDown),
+                    Pad(GamepadButton::DPadDown),
+                ],
+            ),
+            (
+                Action::MoveLeft,
+                vec![
+                    Key(KeyCode::KeyA),
+                    Key(KeyCode::ArrowLeft),
+                    Pad(GamepadButton::DPadLeft),
+                ],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::KeyD),
+                    Key(KeyCode::ArrowRight),
+                    Pad(GamepadButton::DPadRight),
+                ],
+            ),
+            (
+                Action::Fire,
+                vec![Mouse(MouseButton::Left), Pad(GamepadButton::RightTrigger2)],
+            ),
+            (
+                Action::Dodge,
+                vec![
+                    Key(KeyCode::Space),
+                    Mouse(MouseButton::Right),
+                    Pad(GamepadButton::LeftTrigger2),
+                ],
+            ),
+            (
+                Action::Reload,
+                vec![Key(KeyCode::KeyR), Pad(GamepadButton::West)],
+            ),
+            (
+                Action::Interact,
+                vec![Key(KeyCode::KeyE), Pad(GamepadButton::South)],
+            ),
+            (
+                Action::Blank,
+                vec![Key(KeyCode::KeyQ), Pad(GamepadButton::East)],
+            ),
+            (
+                Action::DropWeapon,
+                vec![Key(KeyCode::KeyG), Pad(GamepadButton::North)],
+            ),
+            (
+                Action::NextWeapon,
+                vec![Binding::WheelDown, Pad(GamepadButton::RightTrigger)],
+            ),
+            (
+                Action::PreviousWeapon,
+                vec![Binding::WheelUp, Pad(GamepadButton::LeftTrigger)],
+            ),
+            (
+                Action::Pause,
+                vec![Key(KeyCode::Escape), Pad(GamepadButton::Start)],
+            ),
+        ]);
+        for (slot, key) in (1..).zip(slot_keys) {
+            actions.insert(Action::Slot(slot), vec![Key(key)]);
+        }
+
+        Self {
+            actions,
+            aim_assist: true,
+            stick_deadzone: 0.2,
+        }
+    }
+}
+
+impl InputBindings {
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.actions.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Binds `binding` to `action` in place of the action's other bindings from the same kind
+    /// of device, and takes it away from any other action it was bound to.
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        for bindings in self.actions.values_mut() {
+            bindings.retain(|bound| *bound != binding);
+        }
+        let bindings = self.actions.entry(action).or_default();
+        bindings.retain(|bound| bound.is_gamepad() != binding.is_gamepad());
+        bindings.push(binding);
+    }
+
+    fn load(path: &Path) -> Result<Self, InputConfigError> {
+        let mut bindings: Self = ron::from_str(&fs::read_to_string(path)?)?;
+        // Actions added since the file was written start out with their default bindings.
+        for (action, defaults) in Self::default().actions {
+            bindings.actions.entry(action).or_insert(defaults);
+        }
+        Ok(bindings)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), InputConfigError> {
+        let config = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, config)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum InputConfigError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+}
+
+impl fmt::Display for InputConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputConfigError::Io(error) => write!(f, "could not access input config: {error}"),
+            InputConfigError::Parse(error) => write!(f, "invalid input config: {error}"),
+            InputConfigError::Write(error) => write!(f, "could not write input config: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for InputConfigError {}
+
+impl From<std::io::Error> for InputConfigError {
+    fn from(error: std::io::Error) -> Self {
+        InputConfigError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for InputConfigError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        InputConfigError::Parse(error)
+    }
+}
+
+impl From<ron::Error> for InputConfigError {
+    fn from(error: ron::Error) -> Self {
+        InputConfigError::Write(error)
+    }
+}
+
+/// Direction to move in, up to length 1. Sticks give partial lengths; keys are all or nothing.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+pub struct MoveInput(pub Vec2);
+
+/// Where the player is aiming from the input side. Gameplay reads the aim off the player's
+/// rotation, which `player_aiming_system` sets from this.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+pub enum AimInput {
+    /// Nothing to aim with yet, or no window to aim in.
+    #[default]
+    None,
+    /// The mouse cursor, in world space.
+    Cursor(Vec2),
+    /// A right stick direction, kept when the stick is let go.
+    Stick(Vec2),
+}
+
+/// Turns keyboard, mouse and gamepad input into [`Action`]s, [`MoveInput`] and [`AimInput`].
+pub struct ActionPlugin;
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputBindings>()
+            .init_resource::<ButtonInput<Action>>()
+            .init_resource::<MoveInput>()
+            .init_resource::<AimInput>()
+            .add_systems(
+                PreUpdate,
+                (update_actions_system, update_aim_system).after(InputSystem),
+            );
+    }
+}
+
+/// Loads [`InputBindings`] from the input config file, `input.ron` unless `--input-config`
+/// says otherwise, and saves them back whenever they change.
+pub struct InputConfigPlugin;
+
+impl Plugin for InputConfigPlugin {
+    fn build(&self, app: &mut App) {
+        let path = PathBuf::from(
+            cli::arg_value(INPUT_CONFIG_ARG).unwrap_or(DEFAULT_INPUT_CONFIG_PATH.to_string()),
+        );
+        let bindings = if path.exists() {
+            InputBindings::load(&path).unwrap_or_else(|error| {
+                warn!("Using the default controls: {error}");
+                InputBindings::default()
+            })
+        } else {
+            InputBindings::default()
+        };
+
+        app.insert_resource(bindings)
+            .insert_resource(InputConfigPath(path))
+            .add_systems(
+                Update,
+                save_input_config.run_if(resource_changed::<InputBindings>),
+            );
+    }
+}
+
+#[derive(Resource)]
+struct InputConfigPath(PathBuf);
+
+fn save_input_config(bindings: Res<InputBindings>, path: Res<InputConfigPath>) {
+    if let Err(error) = bindings.save(&path.0) {
+        error!("Could not save controls to {}: {error}", path.0.display());
+    }
+}
+
+/// The state of every binding this frame.
+struct BindingState<'a> {
+    keyboard_input: &'a ButtonInput<KeyCode>,
+    mouse_button_input: &'a ButtonInput<MouseButton>,
+    mouse_scroll: f32,
+    gamepads: Vec<&'a Gamepad>,
+}
+
+impl BindingState<'_> {
+    fn is_active(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keyboard_input.pressed(key),
+            Binding::Mouse(button) => self.mouse_button_input.pressed(button),
+            Binding::Gamepad(button) => self.gamepads.iter().any(|pad| pad.pressed(button)),
+            Binding::WheelUp => self.mouse_scroll > 0.0,
+            Binding::WheelDown => self.mouse_scroll < 0.0,
+        }
+    }
+
+    /// The stick pushed furthest, if any is past the deadzone, with its length capped at 1.
+    fn stick(&self, read: impl Fn(&Gamepad) -> Vec2, deadzone: f32) -> Option<Vec2> {
+        self.gamepads
+            .iter()
+            .map(|pad| read(pad))
+            .filter(|stick| stick.length() > deadzone)
+            .max_by(|a, b| a.length().total_cmp(&b.length()))
+            .map(|stick| stick.clamp_length_max(1.0))
+    }
+}
+
+fn update_actions_system(
+    mut actions: ResMut<ButtonInput<Action>>,
+    mut move_input: ResMut<MoveInput>,
+    bindings: Res<InputBindings>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mouse_scroll: Res<AccumulatedMouseScroll>,
+    gamepad_query: Query<&Gamepad>,
+) {
+    let state = BindingState {
+        keyboard_input: &keyboard_input,
+        mouse_button_input: &mouse_button_input,
+        mouse_scroll: mouse_scroll.delta.y,
+        gamepads: gamepad_query.iter().collect(),
+    };
+
+    actions.clear();
+    for action in Action::ALL {
+        if bindings
+            .bindings(action)
+            .iter()
+            .any(|binding| state.is_active(*binding))
+        {
+            actions.press(action);
+        } else {
+            actions.release(action);
+        }
+    }
+
+    let axis = |negative, positive| {
+        actions.pressed(positive) as i32 as f32 - actions.pressed(negative) as i32 as f32
+    };
+    let digital = Vec2::new(
+        axis(Action::MoveLeft, Action::MoveRight),
+        axis(Action::MoveDown, Action::MoveUp),
+    );
+    move_input.0 = state
+        .stick(Gamepad::left_stick, bindings.stick_deadzone)
+        .unwrap_or(digital.normalize_or_zero());
+}
+
+/// Aims with whichever of the mouse and the right stick moved last.
+fn update_aim_system(
+    mut aim_input: ResMut<AimInput>,
+    bindings: Res<InputBindings>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    gamepad_query: Query<&Gamepad>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) {
+    let stick = gamepad_query
+        .iter()
+        .map(Gamepad::right_stick)
+        .filter(|stick| stick.length() > bindings.stick_deadzone)
+        .max_by(|a, b| a.length().total_cmp(&b.length()));
+    if let Some(stick) = stick {
+        *aim_input = AimInput::Stick(stick.normalize());
+        return;
+    }
+
+    let using_mouse = mouse_motion.delta != Vec2::ZERO || !matches!(*aim_input, AimInput::Stick(_));
+    if using_mouse
+        && let Ok(window) = window_query.single()
+        && let Some(cursor_position) = window.cursor_position()
+        && let Ok((camera, camera_transform)) = camera_query.single()
+        && let Ok(world_position) = camera.viewport_to_world_2d(camera_transform, cursor_position)
+    {
+        *aim_input = AimInput::Cursor(world_position);
+    }
+}
+
+/// Bends `aim` towards the target closest to its line, among those within the assist cone and
+/// range and in sight of `origin`. Returns `aim` as it was if there's no such target.
+pub fn assisted_aim(
+    origin: Vec2,
+    aim: Vec2,
+    targets: impl IntoIterator<Item = Vec2>,
+    arena_grid: &ArenaGrid,
+) -> Vec2 {
+    let cone = AIM_ASSIST_CONE_DEGREES.to_radians();
+    let best = targets
+        .into_iter()
+        .filter_map(|target| {
+            let offset = target - origin;
+            let distance = offset.length();
+            if distance == 0.0 || distance > AIM_ASSIST_RANGE {
+                return None;
+            }
+            let angle = aim.angle_to(offset).abs();
+            (angle <= cone).then_some((offset / distance, angle, distance))
+        })
+        .filter(|(direction, _, distance)| {
+            raycast_tiles(arena_grid, origin, *direction, *distance).is_none()
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+
+    match best {
+        Some((direction, _, _)) => {
+            Vec2::from_angle(aim.angle_to(direction) * AIM_ASSIST_STRENGTH).rotate(aim)
+        }
+        None => aim,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::TileType;
+    use crate::collision::tile_to_world;
+
+    #[test]
+    fn rebinding_replaces_the_same_device_and_frees_the_binding() {
+        let mut bindings = InputBindings::default();
+        bindings.rebind(Action::Fire, Binding::Key(KeyCode::KeyR));
+
+        assert_eq!(
+            bindings.bindings(Action::Fire),
+            [
+                Binding::Gamepad(GamepadButton::RightTrigger2),
+                Binding::Key(KeyCode::KeyR)
+            ]
+        );
+        assert_eq!(
+            bindings.bindings(Action::Reload),
+            [Binding::Gamepad(GamepadButton::West)],
+            "R moved from reload to fire"
+        );
+
+        bindings.rebind(Action::Fire, Binding::Gamepad(GamepadButton::South));
+        assert_eq!(
+            bindings.bindings(Action::Fire),
+            [
+                Binding::Key(KeyCode::KeyR),
+                Binding::Gamepad(GamepadButton::South)
+            ]
+        );
+    }
+
+    #[test]
+    fn config_files_round_trip_and_fill_in_missing_actions() {
+        let mut bindings = InputBindings::default();
+        bindings.rebind(Action::Dodge, Binding::Key(KeyCode::ShiftLeft));
+        bindings.aim_assist = false;
+        bindings.actions.remove(&Action::Pause);
+
+        let path = std::env::temp_dir().join(format!("input-{}.ron", std::process::id()));
+        bindings.save(&path).unwrap();
+        let loaded = InputBindings::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(
+            loaded.bindings(Action::Pause),
+            InputBindings::default().bindings(Action::Pause)
+        );
+        bindings
+            .actions
+            .insert(Action::Pause, loaded.bindings(Action::Pause).to_vec());
+        assert_eq!(loaded, bindings);
+    }
+
+    #[test]
+    fn every_action_has_a_default_binding() {
+        let bindings = InputBindings::default();
+        for action in Action::ALL {
+            assert!(!bindings.bindings(action).is_empty(), "{action:?}");
+        }
+        assert_eq!(bindings.actions.len(), Action::ALL.len());
+    }
+
+    #[test]
+    fn aim_assist_bends_towards_visible_targets_in_the_cone() {
+        let mut grid = vec![vec![TileType::Floor; 30]; 30];
+        grid[20][16] = TileType::Wall;
+        let arena_grid = ArenaGrid::from_tiles(grid);
+        let origin = tile_to_world(&arena_grid, 15, 15);
+        let aim = Vec2::X;
+
+        let in_cone = origin + Vec2::from_angle(0.1) * 150.0;
+        let assisted = assisted_aim(origin, aim, [in_cone], &arena_grid);
+        let target_angle = aim.angle_to(in_cone - origin);
+        assert!(aim.angle_to(assisted) > 0.0);
+        assert!(aim.angle_to(assisted) <= target_angle);
+
+        let outside_cone = origin + Vec2::from_angle(0.6) * 150.0;
+        let too_far = origin + Vec2::X * (AIM_ASSIST_RANGE + 10.0);
+        assert_eq!(
+            assisted_aim(origin, aim, [outside_cone, too_far], &arena_grid),
+            aim
+        );
+
+        let up = Vec2::Y;
+        let behind_wall = tile_to_world(&arena_grid, 16, 22);
+        assert_eq!(assisted_aim(origin, up, [behind_wall], &arena_grid), up);
+    }
+}
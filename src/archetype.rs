@@ -0,0 +1,797 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext, LoadState};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use ron::extensions::Extensions;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::GameState;
+use crate::arena::ArenaTuning;
+use crate::blank::BlankStats;
+use crate::dodge::DodgeStats;
+use crate::enemy_weapon::{AimMode, EnemyWeaponStats};
+use crate::player::Faction;
+use crate::weapon::{FireMode, StartingWeapon, WeaponStats};
+
+const PLAYER_ARCHETYPE_PATH: &str = "archetypes/base.player.ron";
+const WEAPON_ARCHETYPES_PATH: &str = "archetypes/base.weapons.ron";
+const ENEMY_ARCHETYPES_PATH: &str = "archetypes/base.enemies.ron";
+const ARENA_TUNING_PATH: &str = "archetypes/base.arena.ron";
+
+/// An sRGB colour written as `(r, g, b)` with each channel in `0..=1`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Rgb(pub f32, pub f32, pub f32);
+
+impl From<Rgb> for Color {
+    fn from(Rgb(red, green, blue): Rgb) -> Self {
+        Color::srgb(red, green, blue)
+    }
+}
+
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct PlayerArchetype {
+    pub health: f32,
+    pub speed: f32,
+    pub size: f32,
+    pub color: Rgb,
+    pub invulnerability_seconds: f32,
+    /// Weapon id the run starts with unless `--weapon` picks another.
+    pub starting_weapon: String,
+    pub dodge: DodgeStats,
+    pub blank: BlankStats,
+}
+
+/// How one side's projectiles look and how long they live.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectileArchetype {
+    pub size: (f32, f32),
+    pub color: Rgb,
+    pub lifetime_seconds: f32,
+    /// How far in front of the shooter projectiles appear.
+    pub spawn_offset: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectileArchetypes {
+    pub player: ProjectileArchetype,
+    pub enemy: ProjectileArchetype,
+}
+
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct WeaponArchetypes {
+    pub projectiles: ProjectileArchetypes,
+    pub weapons: BTreeMap<String, WeaponStats>,
+    /// Weapon ids that can be found lying in the arena.
+    pub loot: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyArchetype {
+    pub health: f32,
+    pub speed: f32,
+    pub contact_damage: f32,
+    pub size: f32,
+    pub color: Rgb,
+    #[serde(default)]
+    pub weapon: Option<EnemyWeaponStats>,
+    /// Bullet pattern asset path, for enemies that fire patterns instead of a weapon.
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+/// Which enemies spawn and how many.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnTable {
+    /// Enemies scattered over an arena without rooms.
+    pub max_enemies: usize,
+    pub per_room: usize,
+    pub per_boss_room: usize,
+    /// Chance that a regular enemy is drawn from `ranged` rather than `melee`.
+    pub ranged_chance: f64,
+    pub melee: Vec<String>,
+    pub ranged: Vec<String>,
+    /// Spawned first in every boss room.
+    pub boss: String,
+}
+
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct EnemyArchetypes {
+    pub spawning: SpawnTable,
+    pub enemies: BTreeMap<String, EnemyArchetype>,
+}
+
+#[derive(Debug)]
+pub enum ArchetypeError {
+    Parse(ron::error::SpannedError),
+    Io(std::io::Error),
+    Invalid {
+        archetype: String,
+        field: &'static str,
+        reason: String,
+    },
+    UnknownReference {
+        from: String,
+        kind: &'static str,
+        id: String,
+    },
+}
+
+impl fmt::Display for ArchetypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchetypeError::Parse(error) => write!(f, "invalid archetype file: {error}"),
+            ArchetypeError::Io(error) => write!(f, "could not read archetype file: {error}"),
+            ArchetypeError::Invalid {
+                archetype,
+                field,
+                reason,
+            } => write!(f, "{archetype}: `{field}` {reason}"),
+            ArchetypeError::UnknownReference { from, kind, id } => {
+                write!(f, "{from} refers to unknown {kind} '{id}'")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArchetypeError {}
+
+impl From<std::io::Error> for ArchetypeError {
+    fn from(error: std::io::Error) -> Self {
+        ArchetypeError::Io(error)
+    }
+}
+
+fn invalid(archetype: &str, field: &'static str, reason: impl Into<String>) -> ArchetypeError {
+    ArchetypeError::Invalid {
+        archetype: archetype.to_string(),
+        field,
+        reason: reason.into(),
+    }
+}
+
+fn require_positive(
+    archetype: &str,
+    field: &'static str,
+    value: f32,
+) -> Result<(), ArchetypeError> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(invalid(
+            archetype,
+            field,
+            format!("must be positive, got {value}"),
+        ))
+    }
+}
+
+fn require_non_negative(
+    archetype: &str,
+    field: &'static str,
+    value: f32,
+) -> Result<(), ArchetypeError> {
+    if value >= 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(invalid(
+            archetype,
+            field,
+            format!("must not be negative, got {value}"),
+        ))
+    }
+}
+
+fn require_color(archetype: &str, color: Rgb) -> Result<(), ArchetypeError> {
+    let Rgb(red, green, blue) = color;
+    if [red, green, blue]
+        .iter()
+        .all(|channel| (0.0..=1.0).contains(channel))
+    {
+        Ok(())
+    } else {
+        Err(invalid(
+            archetype,
+            "color",
+            format!("channels must be between 0 and 1, got ({red}, {green}, {blue})"),
+        ))
+    }
+}
+
+fn require_known<T>(
+    map: &BTreeMap<String, T>,
+    from: &str,
+    kind: &'static str,
+    id: &str,
+) -> Result<(), ArchetypeError> {
+    if map.contains_key(id) {
+        Ok(())
+    } else {
+        Err(ArchetypeError::UnknownReference {
+            from: from.to_string(),
+            kind,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// A RON file of archetypes, checked for nonsense values as it loads.
+trait ArchetypeFile: Asset + DeserializeOwned {
+    const EXTENSIONS: &'static [&'static str];
+
+    fn validate(&self) -> Result<(), ArchetypeError>;
+
+    fn from_ron(bytes: &[u8]) -> Result<Self, ArchetypeError> {
+        let file: Self = ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_bytes(bytes)
+            .map_err(ArchetypeError::Parse)?;
+        file.validate()?;
+        Ok(file)
+    }
+}
+
+impl ArchetypeFile for PlayerArchetype {
+    const EXTENSIONS: &'static [&'static str] = &["player.ron"];
+
+    fn validate(&self) -> Result<(), ArchetypeError> {
+        require_positive("player", "health", self.health)?;
+        require_positive("player", "speed", self.speed)?;
+        require_positive("player", "size", self.size)?;
+        require_non_negative(
+            "player",
+            "invulnerability_seconds",
+            self.invulnerability_seconds,
+        )?;
+        require_color("player", self.color)?;
+
+        let dodge = &self.dodge;
+        require_positive("player", "dodge.speed", dodge.speed)?;
+        require_positive("player", "dodge.duration_seconds", dodge.duration_seconds)?;
+        require_non_negative(
+            "player",
+            "dodge.invulnerable_seconds",
+            dodge.invulnerable_seconds,
+        )?;
+        require_non_negative("player", "dodge.cooldown_seconds", dodge.cooldown_seconds)?;
+
+        let blank = &self.blank;
+        require_positive("player", "blank.radius", blank.radius)?;
+        require_non_negative(
+            "player",
+            "blank.knockback_distance",
+            blank.knockback_distance,
+        )?;
+        require_non_negative("player", "blank.stun_seconds", blank.stun_seconds)
+    }
+}
+
+impl ProjectileArchetype {
+    fn validate(&self, archetype: &str) -> Result<(), ArchetypeError> {
+        require_positive(archetype, "size", self.size.0)?;
+        require_positive(archetype, "size", self.size.1)?;
+        require_positive(archetype, "lifetime_seconds", self.lifetime_seconds)?;
+        require_non_negative(archetype, "spawn_offset", self.spawn_offset)?;
+        require_color(archetype, self.color)
+    }
+}
+
+fn validate_weapon(id: &str, stats: &WeaponStats) -> Result<(), ArchetypeError> {
+    let archetype = format!("weapon '{id}'");
+    require_positive(&archetype, "fire_rate", stats.fire_rate)?;
+    require_non_negative(&archetype, "reload_seconds", stats.reload_seconds)?;
+    require_positive(&archetype, "projectile_speed", stats.projectile_speed)?;
+    require_non_negative(&archetype, "projectile_damage", stats.projectile_damage)?;
+    require_non_negative(&archetype, "spread_degrees", stats.spread_degrees)?;
+    require_non_negative(&archetype, "recoil", stats.recoil)?;
+    if stats.magazine_size == 0 {
+        return Err(invalid(&archetype, "magazine_size", "must be at least 1"));
+    }
+    if stats.projectiles_per_shot == 0 {
+        return Err(invalid(
+            &archetype,
+            "projectiles_per_shot",
+            "must be at least 1",
+        ));
+    }
+    match stats.fire_mode {
+        FireMode::Burst { shots, interval } => {
+            if shots == 0 {
+                return Err(invalid(
+                    &archetype,
+                    "fire_mode",
+                    "bursts need at least 1 shot",
+                ));
+            }
+            require_non_negative(&archetype, "fire_mode", interval)
+        }
+        FireMode::Charge { seconds } => require_non_negative(&archetype, "fire_mode", seconds),
+        FireMode::SemiAuto | FireMode::Automatic => Ok(()),
+    }
+}
+
+impl ArchetypeFile for WeaponArchetypes {
+    const EXTENSIONS: &'static [&'static str] = &["weapons.ron"];
+
+    fn validate(&self) -> Result<(), ArchetypeError> {
+        self.projectiles.player.validate("player projectile")?;
+        self.projectiles.enemy.validate("enemy projectile")?;
+        for (id, stats) in &self.weapons {
+            validate_weapon(id, stats)?;
+        }
+        for id in &self.loot {
+            require_known(&self.weapons, "loot", "weapon", id)?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_enemy(id: &str, enemy: &EnemyArchetype) -> Result<(), ArchetypeError> {
+    let archetype = format!("enemy '{id}'");
+    require_positive(&archetype, "health", enemy.health)?;
+    require_non_negative(&archetype, "speed", enemy.speed)?;
+    require_non_negative(&archetype, "contact_damage", enemy.contact_damage)?;
+    require_positive(&archetype, "size", enemy.size)?;
+    require_color(&archetype, enemy.color)?;
+
+    if let Some(weapon) = &enemy.weapon {
+        require_positive(
+            &archetype,
+            "weapon.projectile_speed",
+            weapon.projectile_speed,
+        )?;
+        require_non_negative(
+            &archetype,
+            "weapon.projectile_damage",
+            weapon.projectile_damage,
+        )?;
+        require_positive(&archetype, "weapon.range", weapon.range)?;
+        require_positive(
+            &archetype,
+            "weapon.cooldown_seconds",
+            weapon.cooldown_seconds,
+        )?;
+        if let AimMode::Spread { count, arc_degrees } = weapon.aim {
+            if count == 0 {
+                return Err(invalid(
+                    &archetype,
+                    "weapon.aim",
+                    "spreads need at least 1 shot",
+                ));
+            }
+            require_non_negative(&archetype, "weapon.aim", arc_degrees)?;
+        }
+    }
+    Ok(())
+}
+
+impl ArchetypeFile for EnemyArchetypes {
+    const EXTENSIONS: &'static [&'static str] = &["enemies.ron"];
+
+    fn validate(&self) -> Result<(), ArchetypeError> {
+        for (id, enemy) in &self.enemies {
+            validate_enemy(id, enemy)?;
+        }
+
+        let spawning = &self.spawning;
+        if !(0.0..=1.0).contains(&spawning.ranged_chance) {
+            return Err(invalid(
+                "spawning",
+                "ranged_chance",
+                format!("must be between 0 and 1, got {}", spawning.ranged_chance),
+            ));
+        }
+        if spawning.melee.is_empty() {
+            return Err(invalid("spawning", "melee", "needs at least one enemy"));
+        }
+        if spawning.ranged.is_empty() && spawning.ranged_chance > 0.0 {
+            return Err(invalid(
+                "spawning",
+                "ranged",
+                "needs at least one enemy while ranged_chance is above 0",
+            ));
+        }
+        for id in spawning.melee.iter().chain(&spawning.ranged) {
+            require_known(&self.enemies, "spawning", "enemy", id)?;
+        }
+        for id in &spawning.ranged {
+            if self.enemies[id].weapon.is_none() {
+                return Err(invalid(
+                    &format!("enemy '{id}'"),
+                    "weapon",
+                    "is required for enemies in the ranged spawn list",
+                ));
+            }
+        }
+        require_known(&self.enemies, "spawning", "enemy", &spawning.boss)
+    }
+}
+
+impl ArchetypeFile for ArenaTuning {
+    const EXTENSIONS: &'static [&'static str] = &["arena.ron"];
+
+    fn validate(&self) -> Result<(), ArchetypeError> {
+        let cave = &self.cave;
+        if !(cave.noise_scale > 0.0 && cave.noise_scale.is_finite()) {
+            return Err(invalid(
+                "cave",
+                "noise_scale",
+                format!("must be positive, got {}", cave.noise_scale),
+            ));
+        }
+        for (field, threshold) in [
+            ("wall_conversion_threshold", cave.wall_conversion_threshold),
+            (
+                "floor_conversion_threshold",
+                cave.floor_conversion_threshold,
+            ),
+        ] {
+            if threshold > 8 {
+                return Err(invalid(
+                    "cave",
+                    field,
+                    format!("counts neighbours so must be at most 8, got {threshold}"),
+                ));
+            }
+        }
+
+        let room = &self.room;
+        if !(0.0..=1.0).contains(&room.obstacle_probability) {
+            return Err(invalid(
+                "room",
+                "obstacle_probability",
+                format!("must be between 0 and 1, got {}", room.obstacle_probability),
+            ));
+        }
+        if room.min_obstacle_size == 0 || room.min_obstacle_size > room.max_obstacle_size {
+            return Err(invalid(
+                "room",
+                "min_obstacle_size",
+                format!(
+                    "must be between 1 and max_obstacle_size ({}), got {}",
+                    room.max_obstacle_size, room.min_obstacle_size
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Reads any [`ArchetypeFile`] from disk.
+struct ArchetypeLoader<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for ArchetypeLoader<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: ArchetypeFile> AssetLoader for ArchetypeLoader<T> {
+    type Asset = T;
+    type Settings = ();
+    type Error = ArchetypeError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<T, ArchetypeError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        T::from_ron(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        T::EXTENSIONS
+    }
+}
+
+/// Every archetype the game spawns from, available once the files have loaded and their
+/// cross-references check out. Replaced wholesale when a file is reloaded.
+#[derive(Resource, Debug, Clone)]
+pub struct Archetypes {
+    pub player: PlayerArchetype,
+    pub weapons: WeaponArchetypes,
+    pub enemies: EnemyArchetypes,
+    pub arena: ArenaTuning,
+}
+
+impl Archetypes {
+    pub fn resolve(
+        player: PlayerArchetype,
+        weapons: WeaponArchetypes,
+        enemies: EnemyArchetypes,
+        arena: ArenaTuning,
+    ) -> Result<Self, ArchetypeError> {
+        require_known(
+            &weapons.weapons,
+            "player",
+            "weapon",
+            &player.starting_weapon,
+        )?;
+        Ok(Self {
+            player,
+            weapons,
+            enemies,
+            arena,
+        })
+    }
+
+    pub fn weapon(&self, id: &str) -> &WeaponStats {
+        &self.weapons.weapons[id]
+    }
+
+    /// Looks a weapon up by display name, which is how carried weapons are told apart.
+    pub fn weapon_named(&self, name: &str) -> Option<&WeaponStats> {
+        self.weapons
+            .weapons
+            .values()
+            .find(|stats| stats.name == name)
+    }
+
+    pub fn enemy(&self, id: &str) -> &EnemyArchetype {
+        &self.enemies.enemies[id]
+    }
+
+    pub fn projectile(&self, faction: Faction) -> &ProjectileArchetype {
+        match faction {
+            Faction::Player => &self.weapons.projectiles.player,
+            Faction::Enemy => &self.weapons.projectiles.enemy,
+        }
+    }
+
+    /// The archetypes shipped in `assets/archetypes`.
+    #[cfg(test)]
+    pub fn shipped() -> Self {
+        Self::resolve(
+            PlayerArchetype::from_ron(include_bytes!("../assets/archetypes/base.player.ron"))
+                .unwrap(),
+            WeaponArchetypes::from_ron(include_bytes!("../assets/archetypes/base.weapons.ron"))
+                .unwrap(),
+            EnemyArchetypes::from_ron(include_bytes!("../assets/archetypes/base.enemies.ron"))
+                .unwrap(),
+            ArenaTuning::from_ron(include_bytes!("../assets/archetypes/base.arena.ron")).unwrap(),
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Resource)]
+struct ArchetypeHandles {
+    player: Handle<PlayerArchetype>,
+    weapons: Handle<WeaponArchetypes>,
+    enemies: Handle<EnemyArchetypes>,
+    arena: Handle<ArenaTuning>,
+}
+
+/// The archetype files as currently loaded by the asset server.
+#[derive(SystemParam)]
+struct LoadedArchetypes<'w> {
+    handles: Res<'w, ArchetypeHandles>,
+    asset_server: Res<'w, AssetServer>,
+    players: Res<'w, Assets<PlayerArchetype>>,
+    weapons: Res<'w, Assets<WeaponArchetypes>>,
+    enemies: Res<'w, Assets<EnemyArchetypes>>,
+    arena: Res<'w, Assets<ArenaTuning>>,
+}
+
+impl LoadedArchetypes<'_> {
+    fn failure(&self) -> Option<String> {
+        let ids = [
+            self.handles.player.id().untyped(),
+            self.handles.weapons.id().untyped(),
+            self.handles.enemies.id().untyped(),
+            self.handles.arena.id().untyped(),
+        ];
+        ids.into_iter()
+            .find_map(|id| match self.asset_server.get_load_state(id) {
+                Some(LoadState::Failed(error)) => Some(error.to_string()),
+                _ => None,
+            })
+    }
+
+    /// `None` until every file has loaded.
+    fn resolve(&self) -> Option<Result<Archetypes, ArchetypeError>> {
+        Some(Archetypes::resolve(
+            self.players.get(&self.handles.player)?.clone(),
+            self.weapons.get(&self.handles.weapons)?.clone(),
+            self.enemies.get(&self.handles.enemies)?.clone(),
+            self.arena.get(&self.handles.arena)?.clone(),
+        ))
+    }
+}
+
+pub struct ArchetypePlugin;
+
+impl Plugin for ArchetypePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<PlayerArchetype>()
+            .init_asset::<WeaponArchetypes>()
+            .init_asset::<EnemyArchetypes>()
+            .init_asset::<ArenaTuning>()
+            .init_asset_loader::<ArchetypeLoader<PlayerArchetype>>()
+            .init_asset_loader::<ArchetypeLoader<WeaponArchetypes>>()
+            .init_asset_loader::<ArchetypeLoader<EnemyArchetypes>>()
+            .init_asset_loader::<ArchetypeLoader<ArenaTuning>>()
+            .add_systems(Startup, load_archetypes)
+            .add_systems(
+                Update,
+                (
+                    finish_loading_archetypes.run_if(in_state(GameState::Loading)),
+                    reload_archetypes.run_if(resource_exists::<Archetypes>),
+                ),
+            );
+    }
+}
+
+fn load_archetypes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ArchetypeHandles {
+        player: asset_server.load(PLAYER_ARCHETYPE_PATH),
+        weapons: asset_server.load(WEAPON_ARCHETYPES_PATH),
+        enemies: asset_server.load(ENEMY_ARCHETYPES_PATH),
+        arena: asset_server.load(ARENA_TUNING_PATH),
+    });
+}
+
+/// Opens the main menu once every archetype file has loaded, or quits with the first problem
+/// found.
+fn finish_loading_archetypes(
+    mut commands: Commands,
+    loaded: LoadedArchetypes,
+    mut starting_weapon: ResMut<StartingWeapon>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    if let Some(error) = loaded.failure() {
+        error!("Could not load archetypes: {error}");
+        app_exit.write(AppExit::error());
+        return;
+    }
+
+    match loaded.resolve() {
+        None => {}
+        Some(Ok(archetypes)) => {
+            if let Some(id) = starting_weapon
+                .0
+                .take_if(|id| !archetypes.weapons.weapons.contains_key(id.as_str()))
+            {
+                let known: Vec<_> = archetypes.weapons.weapons.keys().cloned().collect();
+                warn!(
+                    "Ignoring unknown weapon '{id}', expected one of: {}.",
+                    known.join(", ")
+                );
+            }
+            commands.insert_resource(archetypes);
+            next_state.set(GameState::MainMenu);
+        }
+        Some(Err(error)) => {
+            error!("Could not load archetypes: {error}");
+            app_exit.write(AppExit::error());
+        }
+    }
+}
+
+fn any_modified<A: Asset>(events: &mut EventReader<AssetEvent<A>>) -> bool {
+    // Counting rather than `any` so every event is read and none linger until next frame.
+    events
+        .read()
+        .filter(|event| matches!(event, AssetEvent::Modified { .. }))
+        .count()
+        > 0
+}
+
+/// Swaps in new archetypes when a file changes on disk. Files that fail to parse or validate
+/// keep their previous contents, so a typo never takes the running game down.
+fn reload_archetypes(
+    mut archetypes: ResMut<Archetypes>,
+    loaded: LoadedArchetypes,
+    mut player_events: EventReader<AssetEvent<PlayerArchetype>>,
+    mut weapon_events: EventReader<AssetEvent<WeaponArchetypes>>,
+    mut enemy_events: EventReader<AssetEvent<EnemyArchetypes>>,
+    mut arena_events: EventReader<AssetEvent<ArenaTuning>>,
+) {
+    let modified = [
+        any_modified(&mut player_events),
+        any_modified(&mut weapon_events),
+        any_modified(&mut enemy_events),
+        any_modified(&mut arena_events),
+    ];
+    if !modified.contains(&true) {
+        return;
+    }
+
+    match loaded.resolve() {
+        Some(Ok(reloaded)) => {
+            *archetypes = reloaded;
+            info!("Reloaded archetypes.");
+        }
+        Some(Err(error)) => warn!("Keeping previous archetypes: {error}"),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRUNT: &str = "(health: 50, speed: 75, contact_damage: 10, size: 10, \
+                         color: (0.8, 0.2, 0.2))";
+
+    fn enemies_with(spawning: &str, enemies: &str) -> Result<EnemyArchetypes, ArchetypeError> {
+        let ron = format!("(spawning: {spawning}, enemies: {{ {enemies} }})");
+        EnemyArchetypes::from_ron(ron.as_bytes())
+    }
+
+    #[test]
+    fn shipped_archetypes_load_and_resolve() {
+        let archetypes = Archetypes::shipped();
+        assert_eq!(archetypes.weapon("pistol").name, "Pistol");
+        assert!(archetypes.enemy("boss").pattern.is_some());
+        let spawning = &archetypes.enemies.spawning;
+        for id in &spawning.ranged {
+            assert!(archetypes.enemy(id).weapon.is_some(), "{id} has no weapon");
+        }
+    }
+
+    #[test]
+    fn bad_values_name_the_archetype_and_field() {
+        let error = PlayerArchetype::from_ron(
+            b"(health: 100, speed: -5, size: 10, color: (1, 1, 1), \
+              invulnerability_seconds: 1, starting_weapon: \"pistol\", \
+              dodge: (speed: 1, duration_seconds: 1, invulnerable_seconds: 1, \
+              cooldown_seconds: 1), \
+              blank: (charges_per_floor: 1, radius: 1, knockback_distance: 1, stun_seconds: 1))",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "player: `speed` must be positive, got -5"
+        );
+
+        let spawning = "(max_enemies: 1, per_room: 1, per_boss_room: 1, ranged_chance: 0.5, \
+                        melee: [\"grunt\"], ranged: [\"grunt\"], boss: \"grunt\")";
+        let error = enemies_with(spawning, &format!("\"grunt\": {GRUNT}")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "enemy 'grunt': `weapon` is required for enemies in the ranged spawn list"
+        );
+    }
+
+    #[test]
+    fn unknown_references_are_rejected() {
+        let spawning = "(max_enemies: 1, per_room: 1, per_boss_room: 1, ranged_chance: 0, \
+                        melee: [\"grunt\"], ranged: [], boss: \"dragon\")";
+        let error = enemies_with(spawning, &format!("\"grunt\": {GRUNT}")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "spawning refers to unknown enemy 'dragon'"
+        );
+
+        let shipped = Archetypes::shipped();
+        let player = PlayerArchetype {
+            starting_weapon: "bazooka".into(),
+            ..shipped.player.clone()
+        };
+        let error = Archetypes::resolve(player, shipped.weapons, shipped.enemies, shipped.arena)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "player refers to unknown weapon 'bazooka'"
+        );
+    }
+
+    #[test]
+    fn syntax_errors_report_a_position() {
+        let error = EnemyArchetypes::from_ron(b"(spawning: (max_enemies: ten))").unwrap_err();
+        assert!(matches!(error, ArchetypeError::Parse(_)));
+        assert!(
+            error.to_string().starts_with("invalid archetype file: 1:"),
+            "{error}"
+        );
+    }
+}
@@ -1,76 +1,236 @@
 use bevy::prelude::*;
-use noise::{NoiseFn, Perlin};
-use rand::{Rng, rng};
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
 
-use crate::GameState;
+use crate::archetype::Archetypes;
+use crate::arena_cave::{CaveGenerator, CaveTuning};
+use crate::arena_rooms::{RoomGenerator, RoomTuning};
+use crate::cli;
+use crate::dungeon::{DungeonGenerator, Room};
+use crate::floor::{CurrentFloor, SetupFloor};
+use crate::seed::{RngStream, RunSeed};
 
 pub const ARENA_WIDTH_TILES: usize = 86;
 pub const ARENA_HEIGHT_TILES: usize = 49;
 pub const TILE_SIZE: f32 = 15.0;
-const WALL_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
-const FLOOR_COLOR: Color = Color::srgb(0.15, 0.15, 0.18);
 
-const NOISE_SCALE: f64 = 0.4;
-const NOISE_THRESHOLD: f64 = 0.1;
+const MIN_CONNECTED_REGION_TILES: usize = 12;
 
-const SMOOTHING_ITERATIONS: usize = 3;
-const WALL_CONVERSION_THRESHOLD: usize = 5;
-const FLOOR_CONVERSION_THRESHOLD: usize = 4;
-
-#[derive(Component)]
-pub struct Wall;
-
-#[derive(Component)]
-pub struct ArenaFloor;
+const ARENA_MODE_ARG: &str = "--arena";
+const ARENA_MODE_ENV_VAR: &str = "RUSTY_GUNGEON_ARENA";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TileType {
     Floor,
     Wall,
+    Door,
+    LockedDoor,
 }
 
-#[derive(Resource)]
-pub struct ArenaGrid {
-    pub grid: Vec<Vec<TileType>>,
-    pub width: usize,
-    pub height: usize,
+impl TileType {
+    pub fn is_solid(self) -> bool {
+        matches!(self, TileType::Wall | TileType::LockedDoor)
+    }
 }
 
-impl ArenaGrid {
-    fn new(width: usize, height: usize) -> Self {
-        let mut grid = vec![vec![TileType::Floor; width]; height];
-        let perlin = Perlin::new(rng().random());
+pub struct ArenaLayout {
+    pub tiles: Vec<Vec<TileType>>,
+    pub spawn: (usize, usize),
+    pub rooms: Vec<Room>,
+}
 
-        for (y, row) in grid.iter_mut().enumerate() {
-            for (x, cell) in row.iter_mut().enumerate() {
-                let noise_val = perlin.get([x as f64 * NOISE_SCALE, y as f64 * NOISE_SCALE]);
+impl ArenaLayout {
+    pub fn open(tiles: Vec<Vec<TileType>>) -> Self {
+        let height = tiles.len();
+        let width = tiles.first().map_or(0, Vec::len);
+        Self {
+            tiles,
+            spawn: (width / 2, height / 2),
+            rooms: Vec::new(),
+        }
+    }
+}
 
-                if noise_val > NOISE_THRESHOLD {
-                    *cell = TileType::Wall;
-                } else {
-                    *cell = TileType::Floor;
-                }
-            }
+/// Produces the raw tile layout for an arena. Border walls, the cleared spawn and region
+/// connectivity are applied afterwards by [`ArenaGrid::generate`].
+pub trait ArenaGenerator: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> ArenaLayout;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArenaKind {
+    Cave,
+    Room,
+    Dungeon,
+}
+
+impl ArenaKind {
+    pub fn generator(self, tuning: &ArenaTuning) -> Box<dyn ArenaGenerator> {
+        match self {
+            ArenaKind::Cave => Box::new(CaveGenerator(tuning.cave.clone())),
+            ArenaKind::Room => Box::new(RoomGenerator(tuning.room.clone())),
+            ArenaKind::Dungeon => Box::new(DungeonGenerator),
         }
+    }
+}
+
+/// Generator parameters, loaded from the arena tuning file alongside the archetypes.
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct ArenaTuning {
+    pub cave: CaveTuning,
+    pub room: RoomTuning,
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArenaMode {
+    #[default]
+    Cave,
+    Room,
+    Dungeon,
+    Mixed,
+}
 
-        for _ in 0..SMOOTHING_ITERATIONS {
-            let mut next_grid = grid.clone();
-            for y in 1..(height - 1) {
-                for x in 1..(width - 1) {
-                    let wall_neighbors = count_wall_neighbors(&grid, x, y, width, height);
+impl ArenaMode {
+    pub fn from_args_or_env() -> Self {
+        let raw = cli::arg_value(ARENA_MODE_ARG).or_else(|| std::env::var(ARENA_MODE_ENV_VAR).ok());
+        let Some(raw) = raw else {
+            return Self::default();
+        };
 
-                    if grid[y][x] == TileType::Wall {
-                        if wall_neighbors < FLOOR_CONVERSION_THRESHOLD {
-                            next_grid[y][x] = TileType::Floor;
+        Self::from_name(&raw).unwrap_or_else(|| {
+            warn!("Ignoring unknown arena mode '{raw}', expected cave, room, dungeon or mixed.");
+            Self::default()
+        })
+    }
+
+    /// Parses a mode's [`name`](Self::name), ignoring case and surrounding whitespace.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "cave" => Some(ArenaMode::Cave),
+            "room" => Some(ArenaMode::Room),
+            "dungeon" => Some(ArenaMode::Dungeon),
+            "mixed" => Some(ArenaMode::Mixed),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArenaMode::Cave => "cave",
+            ArenaMode::Room => "room",
+            ArenaMode::Dungeon => "dungeon",
+            ArenaMode::Mixed => "mixed",
+        }
+    }
+
+    /// The mode after this one, for cycling through them in the settings menu.
+    pub fn next(self) -> Self {
+        match self {
+            ArenaMode::Cave => ArenaMode::Room,
+            ArenaMode::Room => ArenaMode::Dungeon,
+            ArenaMode::Dungeon => ArenaMode::Mixed,
+            ArenaMode::Mixed => ArenaMode::Cave,
+        }
+    }
+
+    pub fn pick(self, rng: &mut impl Rng) -> ArenaKind {
+        match self {
+            ArenaMode::Cave => ArenaKind::Cave,
+            ArenaMode::Room => ArenaKind::Room,
+            ArenaMode::Dungeon => ArenaKind::Dungeon,
+            ArenaMode::Mixed => match rng.random_range(0..3) {
+                0 => ArenaKind::Cave,
+                1 => ArenaKind::Room,
+                _ => ArenaKind::Dungeon,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RegionMap {
+    labels: Vec<Vec<Option<usize>>>,
+    sizes: Vec<usize>,
+}
+
+impl RegionMap {
+    pub fn label(grid: &[Vec<TileType>]) -> Self {
+        let height = grid.len();
+        let width = grid.first().map_or(0, Vec::len);
+        let mut labels = vec![vec![None; width]; height];
+        let mut sizes = Vec::new();
+        let mut stack = Vec::new();
+
+        for start_y in 0..height {
+            for start_x in 0..width {
+                if grid[start_y][start_x] == TileType::Wall || labels[start_y][start_x].is_some() {
+                    continue;
+                }
+
+                let region = sizes.len();
+                let mut size = 0;
+                labels[start_y][start_x] = Some(region);
+                stack.push((start_x, start_y));
+
+                while let Some((x, y)) = stack.pop() {
+                    size += 1;
+                    for (nx, ny) in orthogonal_neighbors(x, y, width, height) {
+                        if grid[ny][nx] != TileType::Wall && labels[ny][nx].is_none() {
+                            labels[ny][nx] = Some(region);
+                            stack.push((nx, ny));
                         }
-                    } else if wall_neighbors >= WALL_CONVERSION_THRESHOLD {
-                        next_grid[y][x] = TileType::Wall;
                     }
                 }
+
+                sizes.push(size);
             }
-            grid = next_grid;
         }
 
+        Self { labels, sizes }
+    }
+
+    pub fn region_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.labels
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .flatten()
+    }
+
+    pub fn region_count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn region_size(&self, region: usize) -> usize {
+        self.sizes.get(region).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Resource)]
+pub struct ArenaGrid {
+    pub grid: Vec<Vec<TileType>>,
+    pub width: usize,
+    pub height: usize,
+    pub regions: RegionMap,
+    pub spawn: (usize, usize),
+    pub rooms: Vec<Room>,
+}
+
+impl ArenaGrid {
+    pub fn generate(
+        generator: &dyn ArenaGenerator,
+        width: usize,
+        height: usize,
+        rng: &mut StdRng,
+    ) -> Self {
+        let ArenaLayout {
+            tiles: mut grid,
+            spawn,
+            rooms,
+        } = generator.generate(width, height, rng);
+
         if height > 0 {
             for cell in grid[0].iter_mut() {
                 *cell = TileType::Wall;
@@ -91,106 +251,299 @@ impl ArenaGrid {
             }
         }
 
-        let center_x = width / 2;
-        let center_y = height / 2;
-        for _r in 0..=1 {
-            for c_offset in -1..=1 {
-                for r_offset in -1..=1 {
-                    let clear_x = (center_x as i32 + c_offset) as usize;
-                    let clear_y = (center_y as i32 + r_offset) as usize;
-                    if clear_x > 0 && clear_x < width - 1 && clear_y > 0 && clear_y < height - 1 {
-                        grid[clear_y][clear_x] = TileType::Floor;
-                    }
+        let (spawn_x, spawn_y) = spawn;
+        for c_offset in -1..=1 {
+            for r_offset in -1..=1 {
+                let clear_x = (spawn_x as i32 + c_offset) as usize;
+                let clear_y = (spawn_y as i32 + r_offset) as usize;
+                if clear_x > 0 && clear_x < width - 1 && clear_y > 0 && clear_y < height - 1 {
+                    grid[clear_y][clear_x] = TileType::Floor;
                 }
             }
         }
 
+        connect_regions(&mut grid, spawn);
+        let regions = RegionMap::label(&grid);
+
         Self {
             grid,
             width,
             height,
+            regions,
+            spawn,
+            rooms,
+        }
+    }
+
+    /// Wraps a finished tile layout, as is, without rooms. Spawns on the centre tile, or the open
+    /// tile closest to it if the centre is solid.
+    pub fn from_tiles(grid: Vec<Vec<TileType>>) -> Self {
+        let height = grid.len();
+        let width = grid.first().map_or(0, Vec::len);
+        let center = (width / 2, height / 2);
+        let spawn = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| !grid[y][x].is_solid())
+            .min_by_key(|&(x, y)| x.abs_diff(center.0).pow(2) + y.abs_diff(center.1).pow(2))
+            .unwrap_or(center);
+
+        Self {
+            regions: RegionMap::label(&grid),
+            grid,
+            width,
+            height,
+            spawn,
+            rooms: Vec::new(),
+        }
+    }
+
+    pub fn spawn_tile(&self) -> (usize, usize) {
+        self.spawn
+    }
+
+    pub fn room_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.rooms
+            .iter()
+            .position(|room| room.bounds.contains(x, y))
+    }
+
+    pub fn is_room_locked(&self, room: usize) -> bool {
+        self.rooms[room]
+            .doors
+            .iter()
+            .any(|&(x, y)| self.grid[y][x] == TileType::LockedDoor)
+    }
+
+    pub fn set_room_locked(&mut self, room: usize, locked: bool) {
+        let door_tile = if locked {
+            TileType::LockedDoor
+        } else {
+            TileType::Door
+        };
+        for &(x, y) in &self.rooms[room].doors {
+            self.grid[y][x] = door_tile;
+        }
+    }
+
+    pub fn is_reachable(&self, x: usize, y: usize) -> bool {
+        let (spawn_x, spawn_y) = self.spawn_tile();
+        match self.regions.region_at(spawn_x, spawn_y) {
+            Some(spawn_region) => self.regions.region_at(x, y) == Some(spawn_region),
+            None => false,
         }
     }
 }
 
-fn count_wall_neighbors(
-    grid: &[Vec<TileType>],
-    x: usize,
-    y: usize,
-    width: usize,
-    height: usize,
-) -> usize {
-    let mut count = 0;
-    for i in -1..=1 {
-        for j in -1..=1 {
-            if i == 0 && j == 0 {
-                continue;
+/// Joins every sizeable floor region to the one containing `spawn` by carving the shortest
+/// corridor through walls, then fills whatever is still disconnected.
+fn connect_regions(grid: &mut [Vec<TileType>], spawn: (usize, usize)) {
+    let regions = RegionMap::label(grid);
+    let Some(spawn_region) = regions.region_at(spawn.0, spawn.1) else {
+        return;
+    };
+
+    let height = grid.len();
+    let width = grid.first().map_or(0, Vec::len);
+    let mut connected = vec![vec![false; width]; height];
+    let mut region_connected = vec![false; regions.region_count()];
+
+    mark_region_connected(&regions, spawn_region, &mut connected);
+    region_connected[spawn_region] = true;
+
+    let mut parents = vec![vec![None; width]; height];
+    let mut frontier = std::collections::VecDeque::new();
+
+    loop {
+        for row in parents.iter_mut() {
+            row.fill(None);
+        }
+        frontier.clear();
+        for (y, row) in connected.iter().enumerate() {
+            for (x, &is_connected) in row.iter().enumerate() {
+                if is_connected {
+                    parents[y][x] = Some((x, y));
+                    frontier.push_back((x, y));
+                }
+            }
+        }
+
+        let mut reached = None;
+        while let Some((x, y)) = frontier.pop_front() {
+            if let Some(region) = regions.region_at(x, y)
+                && !region_connected[region]
+                && regions.region_size(region) >= MIN_CONNECTED_REGION_TILES
+            {
+                reached = Some((x, y, region));
+                break;
             }
-            let check_x = x as i32 + i;
-            let check_y = y as i32 + j;
 
-            if check_x >= 0 && check_x < width as i32 && check_y >= 0 && check_y < height as i32 {
-                if grid[check_y as usize][check_x as usize] == TileType::Wall {
-                    count += 1;
+            for (nx, ny) in orthogonal_neighbors(x, y, width, height) {
+                let is_interior = nx > 0 && nx < width - 1 && ny > 0 && ny < height - 1;
+                if is_interior && parents[ny][nx].is_none() {
+                    parents[ny][nx] = Some((x, y));
+                    frontier.push_back((nx, ny));
                 }
-            } else {
-                count += 1;
+            }
+        }
+
+        let Some((end_x, end_y, region)) = reached else {
+            break;
+        };
+
+        let (mut x, mut y) = (end_x, end_y);
+        while !connected[y][x] {
+            grid[y][x] = TileType::Floor;
+            connected[y][x] = true;
+            let Some(parent) = parents[y][x] else {
+                break;
+            };
+            (x, y) = parent;
+        }
+
+        mark_region_connected(&regions, region, &mut connected);
+        region_connected[region] = true;
+    }
+
+    for (y, row) in grid.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            if *cell != TileType::Wall && !connected[y][x] {
+                *cell = TileType::Wall;
+            }
+        }
+    }
+}
+
+fn mark_region_connected(regions: &RegionMap, region: usize, connected: &mut [Vec<bool>]) {
+    for (y, row) in connected.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            if regions.region_at(x, y) == Some(region) {
+                *cell = true;
             }
         }
     }
-    count
+}
+
+pub(crate) fn orthogonal_neighbors(
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    [(0, 1), (1, 0), (0, -1), (-1, 0)]
+        .into_iter()
+        .filter_map(move |(dx, dy)| {
+            let nx = x as i32 + dx;
+            let ny = y as i32 + dy;
+            (nx >= 0 && ny >= 0 && nx < width as i32 && ny < height as i32)
+                .then_some((nx as usize, ny as usize))
+        })
 }
 
 pub struct ArenaPlugin;
 
 impl Plugin for ArenaPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::InGame), setup_arena);
-    }
-}
-
-pub fn setup_arena(mut commands: Commands) {
-    let arena_grid = ArenaGrid::new(ARENA_WIDTH_TILES, ARENA_HEIGHT_TILES);
-
-    let total_arena_width_pixels = ARENA_WIDTH_TILES as f32 * TILE_SIZE;
-    let total_arena_height_pixels = ARENA_HEIGHT_TILES as f32 * TILE_SIZE;
-
-    commands.spawn((
-        ArenaFloor,
-        Sprite {
-            color: FLOOR_COLOR,
-            custom_size: Some(Vec2::new(
-                total_arena_width_pixels,
-                total_arena_height_pixels,
-            )),
-            ..default()
-        },
-        Transform::from_xyz(0.0, 0.0, -1.0),
-        Visibility::Visible,
-    ));
-
-    for y in 0..arena_grid.height {
-        for x in 0..arena_grid.width {
-            if arena_grid.grid[y][x] == TileType::Wall {
-                let pos_x =
-                    (x as f32 * TILE_SIZE) - (total_arena_width_pixels / 2.0) + (TILE_SIZE / 2.0);
-                let pos_y =
-                    (y as f32 * TILE_SIZE) - (total_arena_height_pixels / 2.0) + (TILE_SIZE / 2.0);
-
-                commands.spawn((
-                    Wall,
-                    Sprite {
-                        color: WALL_COLOR,
-                        custom_size: Some(Vec2::splat(TILE_SIZE)),
-                        ..default()
-                    },
-                    Transform::from_xyz(pos_x, pos_y, 0.0),
-                    Visibility::Visible,
-                ));
+        app.insert_resource(ArenaMode::from_args_or_env())
+            .add_systems(SetupFloor, setup_arena);
+    }
+}
+
+pub fn setup_arena(
+    mut commands: Commands,
+    run_seed: Res<RunSeed>,
+    floor: Res<CurrentFloor>,
+    arena_mode: Res<ArenaMode>,
+    archetypes: Res<Archetypes>,
+) {
+    let mut rng = run_seed.rng(RngStream::ArenaLayout, floor.0);
+    let generator = arena_mode.pick(&mut rng).generator(&archetypes.arena);
+    let arena_grid = ArenaGrid::generate(
+        generator.as_ref(),
+        ARENA_WIDTH_TILES,
+        ARENA_HEIGHT_TILES,
+        &mut rng,
+    );
+
+    commands.insert_resource(arena_grid);
+    info!("Arena setup complete ({} generator).", generator.name());
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    const SEEDS: [u64; 6] = [0, 1, 7, 42, 1234, 98765];
+
+    /// A walled grid with the `open` rectangles, given as `(x, y, width, height)`, carved out.
+    fn grid_with(
+        width: usize,
+        height: usize,
+        open: &[(usize, usize, usize, usize)],
+    ) -> Vec<Vec<TileType>> {
+        let mut grid = vec![vec![TileType::Wall; width]; height];
+        for &(x, y, w, h) in open {
+            for row in grid.iter_mut().skip(y).take(h) {
+                for cell in row.iter_mut().skip(x).take(w) {
+                    *cell = TileType::Floor;
+                }
             }
         }
+        grid
     }
 
-    commands.insert_resource(arena_grid);
-    info!("Arena setup complete with walls.");
+    #[test]
+    fn every_floor_tile_of_a_generated_arena_is_reachable() {
+        let tuning = Archetypes::shipped().arena;
+        for kind in [ArenaKind::Cave, ArenaKind::Room] {
+            for seed in SEEDS {
+                let arena_grid = ArenaGrid::generate(
+                    kind.generator(&tuning).as_ref(),
+                    ARENA_WIDTH_TILES,
+                    ARENA_HEIGHT_TILES,
+                    &mut StdRng::seed_from_u64(seed),
+                );
+                assert_eq!(arena_grid.regions.region_count(), 1, "{kind:?} seed {seed}");
+                for (y, row) in arena_grid.grid.iter().enumerate() {
+                    for (x, &tile) in row.iter().enumerate() {
+                        assert!(
+                            tile == TileType::Wall || arena_grid.is_reachable(x, y),
+                            "{kind:?} seed {seed}: ({x}, {y}) is cut off"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn small_pockets_are_filled() {
+        // A 3x3 pocket, under the size worth a corridor.
+        let mut grid = grid_with(20, 12, &[(1, 1, 8, 10), (13, 4, 3, 3)]);
+        let regions = RegionMap::label(&grid);
+        assert_eq!(regions.region_count(), 2);
+        assert_eq!(regions.region_size(regions.region_at(14, 5).unwrap()), 9);
+
+        connect_regions(&mut grid, (4, 5));
+        for row in &grid[4..7] {
+            assert_eq!(row[13..16], [TileType::Wall; 3]);
+        }
+        assert_eq!(RegionMap::label(&grid).region_count(), 1);
+    }
+
+    #[test]
+    fn large_pockets_get_a_corridor() {
+        let mut grid = grid_with(20, 12, &[(1, 1, 8, 10), (13, 4, 4, 4)]);
+        assert_eq!(RegionMap::label(&grid).region_count(), 2);
+
+        connect_regions(&mut grid, (4, 5));
+        let regions = RegionMap::label(&grid);
+        assert_eq!(regions.region_count(), 1);
+        assert_eq!(regions.region_at(14, 5), regions.region_at(4, 5));
+        assert!(
+            regions.region_size(0) > 8 * 10 + 4 * 4,
+            "joined by a carved corridor"
+        );
+    }
 }
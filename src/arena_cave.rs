@@ -0,0 +1,95 @@
+use noise::{NoiseFn, Perlin};
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
+
+use crate::arena::{ArenaGenerator, ArenaLayout, TileType};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CaveTuning {
+    /// Noise sampling step per tile; smaller values give larger, smoother caves.
+    pub noise_scale: f64,
+    /// Noise above this becomes wall.
+    pub noise_threshold: f64,
+    pub smoothing_iterations: usize,
+    /// A floor tile with at least this many wall neighbours becomes wall.
+    pub wall_conversion_threshold: usize,
+    /// A wall tile with fewer than this many wall neighbours becomes floor.
+    pub floor_conversion_threshold: usize,
+}
+
+/// Perlin noise thresholded into walls, then smoothed with a cellular automaton.
+pub struct CaveGenerator(pub CaveTuning);
+
+impl ArenaGenerator for CaveGenerator {
+    fn name(&self) -> &'static str {
+        "cave"
+    }
+
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> ArenaLayout {
+        let tuning = &self.0;
+        let mut grid = vec![vec![TileType::Floor; width]; height];
+        let perlin = Perlin::new(rng.random());
+
+        for (y, row) in grid.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                let noise_val =
+                    perlin.get([x as f64 * tuning.noise_scale, y as f64 * tuning.noise_scale]);
+
+                if noise_val > tuning.noise_threshold {
+                    *cell = TileType::Wall;
+                } else {
+                    *cell = TileType::Floor;
+                }
+            }
+        }
+
+        for _ in 0..tuning.smoothing_iterations {
+            let mut next_grid = grid.clone();
+            for y in 1..(height - 1) {
+                for x in 1..(width - 1) {
+                    let wall_neighbors = count_wall_neighbors(&grid, x, y, width, height);
+
+                    if grid[y][x] == TileType::Wall {
+                        if wall_neighbors < tuning.floor_conversion_threshold {
+                            next_grid[y][x] = TileType::Floor;
+                        }
+                    } else if wall_neighbors >= tuning.wall_conversion_threshold {
+                        next_grid[y][x] = TileType::Wall;
+                    }
+                }
+            }
+            grid = next_grid;
+        }
+
+        ArenaLayout::open(grid)
+    }
+}
+
+fn count_wall_neighbors(
+    grid: &[Vec<TileType>],
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> usize {
+    let mut count = 0;
+    for i in -1..=1 {
+        for j in -1..=1 {
+            if i == 0 && j == 0 {
+                continue;
+            }
+            let check_x = x as i32 + i;
+            let check_y = y as i32 + j;
+
+            if check_x >= 0 && check_x < width as i32 && check_y >= 0 && check_y < height as i32 {
+                if grid[check_y as usize][check_x as usize] == TileType::Wall {
+                    count += 1;
+                }
+            } else {
+                count += 1;
+            }
+        }
+    }
+    count
+}
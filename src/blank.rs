@@ -0,0 +1,336 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::action::Action;
+use crate::archetype::Archetypes;
+use crate::arena::ArenaGrid;
+use crate::collision::move_and_slide;
+use crate::combat::ImpactEffect;
+use crate::enemy::{Enemy, enemy_movement_system};
+use crate::player::{Faction, Player, Projectile, projectile_movement_system};
+use crate::simulation::FixedInput;
+
+/// Knockback is spread over this long instead of teleporting the enemy.
+const KNOCKBACK_SECONDS: f32 = 0.15;
+const ENEMY_FALLBACK_SIZE: f32 = 10.0;
+
+const BLANK_FLASH_SECONDS: f32 = 0.12;
+const BLANK_FLASH_COLOR: Color = Color::srgba(0.85, 0.95, 1.0, 0.35);
+
+/// Blank tuning, part of the player archetype.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BlankStats {
+    pub charges_per_floor: u32,
+    /// Enemy projectiles and enemies within this distance of the player are affected.
+    pub radius: f32,
+    pub knockback_distance: f32,
+    pub stun_seconds: f32,
+}
+
+/// Blanks the player has left. Refilled at the start of every floor.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blanks {
+    pub charges: u32,
+}
+
+impl Blanks {
+    pub fn new(stats: &BlankStats) -> Self {
+        Self {
+            charges: stats.charges_per_floor,
+        }
+    }
+
+    /// Uses up a charge if there is one.
+    pub fn spend(&mut self) -> bool {
+        let Some(charges) = self.charges.checked_sub(1) else {
+            return false;
+        };
+        self.charges = charges;
+        true
+    }
+}
+
+/// An enemy knocked back by a blank. Stunned enemies don't think, move or shoot; the knockback
+/// plays out over the first [`KNOCKBACK_SECONDS`].
+#[derive(Component, Debug, Clone)]
+pub struct Stunned {
+    pub timer: Timer,
+    knockback_velocity: Vec2,
+    knockback_remaining: f32,
+}
+
+impl Stunned {
+    pub fn new(seconds: f32, knockback: Vec2) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+            knockback_velocity: knockback / KNOCKBACK_SECONDS,
+            knockback_remaining: KNOCKBACK_SECONDS,
+        }
+    }
+}
+
+pub struct BlankPlugin;
+
+impl Plugin for BlankPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                use_blank_system.before(projectile_movement_system),
+                stunned_enemy_system.before(enemy_movement_system),
+            )
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn use_blank_system(
+    mut commands: Commands,
+    mut player_query: Query<(&Transform, &mut Blanks), With<Player>>,
+    projectile_query: Query<(Entity, &Transform, &Projectile)>,
+    enemy_query: Query<(Entity, &Transform), (With<Enemy>, Without<Player>)>,
+    actions: Res<FixedInput<Action>>,
+    archetypes: Res<Archetypes>,
+) {
+    if !actions.just_pressed(Action::Blank) {
+        return;
+    }
+    let Ok((player_transform, mut blanks)) = player_query.single_mut() else {
+        return;
+    };
+    if !blanks.spend() {
+        return;
+    }
+
+    let stats = &archetypes.player.blank;
+    let center = player_transform.translation.truncate();
+    let in_range =
+        |transform: &Transform| transform.translation.truncate().distance(center) <= stats.radius;
+
+    let mut cleared = 0;
+    for (entity, transform, projectile) in projectile_query.iter() {
+        if projectile.faction == Faction::Enemy && in_range(transform) {
+            commands.entity(entity).despawn();
+            cleared += 1;
+        }
+    }
+
+    let mut stunned = 0;
+    for (entity, transform) in enemy_query.iter() {
+        if !in_range(transform) {
+            continue;
+        }
+        let away = (transform.translation.truncate() - center)
+            .try_normalize()
+            .unwrap_or(Vec2::X);
+        commands.entity(entity).insert(Stunned::new(
+            stats.stun_seconds,
+            away * stats.knockback_distance,
+        ));
+        stunned += 1;
+    }
+
+    commands.spawn((
+        ImpactEffect {
+            lifetime: Timer::from_seconds(BLANK_FLASH_SECONDS, TimerMode::Once),
+        },
+        Sprite {
+            color: BLANK_FLASH_COLOR,
+            custom_size: Some(Vec2::splat(stats.radius * 2.0)),
+            ..default()
+        },
+        Transform::from_translation(center.extend(2.0)),
+        Visibility::Visible,
+    ));
+    info!(
+        "Blank cleared {cleared} projectiles and stunned {stunned} enemies, {} left.",
+        blanks.charges
+    );
+}
+
+fn stunned_enemy_system(
+    mut commands: Commands,
+    mut enemy_query: Query<(Entity, &mut Transform, &Sprite, &mut Stunned)>,
+    time: Res<Time>,
+    arena_grid: Res<ArenaGrid>,
+) {
+    for (entity, mut transform, sprite, mut stunned) in enemy_query.iter_mut() {
+        let knockback_time = stunned.knockback_remaining.min(time.delta_secs());
+        if knockback_time > 0.0 {
+            stunned.knockback_remaining -= knockback_time;
+            let size = sprite
+                .custom_size
+                .unwrap_or(Vec2::splat(ENEMY_FALLBACK_SIZE));
+            let result = move_and_slide(
+                &arena_grid,
+                transform.translation.truncate(),
+                size,
+                stunned.knockback_velocity * knockback_time,
+            );
+            transform.translation.x = result.position.x;
+            transform.translation.y = result.position.y;
+        }
+
+        stunned.timer.tick(time.delta());
+        if stunned.timer.finished() {
+            commands.entity(entity).remove::<Stunned>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::arena::TileType;
+    use crate::collision::tile_to_world;
+    use crate::player::ProjectileBundle;
+
+    const FRAME: f32 = 1.0 / 60.0;
+
+    struct Scene {
+        app: App,
+        player: Entity,
+        enemy: Entity,
+        near_enemy_shot: Entity,
+        far_enemy_shot: Entity,
+        near_player_shot: Entity,
+        player_position: Vec2,
+        enemy_start: Vec2,
+    }
+
+    fn scene(charges: u32) -> Scene {
+        let grid = vec![vec![TileType::Floor; 40]; 40];
+        let arena_grid = ArenaGrid::from_tiles(grid);
+        let player_position = tile_to_world(&arena_grid, 20, 20);
+        let archetypes = Archetypes::shipped();
+        let radius = archetypes.player.blank.radius;
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                FRAME,
+            )))
+            .insert_resource(arena_grid)
+            .init_resource::<FixedInput<Action>>()
+            .add_systems(Update, (use_blank_system, stunned_enemy_system).chain());
+
+        let world = app.world_mut();
+        let player = world
+            .spawn((
+                Player,
+                Transform::from_translation(player_position.extend(0.0)),
+                Blanks { charges },
+            ))
+            .id();
+        let enemy_start = player_position + Vec2::new(radius * 0.5, 0.0);
+        let enemy = world
+            .spawn((
+                Enemy,
+                Transform::from_translation(enemy_start.extend(0.0)),
+                Sprite {
+                    custom_size: Some(Vec2::splat(10.0)),
+                    ..default()
+                },
+            ))
+            .id();
+        let projectile = archetypes.projectile(Faction::Enemy).clone();
+        let mut shot = |faction, offset: Vec2| {
+            world
+                .spawn(ProjectileBundle::new(
+                    &projectile,
+                    faction,
+                    player_position + offset,
+                    Vec2::X,
+                    100.0,
+                    1.0,
+                ))
+                .id()
+        };
+        let near_enemy_shot = shot(Faction::Enemy, Vec2::new(0.0, radius * 0.5));
+        let far_enemy_shot = shot(Faction::Enemy, Vec2::new(0.0, radius * 1.5));
+        let near_player_shot = shot(Faction::Player, Vec2::new(0.0, -radius * 0.5));
+        world.insert_resource(archetypes);
+
+        Scene {
+            app,
+            player,
+            enemy,
+            near_enemy_shot,
+            far_enemy_shot,
+            near_player_shot,
+            player_position,
+            enemy_start,
+        }
+    }
+
+    fn press_blank(app: &mut App) {
+        app.world_mut()
+            .resource_mut::<FixedInput<Action>>()
+            .press(Action::Blank);
+        app.update();
+        let mut input = app.world_mut().resource_mut::<FixedInput<Action>>();
+        input.release(Action::Blank);
+        input.clear();
+    }
+
+    #[test]
+    fn blank_clears_enemy_fire_and_stuns_enemies_in_range() {
+        let Scene {
+            mut app,
+            player,
+            enemy,
+            near_enemy_shot,
+            far_enemy_shot,
+            near_player_shot,
+            player_position,
+            enemy_start,
+        } = scene(2);
+        let stats = Archetypes::shipped().player.blank;
+
+        press_blank(&mut app);
+        assert_eq!(app.world().get::<Blanks>(player).unwrap().charges, 1);
+        assert!(app.world().get_entity(near_enemy_shot).is_err());
+        assert!(app.world().get_entity(far_enemy_shot).is_ok());
+        assert!(app.world().get_entity(near_player_shot).is_ok());
+        assert!(app.world().get::<Stunned>(enemy).is_some());
+
+        let frames = (stats.stun_seconds / FRAME).ceil() as usize + 1;
+        for _ in 0..frames {
+            app.update();
+        }
+        assert!(app.world().get::<Stunned>(enemy).is_none());
+        let position = app
+            .world()
+            .get::<Transform>(enemy)
+            .unwrap()
+            .translation
+            .truncate();
+        assert!(position.y == player_position.y);
+        assert!(
+            (position.x - enemy_start.x - stats.knockback_distance).abs() < 1e-2,
+            "knocked back to {position}"
+        );
+    }
+
+    #[test]
+    fn no_blank_without_charges() {
+        let Scene {
+            mut app,
+            player,
+            enemy,
+            near_enemy_shot,
+            ..
+        } = scene(0);
+
+        press_blank(&mut app);
+        assert_eq!(app.world().get::<Blanks>(player).unwrap().charges, 0);
+        assert!(app.world().get_entity(near_enemy_shot).is_ok());
+        assert!(app.world().get::<Stunned>(enemy).is_none());
+    }
+}
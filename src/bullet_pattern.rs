@@ -0,0 +1,940 @@
+use std::f32::consts::TAU;
+use std::fmt;
+use std::sync::Arc;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use ron::extensions::Extensions;
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::archetype::Archetypes;
+use crate::blank::Stunned;
+use crate::enemy_ai::{AiState, EnemyAi, Perception};
+use crate::player::{Faction, Player, Projectile, ProjectileBundle, projectile_movement_system};
+
+const DEFAULT_BULLET_DAMAGE: f32 = 10.0;
+/// Nested emitters deeper than this are rejected so a typo can't flood the arena.
+const MAX_EMITTER_DEPTH: usize = 4;
+
+/// Where a pattern's angles are measured from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Aim {
+    /// Towards the player, or along the heading when there is no player.
+    #[default]
+    Player,
+    /// Along the emitter's facing, or a bullet's direction of travel for nested emitters.
+    Heading,
+}
+
+/// One bullet as written in a pattern file. Speeds are in world units per second and angles in
+/// degrees, positive counter-clockwise. Optional fields may be written without `Some(..)`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BulletDef {
+    pub speed: f32,
+    #[serde(default)]
+    pub acceleration: f32,
+    #[serde(default)]
+    pub min_speed: f32,
+    #[serde(default)]
+    pub max_speed: Option<f32>,
+    #[serde(default)]
+    pub angular_velocity: f32,
+    #[serde(default)]
+    pub damage: Option<f32>,
+    #[serde(default)]
+    pub lifetime: Option<f32>,
+    /// A pattern this bullet runs from its own position while it flies.
+    #[serde(default)]
+    pub emitter: Option<PatternDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum PatternAction {
+    /// `count` bullets evenly around a full circle, all at once.
+    Ring {
+        count: u32,
+        #[serde(default)]
+        offset_degrees: f32,
+        #[serde(default)]
+        aim: Aim,
+        bullet: BulletDef,
+    },
+    /// `count` volleys `interval` seconds apart, each turned `step_degrees` from the last, with
+    /// `arms` bullets evenly spaced per volley.
+    Spiral {
+        count: u32,
+        interval: f32,
+        step_degrees: f32,
+        #[serde(default = "default_arms")]
+        arms: u32,
+        #[serde(default)]
+        offset_degrees: f32,
+        #[serde(default)]
+        aim: Aim,
+        bullet: BulletDef,
+    },
+    /// `count` shots at the player `interval` seconds apart, each a fan of `pellets` bullets
+    /// across `spread_degrees`.
+    AimedBurst {
+        count: u32,
+        interval: f32,
+        #[serde(default = "default_pellets")]
+        pellets: u32,
+        #[serde(default)]
+        spread_degrees: f32,
+        bullet: BulletDef,
+    },
+    /// `count` shots `interval` seconds apart sweeping back and forth by `amplitude_degrees`,
+    /// completing one sweep every `period` shots.
+    Wave {
+        count: u32,
+        interval: f32,
+        amplitude_degrees: f32,
+        period: u32,
+        #[serde(default)]
+        offset_degrees: f32,
+        #[serde(default)]
+        aim: Aim,
+        bullet: BulletDef,
+    },
+    Wait(f32),
+    Repeat {
+        times: u32,
+        actions: Vec<PatternAction>,
+    },
+}
+
+fn default_arms() -> u32 {
+    1
+}
+
+fn default_pellets() -> u32 {
+    1
+}
+
+/// A pattern file: a sequence of actions run one after another.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PatternDef {
+    #[serde(default)]
+    pub looping: bool,
+    pub actions: Vec<PatternAction>,
+}
+
+#[derive(Debug)]
+pub enum PatternError {
+    Parse(ron::error::SpannedError),
+    Io(std::io::Error),
+    ZeroCount(&'static str),
+    NegativeTime(&'static str, f32),
+    InvalidBullet(String),
+    EmptyLoop,
+    TooDeep,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Parse(error) => write!(f, "invalid pattern file: {error}"),
+            PatternError::Io(error) => write!(f, "could not read pattern file: {error}"),
+            PatternError::ZeroCount(action) => write!(f, "{action} needs a count of at least 1"),
+            PatternError::NegativeTime(action, value) => {
+                write!(f, "{action} has a negative duration ({value})")
+            }
+            PatternError::InvalidBullet(reason) => write!(f, "invalid bullet: {reason}"),
+            PatternError::EmptyLoop => write!(f, "a looping pattern must take some time"),
+            PatternError::TooDeep => {
+                write!(f, "emitters are nested more than {MAX_EMITTER_DEPTH} deep")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl From<std::io::Error> for PatternError {
+    fn from(error: std::io::Error) -> Self {
+        PatternError::Io(error)
+    }
+}
+
+/// A [`BulletDef`] checked and ready to spawn.
+#[derive(Debug)]
+pub struct Bullet {
+    pub speed: f32,
+    pub damage: f32,
+    pub lifetime: Option<f32>,
+    pub motion: Option<BulletMotion>,
+    pub emitter: Option<Arc<Timeline>>,
+}
+
+/// A single bullet leaving the emitter `time` seconds into the pattern, `angle` radians from
+/// the direction given by `aim`.
+#[derive(Debug, Clone)]
+pub struct ScheduledShot {
+    pub time: f32,
+    pub angle: f32,
+    pub aim: Aim,
+    pub bullet: Arc<Bullet>,
+}
+
+/// A pattern flattened into the shots it fires, in firing order.
+#[derive(Debug)]
+pub struct Timeline {
+    pub shots: Vec<ScheduledShot>,
+    pub duration: f32,
+    pub looping: bool,
+}
+
+impl Timeline {
+    pub fn compile(pattern: &PatternDef) -> Result<Self, PatternError> {
+        Self::compile_nested(pattern, 0)
+    }
+
+    fn compile_nested(pattern: &PatternDef, depth: usize) -> Result<Self, PatternError> {
+        if depth > MAX_EMITTER_DEPTH {
+            return Err(PatternError::TooDeep);
+        }
+
+        let mut shots = Vec::new();
+        let duration = schedule(&pattern.actions, 0.0, depth, &mut shots)?;
+        if pattern.looping && duration <= 0.0 {
+            return Err(PatternError::EmptyLoop);
+        }
+        shots.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(Self {
+            shots,
+            duration,
+            looping: pattern.looping,
+        })
+    }
+}
+
+/// Appends the shots of `actions` starting at `start` and returns the time they finish.
+fn schedule(
+    actions: &[PatternAction],
+    start: f32,
+    depth: usize,
+    shots: &mut Vec<ScheduledShot>,
+) -> Result<f32, PatternError> {
+    let mut time = start;
+
+    for action in actions {
+        match action {
+            PatternAction::Ring {
+                count,
+                offset_degrees,
+                aim,
+                bullet,
+            } => {
+                let bullet = compile_bullet(bullet, depth)?;
+                let count = require_count("Ring", *count)?;
+                for index in 0..count {
+                    let angle = offset_degrees.to_radians() + TAU * index as f32 / count as f32;
+                    push_shot(shots, time, angle, *aim, &bullet);
+                }
+            }
+            PatternAction::Spiral {
+                count,
+                interval,
+                step_degrees,
+                arms,
+                offset_degrees,
+                aim,
+                bullet,
+            } => {
+                let bullet = compile_bullet(bullet, depth)?;
+                let count = require_count("Spiral", *count)?;
+                let arms = require_count("Spiral arms", *arms)?;
+                let interval = require_time("Spiral", *interval)?;
+                for index in 0..count {
+                    for arm in 0..arms {
+                        let angle = (offset_degrees + step_degrees * index as f32).to_radians()
+                            + TAU * arm as f32 / arms as f32;
+                        push_shot(shots, time + interval * index as f32, angle, *aim, &bullet);
+                    }
+                }
+                time += interval * count as f32;
+            }
+            PatternAction::AimedBurst {
+                count,
+                interval,
+                pellets,
+                spread_degrees,
+                bullet,
+            } => {
+                let bullet = compile_bullet(bullet, depth)?;
+                let count = require_count("AimedBurst", *count)?;
+                let pellets = require_count("AimedBurst pellets", *pellets)?;
+                let interval = require_time("AimedBurst", *interval)?;
+                for index in 0..count {
+                    for pellet in 0..pellets {
+                        let angle = if pellets == 1 {
+                            0.0
+                        } else {
+                            spread_degrees.to_radians()
+                                * (pellet as f32 / (pellets - 1) as f32 - 0.5)
+                        };
+                        push_shot(
+                            shots,
+                            time + interval * index as f32,
+                            angle,
+                            Aim::Player,
+                            &bullet,
+                        );
+                    }
+                }
+                time += interval * count as f32;
+            }
+            PatternAction::Wave {
+                count,
+                interval,
+                amplitude_degrees,
+                period,
+                offset_degrees,
+                aim,
+                bullet,
+            } => {
+                let bullet = compile_bullet(bullet, depth)?;
+                let count = require_count("Wave", *count)?;
+                let period = require_count("Wave period", *period)?;
+                let interval = require_time("Wave", *interval)?;
+                for index in 0..count {
+                    let phase = TAU * index as f32 / period as f32;
+                    let angle = (offset_degrees + amplitude_degrees * phase.sin()).to_radians();
+                    push_shot(shots, time + interval * index as f32, angle, *aim, &bullet);
+                }
+                time += interval * count as f32;
+            }
+            PatternAction::Wait(seconds) => {
+                time += require_time("Wait", *seconds)?;
+            }
+            PatternAction::Repeat { times, actions } => {
+                for _ in 0..require_count("Repeat", *times)? {
+                    time = schedule(actions, time, depth, shots)?;
+                }
+            }
+        }
+    }
+
+    Ok(time)
+}
+
+fn push_shot(
+    shots: &mut Vec<ScheduledShot>,
+    time: f32,
+    angle: f32,
+    aim: Aim,
+    bullet: &Arc<Bullet>,
+) {
+    shots.push(ScheduledShot {
+        time,
+        angle,
+        aim,
+        bullet: bullet.clone(),
+    });
+}
+
+fn require_count(action: &'static str, count: u32) -> Result<u32, PatternError> {
+    if count == 0 {
+        Err(PatternError::ZeroCount(action))
+    } else {
+        Ok(count)
+    }
+}
+
+fn require_time(action: &'static str, seconds: f32) -> Result<f32, PatternError> {
+    if seconds < 0.0 || !seconds.is_finite() {
+        Err(PatternError::NegativeTime(action, seconds))
+    } else {
+        Ok(seconds)
+    }
+}
+
+fn compile_bullet(def: &BulletDef, depth: usize) -> Result<Arc<Bullet>, PatternError> {
+    if def.speed < 0.0 {
+        return Err(PatternError::InvalidBullet(format!(
+            "speed must not be negative, got {}",
+            def.speed
+        )));
+    }
+    if let Some(max_speed) = def.max_speed
+        && max_speed < def.min_speed
+    {
+        return Err(PatternError::InvalidBullet(format!(
+            "max_speed {max_speed} is below min_speed {}",
+            def.min_speed
+        )));
+    }
+    if let Some(lifetime) = def.lifetime
+        && lifetime <= 0.0
+    {
+        return Err(PatternError::InvalidBullet(format!(
+            "lifetime must be positive, got {lifetime}"
+        )));
+    }
+
+    let motion = (def.acceleration != 0.0 || def.angular_velocity != 0.0).then(|| BulletMotion {
+        acceleration: def.acceleration,
+        min_speed: def.min_speed,
+        max_speed: def.max_speed.unwrap_or(f32::INFINITY),
+        angular_velocity: def.angular_velocity.to_radians(),
+    });
+    let emitter = def
+        .emitter
+        .as_ref()
+        .map(|pattern| Timeline::compile_nested(pattern, depth + 1).map(Arc::new))
+        .transpose()?;
+
+    Ok(Arc::new(Bullet {
+        speed: def.speed,
+        damage: def.damage.unwrap_or(DEFAULT_BULLET_DAMAGE),
+        lifetime: def.lifetime,
+        motion,
+        emitter,
+    }))
+}
+
+#[derive(Asset, TypePath, Debug)]
+pub struct BulletPattern {
+    pub timeline: Arc<Timeline>,
+}
+
+impl BulletPattern {
+    pub fn from_ron(bytes: &[u8]) -> Result<Self, PatternError> {
+        let def: PatternDef = ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_bytes(bytes)
+            .map_err(PatternError::Parse)?;
+        Ok(Self {
+            timeline: Arc::new(Timeline::compile(&def)?),
+        })
+    }
+}
+
+#[derive(Default)]
+struct BulletPatternLoader;
+
+impl AssetLoader for BulletPatternLoader {
+    type Asset = BulletPattern;
+    type Settings = ();
+    type Error = PatternError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<BulletPattern, PatternError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        BulletPattern::from_ron(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pattern.ron"]
+    }
+}
+
+/// Per-bullet speed and turning curves applied every frame on top of [`Projectile`].
+#[derive(Component, Debug, Clone, Copy)]
+pub struct BulletMotion {
+    pub acceleration: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    /// Radians per second, positive counter-clockwise.
+    pub angular_velocity: f32,
+}
+
+/// A bullet fired this frame by a [`BulletEmitter`].
+#[derive(Debug)]
+pub struct EmittedBullet {
+    pub direction: Vec2,
+    pub bullet: Arc<Bullet>,
+}
+
+/// Plays a [`Timeline`] back over time, firing each shot once its time comes round.
+#[derive(Component)]
+pub struct BulletEmitter {
+    timeline: Arc<Timeline>,
+    faction: Faction,
+    elapsed: f32,
+    next_shot: usize,
+}
+
+impl BulletEmitter {
+    pub fn new(timeline: Arc<Timeline>, faction: Faction) -> Self {
+        Self {
+            timeline,
+            faction,
+            elapsed: 0.0,
+            next_shot: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.timeline.looping && self.next_shot >= self.timeline.shots.len()
+    }
+
+    /// Advances by `delta` seconds and returns the bullets due. `target` is the direction to
+    /// the player, if there is one.
+    pub fn advance(
+        &mut self,
+        delta: f32,
+        heading: Vec2,
+        target: Option<Vec2>,
+    ) -> Vec<EmittedBullet> {
+        let mut fired = Vec::new();
+        self.elapsed += delta;
+
+        loop {
+            while let Some(shot) = self.timeline.shots.get(self.next_shot)
+                && shot.time <= self.elapsed
+            {
+                let base = match shot.aim {
+                    Aim::Player => target.unwrap_or(heading),
+                    Aim::Heading => heading,
+                };
+                fired.push(EmittedBullet {
+                    direction: Vec2::from_angle(shot.angle).rotate(base),
+                    bullet: shot.bullet.clone(),
+                });
+                self.next_shot += 1;
+            }
+
+            if !self.timeline.looping || self.elapsed < self.timeline.duration {
+                break;
+            }
+            self.elapsed -= self.timeline.duration;
+            self.next_shot = 0;
+        }
+
+        fired
+    }
+}
+
+/// Waits for a pattern asset to finish loading, then swaps itself for a [`BulletEmitter`].
+#[derive(Component)]
+pub struct PendingPattern(pub Handle<BulletPattern>);
+
+pub struct BulletPatternPlugin;
+
+impl Plugin for BulletPatternPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<BulletPattern>()
+            .init_asset_loader::<BulletPatternLoader>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    attach_loaded_patterns,
+                    bullet_emitter_system,
+                    bullet_motion_system,
+                )
+                    .chain()
+                    .before(projectile_movement_system)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+fn attach_loaded_patterns(
+    mut commands: Commands,
+    pending_query: Query<(Entity, &PendingPattern)>,
+    patterns: Res<Assets<BulletPattern>>,
+) {
+    for (entity, pending) in pending_query.iter() {
+        if let Some(pattern) = patterns.get(&pending.0) {
+            commands
+                .entity(entity)
+                .remove::<PendingPattern>()
+                .insert(BulletEmitter::new(pattern.timeline.clone(), Faction::Enemy));
+        }
+    }
+}
+
+/// Fires due shots from every emitter. Enemy emitters only run while the enemy is engaging the
+/// player and not stunned; emitters riding on bullets always run.
+#[allow(clippy::type_complexity)]
+pub fn bullet_emitter_system(
+    mut commands: Commands,
+    mut emitter_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut BulletEmitter,
+            Option<&Projectile>,
+            Option<(&EnemyAi, &Perception)>,
+        ),
+        Without<Stunned>,
+    >,
+    player_query: Query<&Transform, With<Player>>,
+    time: Res<Time>,
+    archetypes: Res<Archetypes>,
+) {
+    let player_pos = player_query
+        .single()
+        .ok()
+        .map(|transform| transform.translation.truncate());
+
+    for (entity, transform, mut emitter, projectile, enemy) in emitter_query.iter_mut() {
+        let engaged = enemy.is_none_or(|(ai, perception)| {
+            matches!(ai.state, AiState::Chase | AiState::Attack) && perception.sees_player
+        });
+        if !engaged {
+            continue;
+        }
+
+        let origin = transform.translation.truncate();
+        let heading = projectile
+            .map(|projectile| projectile.direction)
+            .unwrap_or_else(|| (transform.rotation * Vec3::X).truncate())
+            .normalize_or_zero();
+        let target = player_pos
+            .map(|player_pos| (player_pos - origin).normalize_or_zero())
+            .filter(|direction| *direction != Vec2::ZERO);
+
+        let faction = emitter.faction;
+        for emitted in emitter.advance(time.delta_secs(), heading, target) {
+            spawn_bullet(&mut commands, &archetypes, faction, origin, &emitted);
+        }
+
+        if emitter.is_finished() {
+            commands.entity(entity).remove::<BulletEmitter>();
+        }
+    }
+}
+
+fn spawn_bullet(
+    commands: &mut Commands,
+    archetypes: &Archetypes,
+    faction: Faction,
+    origin: Vec2,
+    emitted: &EmittedBullet,
+) {
+    let bullet = &emitted.bullet;
+    let mut projectile = ProjectileBundle::new(
+        archetypes.projectile(faction),
+        faction,
+        origin,
+        emitted.direction,
+        bullet.speed,
+        bullet.damage,
+    );
+    if let Some(lifetime) = bullet.lifetime {
+        projectile = projectile.with_lifetime(lifetime);
+    }
+
+    let mut entity = commands.spawn(projectile);
+    if let Some(motion) = bullet.motion {
+        entity.insert(motion);
+    }
+    if let Some(timeline) = &bullet.emitter {
+        entity.insert(BulletEmitter::new(timeline.clone(), faction));
+    }
+}
+
+pub fn bullet_motion_system(
+    mut bullet_query: Query<(&mut Projectile, &mut Transform, &BulletMotion)>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+
+    for (mut projectile, mut transform, motion) in bullet_query.iter_mut() {
+        projectile.speed = (projectile.speed + motion.acceleration * delta)
+            .clamp(motion.min_speed, motion.max_speed);
+
+        if motion.angular_velocity != 0.0 {
+            projectile.direction =
+                Vec2::from_angle(motion.angular_velocity * delta).rotate(projectile.direction);
+            transform.rotation =
+                Quat::from_rotation_z(projectile.direction.y.atan2(projectile.direction.x));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::arena::{ArenaGrid, TileType};
+    use crate::combat::ProjectileImpactEvent;
+
+    const FRAME_SECONDS: f32 = 1.0 / 60.0;
+
+    /// A bullet that appeared during a headless run.
+    #[derive(Debug)]
+    struct SpawnRecord {
+        time: f32,
+        position: Vec2,
+        angle_degrees: f32,
+        speed: f32,
+    }
+
+    fn open_arena() -> ArenaGrid {
+        let grid = vec![vec![TileType::Floor; 40]; 40];
+        ArenaGrid::from_tiles(grid)
+    }
+
+    fn timeline(ron: &str) -> Arc<Timeline> {
+        BulletPattern::from_ron(ron.as_bytes())
+            .unwrap_or_else(|error| panic!("{error}"))
+            .timeline
+    }
+
+    /// Runs an emitter at the origin of an open arena, facing +X with the player at +X, for
+    /// `seconds` of fixed frames, recording every projectile in the order it appeared.
+    fn run_headless(ron: &str, seconds: f32) -> Vec<SpawnRecord> {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                FRAME_SECONDS,
+            )))
+            .add_event::<ProjectileImpactEvent>()
+            .insert_resource(open_arena())
+            .insert_resource(Archetypes::shipped())
+            .add_systems(
+                Update,
+                (
+                    bullet_emitter_system,
+                    bullet_motion_system,
+                    projectile_movement_system,
+                )
+                    .chain(),
+            );
+
+        app.world_mut().spawn((
+            Player,
+            Transform::from_translation(Vec3::new(100.0, 0.0, 0.0)),
+        ));
+        app.world_mut().spawn((
+            Transform::default(),
+            BulletEmitter::new(timeline(ron), Faction::Enemy),
+        ));
+
+        let mut seen = HashSet::new();
+        let mut records = Vec::new();
+        let frames = (seconds / FRAME_SECONDS).ceil() as usize + 1;
+        for _ in 0..frames {
+            app.update();
+
+            let time = app.world().resource::<Time>().elapsed_secs();
+            let mut query = app.world_mut().query::<(Entity, &Transform, &Projectile)>();
+            let mut new: Vec<_> = query
+                .iter(app.world())
+                .filter(|(entity, _, _)| !seen.contains(entity))
+                .map(|(entity, transform, projectile)| {
+                    (
+                        entity,
+                        SpawnRecord {
+                            time,
+                            position: transform.translation.truncate(),
+                            angle_degrees: projectile
+                                .direction
+                                .y
+                                .atan2(projectile.direction.x)
+                                .to_degrees(),
+                            speed: projectile.speed,
+                        },
+                    )
+                })
+                .collect();
+            new.sort_by_key(|(entity, _)| *entity);
+            for (entity, record) in new {
+                seen.insert(entity);
+                records.push(record);
+            }
+        }
+
+        records
+    }
+
+    fn assert_angle(actual: f32, expected: f32) {
+        let difference = (actual - expected + 540.0).rem_euclid(360.0) - 180.0;
+        assert!(
+            difference.abs() < 0.01,
+            "expected {expected} degrees, got {actual}"
+        );
+    }
+
+    #[test]
+    fn bundled_patterns_compile() {
+        let ron = include_str!("../assets/patterns/boss.pattern.ron");
+        if let Err(error) = BulletPattern::from_ron(ron.as_bytes()) {
+            panic!("boss: {error}");
+        }
+    }
+
+    #[test]
+    fn ring_fires_evenly_around_the_player_direction() {
+        let records = run_headless(
+            "(actions: [Ring(count: 4, offset_degrees: 45, bullet: (speed: 100))])",
+            0.1,
+        );
+
+        assert_eq!(records.len(), 4);
+        for (record, expected) in records.iter().zip([45.0, 135.0, -135.0, -45.0]) {
+            assert_angle(record.angle_degrees, expected);
+            assert_eq!(record.speed, 100.0);
+        }
+    }
+
+    #[test]
+    fn spiral_turns_each_volley_on_schedule() {
+        let records = run_headless(
+            "(actions: [Spiral(count: 5, interval: 0.1, step_degrees: 15, arms: 2, aim: Heading, \
+             bullet: (speed: 80))])",
+            0.6,
+        );
+
+        assert_eq!(records.len(), 10);
+        for (volley, pair) in records.chunks(2).enumerate() {
+            let expected_time = volley as f32 * 0.1;
+            for record in pair {
+                assert!(
+                    record.time >= expected_time - 1e-4
+                        && record.time < expected_time + FRAME_SECONDS + 1e-4,
+                    "volley {volley} fired at {}",
+                    record.time
+                );
+            }
+            assert_angle(pair[0].angle_degrees, volley as f32 * 15.0);
+            assert_angle(pair[1].angle_degrees, volley as f32 * 15.0 + 180.0);
+        }
+    }
+
+    #[test]
+    fn aimed_burst_and_wave_follow_their_curves() {
+        let records = run_headless(
+            "(actions: [
+                AimedBurst(count: 2, interval: 0.2, pellets: 3, spread_degrees: 20, \
+                    bullet: (speed: 150)),
+                Wave(count: 4, interval: 0.05, amplitude_degrees: 30, period: 4, \
+                    bullet: (speed: 90)),
+            ])",
+            1.0,
+        );
+
+        let angles: Vec<f32> = records.iter().map(|record| record.angle_degrees).collect();
+        assert_eq!(angles.len(), 10);
+        for (actual, expected) in angles
+            .iter()
+            .zip([-10.0, 0.0, 10.0, -10.0, 0.0, 10.0, 0.0, 30.0, 0.0, -30.0])
+        {
+            assert_angle(*actual, expected);
+        }
+        assert!(records[6].time >= 0.4 - 1e-4);
+    }
+
+    #[test]
+    fn nested_emitters_fire_from_their_bullet() {
+        let records = run_headless(
+            "(actions: [Ring(count: 1, aim: Heading, bullet: (
+                speed: 60,
+                lifetime: 1.0,
+                emitter: (actions: [Wait(0.5), Ring(count: 3, aim: Heading, \
+                    bullet: (speed: 30))]),
+            ))])",
+            0.7,
+        );
+
+        assert_eq!(records.len(), 4);
+        let children = &records[1..];
+        for (child, expected) in children.iter().zip([0.0, 120.0, -120.0]) {
+            assert!(child.time >= 0.5);
+            assert!(
+                child.position.x > 25.0,
+                "child fired from {:?}",
+                child.position
+            );
+            assert_angle(child.angle_degrees, expected);
+            assert_eq!(child.speed, 30.0);
+        }
+    }
+
+    #[test]
+    fn looping_patterns_repeat_and_one_shots_finish() {
+        let mut looping = BulletEmitter::new(
+            timeline("(looping: true, actions: [Ring(count: 2, bullet: (speed: 10)), Wait(0.5)])"),
+            Faction::Enemy,
+        );
+        let fired: usize = (0..10)
+            .map(|_| looping.advance(0.25, Vec2::X, None).len())
+            .sum();
+        assert_eq!(fired, 12);
+        assert!(!looping.is_finished());
+
+        let mut once = BulletEmitter::new(
+            timeline(
+                "(actions: [Repeat(times: 3, actions: [Ring(count: 1, bullet: (speed: 10)), Wait(0.1)])])",
+            ),
+            Faction::Enemy,
+        );
+        assert_eq!(once.advance(1.0, Vec2::X, None).len(), 3);
+        assert!(once.is_finished());
+    }
+
+    #[test]
+    fn motion_curves_accelerate_and_turn_bullets() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .add_systems(Update, bullet_motion_system);
+        let bullet = app
+            .world_mut()
+            .spawn((
+                ProjectileBundle::new(
+                    Archetypes::shipped().projectile(Faction::Enemy),
+                    Faction::Enemy,
+                    Vec2::ZERO,
+                    Vec2::X,
+                    100.0,
+                    1.0,
+                ),
+                BulletMotion {
+                    acceleration: -600.0,
+                    min_speed: 40.0,
+                    max_speed: f32::INFINITY,
+                    angular_velocity: 90f32.to_radians(),
+                },
+            ))
+            .id();
+        for _ in 0..11 {
+            app.update();
+        }
+
+        let projectile = app.world().get::<Projectile>(bullet).unwrap();
+        assert_eq!(projectile.speed, 40.0);
+        assert_angle(
+            projectile
+                .direction
+                .y
+                .atan2(projectile.direction.x)
+                .to_degrees(),
+            90.0,
+        );
+    }
+
+    #[test]
+    fn invalid_patterns_report_what_is_wrong() {
+        let error = BulletPattern::from_ron(b"(actions: [Ring(count: 0, bullet: (speed: 1))])")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Ring needs a count of at least 1");
+
+        let error = BulletPattern::from_ron(
+            b"(looping: true, actions: [Ring(count: 1, bullet: (speed: 1))])",
+        )
+        .unwrap_err();
+        assert!(matches!(error, PatternError::EmptyLoop));
+
+        let error = BulletPattern::from_ron(b"(actions: [Spin(count: 1)])").unwrap_err();
+        assert!(matches!(error, PatternError::Parse(_)), "{error}");
+    }
+}
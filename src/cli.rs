@@ -0,0 +1,17 @@
+/// Looks up `--name value` or `--name=value` among the process arguments.
+pub fn arg_value(name: &str) -> Option<String> {
+    find_arg_value(std::env::args().skip(1), name)
+}
+
+fn find_arg_value(mut args: impl Iterator<Item = String>, name: &str) -> Option<String> {
+    let prefix = format!("{name}=");
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
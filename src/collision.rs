@@ -0,0 +1,561 @@
+use bevy::prelude::*;
+
+use crate::arena::{ArenaGrid, TILE_SIZE};
+
+/// Gap kept between a mover and the tile face it was stopped against, so the next query does
+/// not count the touching face as an overlap.
+const CONTACT_SKIN: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveResult {
+    pub position: Vec2,
+    pub blocked_x: bool,
+    pub blocked_y: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub point: Vec2,
+    /// Outward normal of the surface that was hit. For rays that start inside a solid this is
+    /// the reverse of the ray direction.
+    pub normal: Vec2,
+    pub distance: f32,
+}
+
+/// World position of the bottom-left corner of tile (0, 0).
+fn arena_origin(arena_grid: &ArenaGrid) -> Vec2 {
+    -Vec2::new(arena_grid.width as f32, arena_grid.height as f32) * TILE_SIZE / 2.0
+}
+
+pub fn world_to_tile(arena_grid: &ArenaGrid, position: Vec2) -> IVec2 {
+    ((position - arena_origin(arena_grid)) / TILE_SIZE)
+        .floor()
+        .as_ivec2()
+}
+
+pub fn tile_to_world(arena_grid: &ArenaGrid, x: usize, y: usize) -> Vec2 {
+    arena_origin(arena_grid) + (Vec2::new(x as f32, y as f32) + 0.5) * TILE_SIZE
+}
+
+pub fn check_aabb_collision(pos1: Vec2, size1: Vec2, pos2: Vec2, size2: Vec2) -> bool {
+    let half_size1 = size1 / 2.0;
+    let half_size2 = size2 / 2.0;
+
+    let min1 = pos1 - half_size1;
+    let max1 = pos1 + half_size1;
+    let min2 = pos2 - half_size2;
+    let max2 = pos2 + half_size2;
+
+    (min1.x < max2.x && max1.x > min2.x) && (min1.y < max2.y && max1.y > min2.y)
+}
+
+/// Tiles outside the grid count as solid so nothing can leave the arena.
+pub fn is_solid_tile(arena_grid: &ArenaGrid, tile: IVec2) -> bool {
+    if tile.x < 0 || tile.y < 0 {
+        return true;
+    }
+    arena_grid
+        .grid
+        .get(tile.y as usize)
+        .and_then(|row| row.get(tile.x as usize))
+        .is_none_or(|tile_type| tile_type.is_solid())
+}
+
+/// Iterates the solid tiles whose area overlaps the world-space box `min..max`, without
+/// allocating.
+pub struct SolidTiles<'a> {
+    arena_grid: &'a ArenaGrid,
+    min: IVec2,
+    max: IVec2,
+    cursor: IVec2,
+}
+
+impl Iterator for SolidTiles<'_> {
+    type Item = IVec2;
+
+    fn next(&mut self) -> Option<IVec2> {
+        while self.cursor.y <= self.max.y {
+            let tile = self.cursor;
+            self.cursor.x += 1;
+            if self.cursor.x > self.max.x {
+                self.cursor.x = self.min.x;
+                self.cursor.y += 1;
+            }
+            if is_solid_tile(self.arena_grid, tile) {
+                return Some(tile);
+            }
+        }
+        None
+    }
+}
+
+pub fn solid_tiles_in(arena_grid: &ArenaGrid, min: Vec2, max: Vec2) -> SolidTiles<'_> {
+    let origin = arena_origin(arena_grid);
+    let min_tile = ((min - origin) / TILE_SIZE).floor().as_ivec2();
+    let max_tile = ((max - origin) / TILE_SIZE).ceil().as_ivec2() - IVec2::ONE;
+    SolidTiles {
+        arena_grid,
+        min: min_tile,
+        max: max_tile,
+        cursor: min_tile,
+    }
+}
+
+/// Moves a box by `delta`, one axis at a time, stopping flush against the first solid tile on
+/// each axis and keeping the remaining motion along the other axis.
+pub fn move_and_slide(arena_grid: &ArenaGrid, center: Vec2, size: Vec2, delta: Vec2) -> MoveResult {
+    let half_size = size / 2.0;
+    let (x, blocked_x) = sweep_axis(arena_grid, center, half_size, delta.x, Axis::X);
+    let after_x = Vec2::new(x, center.y);
+    let (y, blocked_y) = sweep_axis(arena_grid, after_x, half_size, delta.y, Axis::Y);
+
+    MoveResult {
+        position: Vec2::new(x, y),
+        blocked_x,
+        blocked_y,
+    }
+}
+
+/// Walks the tiles along a ray with a DDA (Amanatides & Woo) and returns the first solid tile
+/// face crossed within `max_distance`. `direction` must be normalized.
+pub fn raycast_tiles(
+    arena_grid: &ArenaGrid,
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+) -> Option<RayHit> {
+    if direction == Vec2::ZERO {
+        return None;
+    }
+
+    let grid_origin = arena_origin(arena_grid);
+    let mut tile = world_to_tile(arena_grid, origin);
+    if is_solid_tile(arena_grid, tile) {
+        return Some(RayHit {
+            point: origin,
+            normal: -direction,
+            distance: 0.0,
+        });
+    }
+
+    let step = IVec2::new(direction.x.signum() as i32, direction.y.signum() as i32);
+    let t_delta = Vec2::new(TILE_SIZE / direction.x.abs(), TILE_SIZE / direction.y.abs());
+    let next_boundary = |tile: i32, step: i32, origin: f32| {
+        (tile + i32::from(step > 0)) as f32 * TILE_SIZE + origin
+    };
+    let mut t_max = Vec2::new(
+        if direction.x != 0.0 {
+            (next_boundary(tile.x, step.x, grid_origin.x) - origin.x) / direction.x
+        } else {
+            f32::INFINITY
+        },
+        if direction.y != 0.0 {
+            (next_boundary(tile.y, step.y, grid_origin.y) - origin.y) / direction.y
+        } else {
+            f32::INFINITY
+        },
+    );
+
+    loop {
+        let (distance, normal) = if t_max.x < t_max.y {
+            tile.x += step.x;
+            let distance = t_max.x;
+            t_max.x += t_delta.x;
+            (distance, Vec2::new(-step.x as f32, 0.0))
+        } else {
+            tile.y += step.y;
+            let distance = t_max.y;
+            t_max.y += t_delta.y;
+            (distance, Vec2::new(0.0, -step.y as f32))
+        };
+
+        if distance > max_distance {
+            return None;
+        }
+        if is_solid_tile(arena_grid, tile) {
+            return Some(RayHit {
+                point: origin + direction * distance,
+                normal,
+                distance,
+            });
+        }
+    }
+}
+
+/// Slab test of a ray against an axis-aligned box. `direction` must be normalized.
+pub fn raycast_aabb(
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    center: Vec2,
+    half_size: Vec2,
+) -> Option<RayHit> {
+    let min = center - half_size;
+    let max = center + half_size;
+    let mut t_enter = 0.0_f32;
+    let mut t_exit = max_distance;
+    let mut normal = Vec2::ZERO;
+
+    for axis in [Axis::X, Axis::Y] {
+        let o = axis.pick(origin);
+        let d = axis.pick(direction);
+        let (lo, hi) = (axis.pick(min), axis.pick(max));
+
+        if d == 0.0 {
+            if o <= lo || o >= hi {
+                return None;
+            }
+            continue;
+        }
+
+        let (near, far) = if d > 0.0 {
+            ((lo - o) / d, (hi - o) / d)
+        } else {
+            ((hi - o) / d, (lo - o) / d)
+        };
+
+        if near > t_enter {
+            t_enter = near;
+            normal = axis.compose(-d.signum(), 0.0);
+        }
+        t_exit = t_exit.min(far);
+        if t_enter > t_exit {
+            return None;
+        }
+    }
+
+    if normal == Vec2::ZERO {
+        normal = -direction;
+    }
+
+    Some(RayHit {
+        point: origin + direction * t_enter,
+        normal,
+        distance: t_enter,
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Axis {
+    X,
+    Y,
+}
+
+impl Axis {
+    fn pick(self, v: Vec2) -> f32 {
+        match self {
+            Axis::X => v.x,
+            Axis::Y => v.y,
+        }
+    }
+
+    fn compose(self, along: f32, across: f32) -> Vec2 {
+        match self {
+            Axis::X => Vec2::new(along, across),
+            Axis::Y => Vec2::new(across, along),
+        }
+    }
+}
+
+/// Sweeps the box along one axis through every tile line it crosses, so fast movers cannot
+/// skip over a thin wall. Returns the new coordinate on that axis and whether it was blocked.
+fn sweep_axis(
+    arena_grid: &ArenaGrid,
+    center: Vec2,
+    half_size: Vec2,
+    delta: f32,
+    axis: Axis,
+) -> (f32, bool) {
+    let start = axis.pick(center);
+    if delta == 0.0 {
+        return (start, false);
+    }
+
+    let origin = axis.pick(arena_origin(arena_grid));
+    let across = match axis {
+        Axis::X => Axis::Y,
+        Axis::Y => Axis::X,
+    };
+    let across_min = across.pick(center - half_size);
+    let across_max = across.pick(center + half_size);
+    let half_along = axis.pick(half_size);
+
+    let (first_line, last_line, step) = if delta > 0.0 {
+        let leading = start + half_along;
+        (
+            ((leading - origin) / TILE_SIZE).ceil() as i32,
+            ((leading + delta - origin) / TILE_SIZE).ceil() as i32 - 1,
+            1,
+        )
+    } else {
+        let leading = start - half_along;
+        (
+            ((leading - origin) / TILE_SIZE).floor() as i32 - 1,
+            ((leading + delta - origin) / TILE_SIZE).floor() as i32,
+            -1,
+        )
+    };
+
+    let mut line = first_line;
+    while (step > 0 && line <= last_line) || (step < 0 && line >= last_line) {
+        let line_min = origin + line as f32 * TILE_SIZE;
+        let line_max = line_min + TILE_SIZE;
+        let box_min = axis.compose(line_min, across_min);
+        let box_max = axis.compose(line_max, across_max);
+
+        if solid_tiles_in(arena_grid, box_min, box_max)
+            .next()
+            .is_some()
+        {
+            let stopped = if step > 0 {
+                line_min - half_along - CONTACT_SKIN
+            } else {
+                line_max + half_along + CONTACT_SKIN
+            };
+            return (stopped, true);
+        }
+        line += step;
+    }
+
+    (start + delta, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::TileType;
+
+    /// Builds a grid from rows written top row first, `#` for walls and `.` for floor.
+    fn grid_from(rows: &[&str]) -> ArenaGrid {
+        let grid: Vec<Vec<TileType>> = rows
+            .iter()
+            .rev()
+            .map(|row| {
+                row.chars()
+                    .map(|c| match c {
+                        '#' => TileType::Wall,
+                        _ => TileType::Floor,
+                    })
+                    .collect()
+            })
+            .collect();
+        ArenaGrid::from_tiles(grid)
+    }
+
+    fn overlaps_solid(arena_grid: &ArenaGrid, center: Vec2, size: Vec2) -> bool {
+        let half_size = size / 2.0;
+        solid_tiles_in(arena_grid, center - half_size, center + half_size)
+            .next()
+            .is_some()
+    }
+
+    fn open_room() -> ArenaGrid {
+        grid_from(&[
+            "########", //
+            "#......#", //
+            "#......#", //
+            "#......#", //
+            "#......#", //
+            "########", //
+        ])
+    }
+
+    #[test]
+    fn tile_and_world_conversions_round_trip() {
+        let arena_grid = open_room();
+        for y in 0..arena_grid.height {
+            for x in 0..arena_grid.width {
+                let world = tile_to_world(&arena_grid, x, y);
+                assert_eq!(
+                    world_to_tile(&arena_grid, world),
+                    IVec2::new(x as i32, y as i32)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn arena_is_centred_on_the_origin() {
+        let arena_grid = open_room();
+        assert_eq!(
+            tile_to_world(&arena_grid, 0, 0),
+            Vec2::new(-3.5, -2.5) * TILE_SIZE
+        );
+        assert_eq!(world_to_tile(&arena_grid, Vec2::ZERO), IVec2::new(4, 3));
+        assert_eq!(
+            world_to_tile(&arena_grid, Vec2::new(-4.0, -3.0) * TILE_SIZE - 0.1),
+            IVec2::new(-1, -1)
+        );
+    }
+
+    #[test]
+    fn solid_tiles_reports_walls_and_out_of_bounds() {
+        let arena_grid = open_room();
+        let corner = tile_to_world(&arena_grid, 0, 0);
+        let tiles: Vec<IVec2> = solid_tiles_in(
+            &arena_grid,
+            corner - Vec2::splat(TILE_SIZE),
+            corner + Vec2::splat(TILE_SIZE * 0.5),
+        )
+        .collect();
+        assert_eq!(tiles.len(), 4);
+        assert!(tiles.contains(&IVec2::new(-1, -1)));
+        assert!(tiles.contains(&IVec2::new(0, 0)));
+
+        let floor = tile_to_world(&arena_grid, 2, 2);
+        assert_eq!(
+            solid_tiles_in(&arena_grid, floor - 1.0, floor + 1.0).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn touching_a_wall_face_is_not_an_overlap() {
+        let arena_grid = open_room();
+        let size = Vec2::splat(TILE_SIZE);
+        assert!(!overlaps_solid(
+            &arena_grid,
+            tile_to_world(&arena_grid, 1, 1),
+            size
+        ));
+        assert!(overlaps_solid(
+            &arena_grid,
+            tile_to_world(&arena_grid, 1, 1) - Vec2::X,
+            size
+        ));
+    }
+
+    #[test]
+    fn free_movement_is_unchanged() {
+        let arena_grid = open_room();
+        let start = tile_to_world(&arena_grid, 2, 2);
+        let result = move_and_slide(&arena_grid, start, Vec2::splat(4.0), Vec2::new(3.0, -2.0));
+        assert!(!result.blocked_x && !result.blocked_y);
+        assert_eq!(result.position, start + Vec2::new(3.0, -2.0));
+    }
+
+    #[test]
+    fn movement_stops_flush_against_a_wall() {
+        let arena_grid = open_room();
+        let size = Vec2::splat(10.0);
+        let start = tile_to_world(&arena_grid, 5, 2);
+        let result = move_and_slide(&arena_grid, start, size, Vec2::new(50.0, 0.0));
+
+        let wall_face = tile_to_world(&arena_grid, 7, 2).x - TILE_SIZE / 2.0;
+        assert!(result.blocked_x);
+        assert!(!result.blocked_y);
+        assert!((result.position.x + size.x / 2.0 - wall_face).abs() <= CONTACT_SKIN * 2.0);
+        assert!(!overlaps_solid(&arena_grid, result.position, size));
+    }
+
+    #[test]
+    fn diagonal_movement_slides_along_a_wall() {
+        let arena_grid = open_room();
+        let size = Vec2::splat(10.0);
+        let start = tile_to_world(&arena_grid, 3, 4);
+        let result = move_and_slide(&arena_grid, start, size, Vec2::new(6.0, 20.0));
+
+        assert!(!result.blocked_x);
+        assert!(result.blocked_y);
+        assert_eq!(result.position.x, start.x + 6.0);
+        assert!(!overlaps_solid(&arena_grid, result.position, size));
+    }
+
+    #[test]
+    fn fast_movement_does_not_tunnel_through_thin_walls() {
+        let arena_grid = grid_from(&[
+            "##########", //
+            "#...#....#", //
+            "#...#....#", //
+            "##########", //
+        ]);
+        let size = Vec2::new(10.0, 4.0);
+        let start = tile_to_world(&arena_grid, 1, 1);
+        let result = move_and_slide(&arena_grid, start, size, Vec2::new(TILE_SIZE * 6.0, 0.0));
+
+        assert!(result.blocked_x);
+        assert!(result.position.x < tile_to_world(&arena_grid, 4, 1).x);
+    }
+
+    #[test]
+    fn raycast_reports_impact_point_and_normal() {
+        let arena_grid = open_room();
+        let origin = tile_to_world(&arena_grid, 2, 2);
+        let wall_face = tile_to_world(&arena_grid, 7, 2).x - TILE_SIZE / 2.0;
+
+        let hit = raycast_tiles(&arena_grid, origin, Vec2::X, 1000.0).unwrap();
+        assert_eq!(hit.normal, Vec2::NEG_X);
+        assert!((hit.point.x - wall_face).abs() < 1e-3);
+        assert!((hit.point.y - origin.y).abs() < 1e-3);
+        assert!((hit.distance - (wall_face - origin.x)).abs() < 1e-3);
+
+        let hit = raycast_tiles(&arena_grid, origin, Vec2::NEG_Y, 1000.0).unwrap();
+        assert_eq!(hit.normal, Vec2::Y);
+    }
+
+    #[test]
+    fn raycast_stops_at_max_distance() {
+        let arena_grid = open_room();
+        let origin = tile_to_world(&arena_grid, 2, 2);
+        assert!(raycast_tiles(&arena_grid, origin, Vec2::X, TILE_SIZE).is_none());
+    }
+
+    #[test]
+    fn raycast_never_skips_a_single_tile_wall() {
+        let arena_grid = grid_from(&[
+            "##########", //
+            "#...#....#", //
+            "#...#....#", //
+            "##########", //
+        ]);
+        let origin = tile_to_world(&arena_grid, 1, 1);
+        let wall = tile_to_world(&arena_grid, 4, 1);
+        for angle_degrees in -40..=40 {
+            let direction = Vec2::from_angle((angle_degrees as f32).to_radians());
+            let hit = raycast_tiles(&arena_grid, origin, direction, 10_000.0).unwrap();
+            assert!(hit.point.x <= wall.x - TILE_SIZE / 2.0 + 1e-3);
+        }
+    }
+
+    #[test]
+    fn raycast_aabb_hits_the_near_face() {
+        let hit = raycast_aabb(
+            Vec2::ZERO,
+            Vec2::X,
+            100.0,
+            Vec2::new(50.0, 2.0),
+            Vec2::splat(5.0),
+        )
+        .unwrap();
+        assert_eq!(hit.point, Vec2::new(45.0, 0.0));
+        assert_eq!(hit.normal, Vec2::NEG_X);
+
+        assert!(
+            raycast_aabb(
+                Vec2::ZERO,
+                Vec2::X,
+                40.0,
+                Vec2::new(50.0, 0.0),
+                Vec2::splat(5.0)
+            )
+            .is_none()
+        );
+        assert!(
+            raycast_aabb(
+                Vec2::ZERO,
+                Vec2::X,
+                100.0,
+                Vec2::new(50.0, 10.0),
+                Vec2::splat(5.0)
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn raycast_aabb_starting_inside_hits_immediately() {
+        let hit = raycast_aabb(Vec2::ZERO, Vec2::Y, 10.0, Vec2::ZERO, Vec2::splat(5.0)).unwrap();
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.normal, Vec2::NEG_Y);
+    }
+}
@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::collision::check_aabb_collision;
+use crate::enemy::Enemy;
+use crate::floor::FloorEntity;
+use crate::player::{Health, Player, projectile_lifetime_system, projectile_movement_system};
+
+const ENEMY_FALLBACK_HITBOX_SIZE: f32 = 10.0;
+const PLAYER_FALLBACK_HITBOX_SIZE: f32 = 10.0;
+const INVULNERABLE_SPRITE_ALPHA: f32 = 0.4;
+
+const IMPACT_EFFECT_SECONDS: f32 = 0.08;
+const IMPACT_EFFECT_SIZE: f32 = 4.0;
+const IMPACT_EFFECT_COLOR: Color = Color::srgb(1.0, 0.85, 0.4);
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct EnemyDeathEvent {
+    pub entity: Entity,
+    pub position: Vec2,
+}
+
+/// Sent when a projectile stops against a wall tile or an entity hitbox. `point` is the exact
+/// contact position and `normal` the outward normal of the surface that was hit.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ProjectileImpactEvent {
+    pub target: Option<Entity>,
+    pub point: Vec2,
+    pub normal: Vec2,
+    pub damage: f32,
+}
+
+#[derive(Component)]
+pub struct ContactDamage(pub f32);
+
+#[derive(Component)]
+#[require(FloorEntity)]
+pub struct ImpactEffect {
+    pub lifetime: Timer,
+}
+
+#[derive(Component)]
+pub struct Invulnerability {
+    pub timer: Timer,
+}
+
+impl Invulnerability {
+    pub fn new(duration_seconds: f32) -> Self {
+        let mut timer = Timer::from_seconds(duration_seconds, TimerMode::Once);
+        timer.tick(timer.duration());
+        Self { timer }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.timer.finished()
+    }
+
+    pub fn trigger(&mut self) {
+        self.timer.reset();
+    }
+}
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<EnemyDeathEvent>()
+            .add_event::<ProjectileImpactEvent>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    invulnerability_system,
+                    projectile_impact_damage_system,
+                    spawn_impact_effect_system,
+                    enemy_contact_damage_system,
+                    apply_damage_system,
+                    log_enemy_death_system,
+                )
+                    .chain()
+                    .after(projectile_movement_system)
+                    .before(projectile_lifetime_system)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                impact_effect_lifetime_system.run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+fn projectile_impact_damage_system(
+    mut impact_events: EventReader<ProjectileImpactEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for event in impact_events.read() {
+        if let Some(target) = event.target {
+            damage_events.write(DamageEvent {
+                target,
+                amount: event.damage,
+            });
+        }
+    }
+}
+
+fn spawn_impact_effect_system(
+    mut commands: Commands,
+    mut impact_events: EventReader<ProjectileImpactEvent>,
+) {
+    for event in impact_events.read() {
+        let position = event.point + event.normal * IMPACT_EFFECT_SIZE / 2.0;
+        commands.spawn((
+            ImpactEffect {
+                lifetime: Timer::from_seconds(IMPACT_EFFECT_SECONDS, TimerMode::Once),
+            },
+            Sprite {
+                color: IMPACT_EFFECT_COLOR,
+                custom_size: Some(Vec2::splat(IMPACT_EFFECT_SIZE)),
+                ..default()
+            },
+            Transform::from_translation(position.extend(1.0)),
+            Visibility::Visible,
+        ));
+    }
+}
+
+fn impact_effect_lifetime_system(
+    mut commands: Commands,
+    mut effect_query: Query<(Entity, &mut ImpactEffect)>,
+    time: Res<Time>,
+) {
+    for (entity, mut effect) in effect_query.iter_mut() {
+        effect.lifetime.tick(time.delta());
+        if effect.lifetime.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn enemy_contact_damage_system(
+    player_query: Query<(Entity, &Transform, &Sprite, &Invulnerability), With<Player>>,
+    enemy_query: Query<(&Transform, &Sprite, &ContactDamage), With<Enemy>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    let Ok((player_entity, player_transform, player_sprite, invulnerability)) =
+        player_query.single()
+    else {
+        return;
+    };
+
+    if invulnerability.is_active() {
+        return;
+    }
+
+    let player_pos = player_transform.translation.truncate();
+    let player_size = player_sprite
+        .custom_size
+        .unwrap_or(Vec2::splat(PLAYER_FALLBACK_HITBOX_SIZE));
+
+    for (enemy_transform, enemy_sprite, contact_damage) in enemy_query.iter() {
+        let enemy_size = enemy_sprite
+            .custom_size
+            .unwrap_or(Vec2::splat(ENEMY_FALLBACK_HITBOX_SIZE));
+
+        if check_aabb_collision(
+            player_pos,
+            player_size,
+            enemy_transform.translation.truncate(),
+            enemy_size,
+        ) {
+            damage_events.write(DamageEvent {
+                target: player_entity,
+                amount: contact_damage.0,
+            });
+            break;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_damage_system(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    mut health_query: Query<(
+        &mut Health,
+        &Transform,
+        Option<&mut Invulnerability>,
+        Has<Enemy>,
+        Has<Player>,
+    )>,
+    mut death_events: EventWriter<EnemyDeathEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for event in damage_events.read() {
+        let Ok((mut health, transform, invulnerability, is_enemy, is_player)) =
+            health_query.get_mut(event.target)
+        else {
+            continue;
+        };
+
+        if health.current <= 0.0 {
+            continue;
+        }
+
+        if let Some(mut invulnerability) = invulnerability {
+            if invulnerability.is_active() {
+                continue;
+            }
+            invulnerability.trigger();
+        }
+
+        health.current = (health.current - event.amount).clamp(0.0, health.max);
+
+        if health.current > 0.0 {
+            continue;
+        }
+
+        if is_enemy {
+            death_events.write(EnemyDeathEvent {
+                entity: event.target,
+                position: transform.translation.truncate(),
+            });
+            commands.entity(event.target).despawn();
+        } else if is_player {
+            info!("Player died.");
+            next_state.set(GameState::GameOver);
+        }
+    }
+}
+
+fn invulnerability_system(
+    mut invulnerability_query: Query<(&mut Invulnerability, &mut Sprite)>,
+    time: Res<Time>,
+) {
+    for (mut invulnerability, mut sprite) in invulnerability_query.iter_mut() {
+        invulnerability.timer.tick(time.delta());
+
+        let alpha = if invulnerability.is_active() {
+            INVULNERABLE_SPRITE_ALPHA
+        } else {
+            1.0
+        };
+        sprite.color.set_alpha(alpha);
+    }
+}
+
+fn log_enemy_death_system(mut death_events: EventReader<EnemyDeathEvent>) {
+    for event in death_events.read() {
+        info!(
+            "Enemy {} killed at ({:.1}, {:.1}).",
+            event.entity, event.position.x, event.position.y
+        );
+    }
+}
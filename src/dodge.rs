@@ -0,0 +1,250 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::action::{Action, MoveInput};
+use crate::arena::ArenaGrid;
+use crate::collision::move_and_slide;
+use crate::player::{Player, Velocity, player_movement_system};
+use crate::simulation::FixedInput;
+
+/// Size used for collision if the player sprite has no explicit size.
+const PLAYER_FALLBACK_SIZE: f32 = 10.0;
+
+/// Dodge roll tuning, part of the player archetype.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DodgeStats {
+    /// World units per second while rolling.
+    pub speed: f32,
+    pub duration_seconds: f32,
+    /// Projectiles pass through the player for this long from the start of the roll.
+    pub invulnerable_seconds: f32,
+    /// Time after a roll ends before the next can start.
+    pub cooldown_seconds: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DodgeState {
+    Ready,
+    Rolling { direction: Vec2, elapsed: f32 },
+    Recovering { remaining: f32 },
+}
+
+/// The player's dodge roll. Once started, a roll carries on in a straight line for its full
+/// duration regardless of input.
+#[derive(Component, Debug, Clone)]
+pub struct DodgeRoll {
+    pub stats: DodgeStats,
+    pub state: DodgeState,
+}
+
+impl DodgeRoll {
+    pub fn new(stats: DodgeStats) -> Self {
+        Self {
+            stats,
+            state: DodgeState::Ready,
+        }
+    }
+
+    pub fn is_rolling(&self) -> bool {
+        matches!(self.state, DodgeState::Rolling { .. })
+    }
+
+    /// Whether projectiles currently pass through.
+    pub fn is_invulnerable(&self) -> bool {
+        match self.state {
+            DodgeState::Rolling { elapsed, .. } => elapsed < self.stats.invulnerable_seconds,
+            _ => false,
+        }
+    }
+
+    /// Starts a roll along `direction` if one is ready. Returns whether it started.
+    pub fn start(&mut self, direction: Vec2) -> bool {
+        let direction = direction.normalize_or_zero();
+        if self.state != DodgeState::Ready || direction == Vec2::ZERO {
+            return false;
+        }
+        self.state = DodgeState::Rolling {
+            direction,
+            elapsed: 0.0,
+        };
+        true
+    }
+
+    /// Advances by `delta` seconds and returns how far the roll wants to move this frame.
+    pub fn advance(&mut self, delta: f32) -> Vec2 {
+        match self.state {
+            DodgeState::Ready => Vec2::ZERO,
+            DodgeState::Rolling { direction, elapsed } => {
+                let step = delta.min(self.stats.duration_seconds - elapsed).max(0.0);
+                let elapsed = elapsed + delta;
+                self.state = if elapsed >= self.stats.duration_seconds {
+                    DodgeState::Recovering {
+                        remaining: self.stats.cooldown_seconds,
+                    }
+                } else {
+                    DodgeState::Rolling { direction, elapsed }
+                };
+                direction * self.stats.speed * step
+            }
+            DodgeState::Recovering { remaining } => {
+                let remaining = remaining - delta;
+                self.state = if remaining <= 0.0 {
+                    DodgeState::Ready
+                } else {
+                    DodgeState::Recovering { remaining }
+                };
+                Vec2::ZERO
+            }
+        }
+    }
+}
+
+pub struct DodgePlugin;
+
+impl Plugin for DodgePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (dodge_input_system, dodge_movement_system)
+                .chain()
+                .before(player_movement_system)
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Rolls towards where the player is moving, or the aim when standing still.
+fn dodge_input_system(
+    mut player_query: Query<(&Transform, &mut DodgeRoll), With<Player>>,
+    actions: Res<FixedInput<Action>>,
+    move_input: Res<MoveInput>,
+) {
+    if !actions.just_pressed(Action::Dodge) {
+        return;
+    }
+    let Ok((transform, mut dodge)) = player_query.single_mut() else {
+        return;
+    };
+
+    let direction = match move_input.0.normalize_or_zero() {
+        Vec2::ZERO => (transform.rotation * Vec3::X).truncate(),
+        direction => direction,
+    };
+    dodge.start(direction);
+}
+
+pub fn dodge_movement_system(
+    mut player_query: Query<(&mut Transform, &mut Velocity, &Sprite, &mut DodgeRoll), With<Player>>,
+    time: Res<Time>,
+    arena_grid: Res<ArenaGrid>,
+) {
+    let Ok((mut transform, mut velocity, sprite, mut dodge)) = player_query.single_mut() else {
+        return;
+    };
+
+    let delta = dodge.advance(time.delta_secs());
+    if delta == Vec2::ZERO {
+        return;
+    }
+
+    let size = sprite
+        .custom_size
+        .unwrap_or(Vec2::splat(PLAYER_FALLBACK_SIZE));
+    let result = move_and_slide(&arena_grid, transform.translation.truncate(), size, delta);
+    if time.delta_secs() > 0.0 {
+        velocity.0 = (result.position - transform.translation.truncate()) / time.delta_secs();
+    }
+    transform.translation.x = result.position.x;
+    transform.translation.y = result.position.y;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::arena::{TILE_SIZE, TileType};
+    use crate::collision::tile_to_world;
+
+    const FRAME: f32 = 1.0 / 60.0;
+
+    fn stats() -> DodgeStats {
+        DodgeStats {
+            speed: 300.0,
+            duration_seconds: 0.3,
+            invulnerable_seconds: 0.2,
+            cooldown_seconds: 0.5,
+        }
+    }
+
+    #[test]
+    fn rolls_a_fixed_distance_then_cools_down() {
+        let mut dodge = DodgeRoll::new(stats());
+        assert!(dodge.start(Vec2::new(3.0, 4.0)));
+        assert!(dodge.is_invulnerable());
+
+        let mut travelled = Vec2::ZERO;
+        let mut invulnerable_frames = 0;
+        while dodge.is_rolling() {
+            assert!(!dodge.start(Vec2::X), "a roll can't be interrupted");
+            invulnerable_frames += dodge.is_invulnerable() as u32;
+            travelled += dodge.advance(FRAME);
+        }
+        assert!((travelled.length() - 90.0).abs() < 1e-3, "{travelled}");
+        assert!(travelled.normalize().abs_diff_eq(Vec2::new(0.6, 0.8), 1e-5));
+        let invulnerable_time = invulnerable_frames as f32 * FRAME;
+        assert!(
+            (invulnerable_time - 0.2).abs() <= FRAME,
+            "{invulnerable_time}"
+        );
+
+        assert!(!dodge.start(Vec2::X), "still cooling down");
+        dodge.advance(0.5);
+        assert!(dodge.start(Vec2::X));
+    }
+
+    #[test]
+    fn rolls_stop_at_walls() {
+        let mut grid = vec![vec![TileType::Floor; 20]; 20];
+        for row in grid.iter_mut() {
+            row[12] = TileType::Wall;
+        }
+        let arena_grid = ArenaGrid::from_tiles(grid);
+        let start = tile_to_world(&arena_grid, 10, 10);
+        let wall_face = tile_to_world(&arena_grid, 12, 10).x - TILE_SIZE / 2.0;
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                FRAME,
+            )))
+            .insert_resource(arena_grid)
+            .add_systems(Update, dodge_movement_system);
+        let mut dodge = DodgeRoll::new(stats());
+        dodge.start(Vec2::X);
+        let player = app
+            .world_mut()
+            .spawn((
+                Player,
+                Transform::from_translation(start.extend(0.0)),
+                Velocity::default(),
+                Sprite {
+                    custom_size: Some(Vec2::splat(10.0)),
+                    ..default()
+                },
+                dodge,
+            ))
+            .id();
+
+        for _ in 0..30 {
+            app.update();
+        }
+
+        let x = app.world().get::<Transform>(player).unwrap().translation.x;
+        assert!(x + 5.0 <= wall_face + 1e-3, "rolled into the wall at {x}");
+        assert!(x + 5.0 > wall_face - 1.0, "stopped short at {x}");
+    }
+}
@@ -0,0 +1,532 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use crate::GameState;
+use crate::arena::{ArenaGenerator, ArenaGrid, ArenaLayout, TileType, orthogonal_neighbors};
+use crate::collision::world_to_tile;
+use crate::enemy::Enemy;
+use crate::player::Player;
+
+const MIN_LEAF_SIZE_TILES: usize = 12;
+const MAX_SPLIT_DEPTH: usize = 4;
+const MIN_ROOM_SIZE_TILES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoomKind {
+    Start,
+    Combat,
+    Treasure,
+    Shop,
+    Boss,
+}
+
+impl RoomKind {
+    pub fn has_enemies(self) -> bool {
+        matches!(self, RoomKind::Combat | RoomKind::Boss)
+    }
+}
+
+/// A rectangle of tiles; `x`/`y` is the bottom-left tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl TileRect {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    pub fn center(&self) -> (usize, usize) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Room {
+    pub kind: RoomKind,
+    /// Walkable interior; the ring of tiles around it is wall except for `doors`.
+    pub bounds: TileRect,
+    pub doors: Vec<(usize, usize)>,
+}
+
+/// Marks an enemy as belonging to a room so that room's doors stay locked until it dies.
+#[derive(Component)]
+pub struct RoomOccupant(pub usize);
+
+#[derive(Resource, Default)]
+pub struct CurrentRoom(pub Option<usize>);
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct RoomClearedEvent {
+    pub room: usize,
+    pub kind: RoomKind,
+}
+
+/// Binary space partition of the arena into leaves, one room per leaf, with sibling subtrees
+/// joined by L-shaped corridors.
+pub struct DungeonGenerator;
+
+impl ArenaGenerator for DungeonGenerator {
+    fn name(&self) -> &'static str {
+        "dungeon"
+    }
+
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> ArenaLayout {
+        let mut grid = vec![vec![TileType::Wall; width]; height];
+        let mut bounds = Vec::new();
+
+        let root = TileRect {
+            x: 1,
+            y: 1,
+            width: width.saturating_sub(2),
+            height: height.saturating_sub(2),
+        };
+        partition(root, 0, rng, &mut grid, &mut bounds);
+
+        let mut rooms: Vec<Room> = bounds
+            .into_iter()
+            .map(|bounds| Room {
+                kind: RoomKind::Combat,
+                bounds,
+                doors: Vec::new(),
+            })
+            .collect();
+
+        for room in rooms.iter_mut() {
+            room.doors = place_doors(&mut grid, room.bounds);
+        }
+
+        let spawn = assign_room_kinds(&mut rooms, rng)
+            .map(|start| rooms[start].bounds.center())
+            .unwrap_or((width / 2, height / 2));
+
+        ArenaLayout {
+            tiles: grid,
+            spawn,
+            rooms,
+        }
+    }
+}
+
+/// Splits `leaf` recursively, carving a room in each final leaf. Returns the indices into
+/// `rooms` of every room carved in this subtree.
+fn partition(
+    leaf: TileRect,
+    depth: usize,
+    rng: &mut StdRng,
+    grid: &mut [Vec<TileType>],
+    rooms: &mut Vec<TileRect>,
+) -> Vec<usize> {
+    let can_split_x = leaf.width >= MIN_LEAF_SIZE_TILES * 2;
+    let can_split_y = leaf.height >= MIN_LEAF_SIZE_TILES * 2;
+
+    if depth >= MAX_SPLIT_DEPTH || (!can_split_x && !can_split_y) {
+        return carve_room(leaf, rng, grid, rooms).into_iter().collect();
+    }
+
+    let split_x = if can_split_x && can_split_y {
+        leaf.width >= leaf.height
+    } else {
+        can_split_x
+    };
+
+    let (first, second) = if split_x {
+        let at = rng.random_range(MIN_LEAF_SIZE_TILES..=leaf.width - MIN_LEAF_SIZE_TILES);
+        (
+            TileRect { width: at, ..leaf },
+            TileRect {
+                x: leaf.x + at,
+                width: leaf.width - at,
+                ..leaf
+            },
+        )
+    } else {
+        let at = rng.random_range(MIN_LEAF_SIZE_TILES..=leaf.height - MIN_LEAF_SIZE_TILES);
+        (
+            TileRect { height: at, ..leaf },
+            TileRect {
+                y: leaf.y + at,
+                height: leaf.height - at,
+                ..leaf
+            },
+        )
+    };
+
+    let mut first_rooms = partition(first, depth + 1, rng, grid, rooms);
+    let second_rooms = partition(second, depth + 1, rng, grid, rooms);
+
+    let closest = first_rooms
+        .iter()
+        .flat_map(|&a| second_rooms.iter().map(move |&b| (a, b)))
+        .min_by_key(|&(a, b)| manhattan(rooms[a].center(), rooms[b].center()));
+    if let Some((a, b)) = closest {
+        carve_corridor(
+            grid,
+            rooms[a].center(),
+            rooms[b].center(),
+            rng.random_bool(0.5),
+        );
+    }
+
+    first_rooms.extend(second_rooms);
+    first_rooms
+}
+
+fn carve_room(
+    leaf: TileRect,
+    rng: &mut StdRng,
+    grid: &mut [Vec<TileType>],
+    rooms: &mut Vec<TileRect>,
+) -> Option<usize> {
+    let max_width = leaf.width.checked_sub(2)?;
+    let max_height = leaf.height.checked_sub(2)?;
+    if max_width < MIN_ROOM_SIZE_TILES || max_height < MIN_ROOM_SIZE_TILES {
+        return None;
+    }
+
+    let width = rng.random_range(MIN_ROOM_SIZE_TILES..=max_width);
+    let height = rng.random_range(MIN_ROOM_SIZE_TILES..=max_height);
+    let room = TileRect {
+        x: leaf.x + 1 + rng.random_range(0..=max_width - width),
+        y: leaf.y + 1 + rng.random_range(0..=max_height - height),
+        width,
+        height,
+    };
+
+    for row in grid.iter_mut().skip(room.y).take(room.height) {
+        for cell in row.iter_mut().skip(room.x).take(room.width) {
+            *cell = TileType::Floor;
+        }
+    }
+
+    rooms.push(room);
+    Some(rooms.len() - 1)
+}
+
+fn carve_corridor(
+    grid: &mut [Vec<TileType>],
+    from: (usize, usize),
+    to: (usize, usize),
+    horizontal_first: bool,
+) {
+    let corner = if horizontal_first {
+        (to.0, from.1)
+    } else {
+        (from.0, to.1)
+    };
+
+    for (start, end) in [(from, corner), (corner, to)] {
+        let (min_x, max_x) = (start.0.min(end.0), start.0.max(end.0));
+        let (min_y, max_y) = (start.1.min(end.1), start.1.max(end.1));
+        for row in grid.iter_mut().skip(min_y).take(max_y - min_y + 1) {
+            for cell in row.iter_mut().skip(min_x).take(max_x - min_x + 1) {
+                *cell = TileType::Floor;
+            }
+        }
+    }
+}
+
+/// Turns each corridor opening in the wall ring around `bounds` into a door tile. A corridor that
+/// runs along the ring only gets a door where it comes in from outside; the rest of the ring it
+/// carved goes back to wall so the room still seals when its doors lock.
+fn place_doors(grid: &mut [Vec<TileType>], bounds: TileRect) -> Vec<(usize, usize)> {
+    let height = grid.len();
+    let width = grid.first().map_or(0, Vec::len);
+    let left = bounds.x - 1;
+    let right = bounds.x + bounds.width;
+    let bottom = bounds.y - 1;
+    let top = bounds.y + bounds.height;
+    let on_ring = |x: usize, y: usize| {
+        ((x == left || x == right) && (bounds.y..top).contains(&y))
+            || ((y == bottom || y == top) && (bounds.x..right).contains(&x))
+    };
+
+    let openings: Vec<((usize, usize), bool)> = (bounds.y..top)
+        .flat_map(|y| [(left, y), (right, y)])
+        .chain((bounds.x..right).flat_map(|x| [(x, bottom), (x, top)]))
+        .filter(|&(x, y)| grid[y][x] == TileType::Floor)
+        .map(|(x, y)| {
+            let enters = orthogonal_neighbors(x, y, width, height).any(|(nx, ny)| {
+                !grid[ny][nx].is_solid() && !bounds.contains(nx, ny) && !on_ring(nx, ny)
+            });
+            ((x, y), enters)
+        })
+        .collect();
+
+    let mut doors = Vec::new();
+    for ((x, y), enters) in openings {
+        if enters {
+            grid[y][x] = TileType::Door;
+            doors.push((x, y));
+        } else {
+            grid[y][x] = TileType::Wall;
+        }
+    }
+
+    doors
+}
+
+/// Tags a random room as the start, the room farthest from it as the boss, and hands out one
+/// treasure and one shop room among the rest. Returns the start room index.
+fn assign_room_kinds(rooms: &mut [Room], rng: &mut StdRng) -> Option<usize> {
+    if rooms.is_empty() {
+        return None;
+    }
+
+    let start = rng.random_range(0..rooms.len());
+    rooms[start].kind = RoomKind::Start;
+
+    let start_center = rooms[start].bounds.center();
+    let boss = (0..rooms.len())
+        .filter(|&index| index != start)
+        .max_by_key(|&index| manhattan(start_center, rooms[index].bounds.center()));
+    let Some(boss) = boss else {
+        return Some(start);
+    };
+    rooms[boss].kind = RoomKind::Boss;
+
+    let mut remaining: Vec<usize> = (0..rooms.len())
+        .filter(|&index| index != start && index != boss)
+        .collect();
+    remaining.shuffle(rng);
+    for (index, kind) in remaining
+        .into_iter()
+        .zip([RoomKind::Treasure, RoomKind::Shop])
+    {
+        rooms[index].kind = kind;
+    }
+
+    Some(start)
+}
+
+fn manhattan(a: (usize, usize), b: (usize, usize)) -> usize {
+    a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+}
+
+pub struct DungeonPlugin;
+
+impl Plugin for DungeonPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurrentRoom>()
+            .add_event::<RoomClearedEvent>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    current_room_system,
+                    room_lock_system,
+                    log_room_cleared_system,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+fn current_room_system(
+    player_query: Query<&Transform, With<Player>>,
+    arena_grid: Res<ArenaGrid>,
+    mut current_room: ResMut<CurrentRoom>,
+) {
+    let Ok(player_transform) = player_query.single() else {
+        return;
+    };
+
+    let tile = world_to_tile(&arena_grid, player_transform.translation.truncate());
+    let room = if tile.x >= 0 && tile.y >= 0 {
+        arena_grid.room_at(tile.x as usize, tile.y as usize)
+    } else {
+        None
+    };
+
+    if current_room.0 != room {
+        current_room.0 = room;
+    }
+}
+
+fn room_lock_system(
+    current_room: Res<CurrentRoom>,
+    occupant_query: Query<&RoomOccupant, With<Enemy>>,
+    mut arena_grid: ResMut<ArenaGrid>,
+    mut cleared_events: EventWriter<RoomClearedEvent>,
+) {
+    let room_has_enemies = |room: usize| occupant_query.iter().any(|occupant| occupant.0 == room);
+
+    for room in 0..arena_grid.rooms.len() {
+        if arena_grid.is_room_locked(room) && !room_has_enemies(room) {
+            arena_grid.set_room_locked(room, false);
+            cleared_events.write(RoomClearedEvent {
+                room,
+                kind: arena_grid.rooms[room].kind,
+            });
+        }
+    }
+
+    if let Some(room) = current_room.0
+        && !arena_grid.is_room_locked(room)
+        && room_has_enemies(room)
+    {
+        arena_grid.set_room_locked(room, true);
+    }
+}
+
+fn log_room_cleared_system(mut cleared_events: EventReader<RoomClearedEvent>) {
+    for event in cleared_events.read() {
+        info!("Room {} ({:?}) cleared.", event.room, event.kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::arena::RegionMap;
+    use crate::collision::tile_to_world;
+
+    const SEEDS: [u64; 8] = [0, 1, 2, 3, 7, 42, 1234, 98765];
+
+    fn generate(seed: u64) -> ArenaLayout {
+        DungeonGenerator.generate(80, 60, &mut StdRng::seed_from_u64(seed))
+    }
+
+    #[test]
+    fn every_room_is_reachable_from_the_start_room() {
+        for seed in SEEDS {
+            let layout = generate(seed);
+            let regions = RegionMap::label(&layout.tiles);
+            let start = layout
+                .rooms
+                .iter()
+                .find(|room| room.kind == RoomKind::Start)
+                .unwrap();
+            let (start_x, start_y) = start.bounds.center();
+            for room in &layout.rooms {
+                let (x, y) = room.bounds.center();
+                assert_eq!(
+                    regions.region_at(x, y),
+                    regions.region_at(start_x, start_y),
+                    "seed {seed}: {:?} room at {:?} is cut off",
+                    room.kind,
+                    room.bounds
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exactly_one_start_and_one_boss_room() {
+        for seed in SEEDS {
+            let layout = generate(seed);
+            let count = |kind| layout.rooms.iter().filter(|room| room.kind == kind).count();
+            assert_eq!(count(RoomKind::Start), 1, "seed {seed}");
+            assert_eq!(count(RoomKind::Boss), 1, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn a_corridor_along_the_wall_only_gets_doors_where_it_comes_in() {
+        let bounds = TileRect {
+            x: 3,
+            y: 3,
+            width: 5,
+            height: 5,
+        };
+        let mut grid = vec![vec![TileType::Wall; 12]; 12];
+        for (y, row) in grid.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                // The room, a corridor running along its bottom wall past both corners, and one
+                // leaving straight out of its top wall.
+                if bounds.contains(x, y) || (y == 2 && (1..=9).contains(&x)) || (x == 5 && y >= 8) {
+                    *cell = TileType::Floor;
+                }
+            }
+        }
+
+        let mut doors = place_doors(&mut grid, bounds);
+        doors.sort();
+        assert_eq!(doors, vec![(3, 2), (5, 8), (7, 2)]);
+        assert_eq!(
+            grid[2][4..=6],
+            [TileType::Wall; 3],
+            "the rest of the run is walled off"
+        );
+    }
+
+    fn locking_app() -> (App, Entity) {
+        let mut arena_grid = ArenaGrid::from_tiles(vec![vec![TileType::Wall; 12]; 12]);
+        let bounds = TileRect {
+            x: 3,
+            y: 3,
+            width: 5,
+            height: 5,
+        };
+        for y in bounds.y..bounds.y + bounds.height {
+            for x in bounds.x..bounds.x + bounds.width {
+                arena_grid.grid[y][x] = TileType::Floor;
+            }
+        }
+        arena_grid.grid[2][5] = TileType::Door;
+        arena_grid.rooms.push(Room {
+            kind: RoomKind::Combat,
+            bounds,
+            doors: vec![(5, 2)],
+        });
+        let player_position = tile_to_world(&arena_grid, 5, 5);
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<CurrentRoom>()
+            .add_event::<RoomClearedEvent>()
+            .insert_resource(arena_grid)
+            .add_systems(Update, (current_room_system, room_lock_system).chain());
+        app.world_mut().spawn((
+            Player,
+            Transform::from_translation(player_position.extend(0.0)),
+        ));
+        let enemy = app.world_mut().spawn((Enemy, RoomOccupant(0))).id();
+        (app, enemy)
+    }
+
+    fn cleared_rooms(app: &mut App) -> Vec<usize> {
+        app.world_mut()
+            .resource_mut::<Events<RoomClearedEvent>>()
+            .drain()
+            .map(|event| event.room)
+            .collect()
+    }
+
+    #[test]
+    fn doors_lock_while_the_room_has_enemies() {
+        let (mut app, _) = locking_app();
+        app.update();
+        app.update();
+        assert!(app.world().resource::<ArenaGrid>().is_room_locked(0));
+        assert_eq!(
+            app.world().resource::<ArenaGrid>().grid[2][5],
+            TileType::LockedDoor
+        );
+        assert!(cleared_rooms(&mut app).is_empty());
+    }
+
+    #[test]
+    fn doors_unlock_once_the_room_is_cleared() {
+        let (mut app, enemy) = locking_app();
+        app.update();
+        assert!(app.world().resource::<ArenaGrid>().is_room_locked(0));
+
+        app.world_mut().despawn(enemy);
+        app.update();
+        assert!(!app.world().resource::<ArenaGrid>().is_room_locked(0));
+        assert_eq!(
+            app.world().resource::<ArenaGrid>().grid[2][5],
+            TileType::Door
+        );
+        assert_eq!(cleared_rooms(&mut app), vec![0]);
+    }
+}
@@ -1,47 +1,69 @@
+use std::f32::consts::TAU;
+
 use bevy::prelude::*;
 use rand::Rng;
 
 use crate::GameState;
-use crate::arena::{
-    ARENA_HEIGHT_TILES, ARENA_WIDTH_TILES, ArenaGrid, TILE_SIZE, TileType,
-    setup_arena as setup_arena_system,
-};
+use crate::archetype::{Archetypes, EnemyArchetype};
+use crate::arena::{ArenaGrid, TileType, setup_arena as setup_arena_system};
+use crate::blank::Stunned;
+use crate::bullet_pattern::PendingPattern;
+use crate::collision::{move_and_slide, tile_to_world};
+use crate::combat::ContactDamage;
+use crate::dungeon::{RoomKind, RoomOccupant};
+use crate::enemy_ai::{AiState, EnemyAi, Perception};
+use crate::enemy_weapon::EnemyWeapon;
+use crate::floor::{CurrentFloor, FloorEntity, SetupFloor};
+use crate::pathfinding::{AgentPath, FlowField, next_waypoint};
 use crate::player::{Health, Player, Speed};
+use crate::seed::{RngStream, RunSeed};
+use crate::simulation::Interpolated;
 
+/// Size used for collision if a sprite has no explicit size.
 const ENEMY_SPRITE_SIZE: f32 = 10.0;
-const ENEMY_DEFAULT_SPEED: f32 = 75.0;
-const ENEMY_DEFAULT_HEALTH: f32 = 50.0;
-const ENEMY_COLOR: Color = Color::srgb(0.8, 0.2, 0.2);
-const MAX_ENEMIES_SPAWN: usize = 10;
 
 #[derive(Component)]
+#[require(FloorEntity, Interpolated)]
 pub struct Enemy;
 
+/// Id of the archetype an enemy was spawned from.
+#[derive(Component)]
+pub struct EnemyKind(pub String);
+
 #[derive(Bundle)]
 pub struct EnemyBundle {
     enemy_marker: Enemy,
     health: Health,
     speed: Speed,
+    contact_damage: ContactDamage,
+    agent_path: AgentPath,
+    ai: EnemyAi,
+    perception: Perception,
     sprite: Sprite,
     transform: Transform,
     visibility: Visibility,
 }
 
 impl EnemyBundle {
-    fn new(position: Vec3) -> Self {
+    pub fn new(archetype: &EnemyArchetype, position: Vec3, facing_angle: f32) -> Self {
         Self {
             enemy_marker: Enemy,
             health: Health {
-                current: ENEMY_DEFAULT_HEALTH,
-                max: ENEMY_DEFAULT_HEALTH,
+                current: archetype.health,
+                max: archetype.health,
             },
-            speed: Speed(ENEMY_DEFAULT_SPEED),
+            speed: Speed(archetype.speed),
+            contact_damage: ContactDamage(archetype.contact_damage),
+            agent_path: AgentPath::default(),
+            ai: EnemyAi::default(),
+            perception: Perception::default(),
             sprite: Sprite {
-                color: ENEMY_COLOR,
-                custom_size: Some(Vec2::splat(ENEMY_SPRITE_SIZE)),
+                color: archetype.color.into(),
+                custom_size: Some(Vec2::splat(archetype.size)),
                 ..default()
             },
-            transform: Transform::from_translation(position),
+            transform: Transform::from_translation(position)
+                .with_rotation(Quat::from_rotation_z(facing_angle)),
             visibility: Visibility::Visible,
         }
     }
@@ -51,26 +73,41 @@ pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            OnEnter(GameState::InGame),
-            spawn_enemies.after(setup_arena_system),
-        )
-        .add_systems(
-            Update,
-            enemy_movement_system.run_if(in_state(GameState::InGame)),
-        );
+        app.add_systems(SetupFloor, spawn_enemies.after(setup_arena_system))
+            .add_systems(
+                FixedUpdate,
+                enemy_movement_system.run_if(in_state(GameState::InGame)),
+            );
     }
 }
 
-fn spawn_enemies(mut commands: Commands, arena_grid: Res<ArenaGrid>) {
-    let mut rng = rand::rng();
+fn spawn_enemies(
+    mut commands: Commands,
+    arena_grid: Res<ArenaGrid>,
+    run_seed: Res<RunSeed>,
+    floor: Res<CurrentFloor>,
+    archetypes: Res<Archetypes>,
+    asset_server: Res<AssetServer>,
+) {
+    let mut rng = run_seed.rng(RngStream::EnemySpawns, floor.0);
+    let mut spawner = EnemySpawner {
+        commands: &mut commands,
+        archetypes: &archetypes,
+        asset_server: &asset_server,
+    };
+
+    if !arena_grid.rooms.is_empty() {
+        spawn_room_enemies(&mut spawner, &arena_grid, &mut rng);
+        return;
+    }
+
     let mut floor_tiles = Vec::new();
 
+    let (center_x, center_y) = arena_grid.spawn_tile();
+
     for (y, row) in arena_grid.grid.iter().enumerate() {
         for (x, tile_type) in row.iter().enumerate() {
-            if *tile_type == TileType::Floor {
-                let center_x = arena_grid.width / 2;
-                let center_y = arena_grid.height / 2;
+            if *tile_type == TileType::Floor && arena_grid.is_reachable(x, y) {
                 let dist_to_center_sq = ((x as i32 - center_x as i32).pow(2)
                     + (y as i32 - center_y as i32).pow(2))
                     as f32;
@@ -92,144 +129,174 @@ fn spawn_enemies(mut commands: Commands, arena_grid: Res<ArenaGrid>) {
         return;
     }
 
-    let total_arena_width_pixels = ARENA_WIDTH_TILES as f32 * TILE_SIZE;
-    let total_arena_height_pixels = ARENA_HEIGHT_TILES as f32 * TILE_SIZE;
-    let arena_offset_x = -total_arena_width_pixels / 2.0;
-    let arena_offset_y = -total_arena_height_pixels / 2.0;
-
-    for _ in 0..MAX_ENEMIES_SPAWN {
+    let max_enemies = archetypes.enemies.spawning.max_enemies;
+    for _ in 0..max_enemies {
         if let Some(idx) = floor_tiles
             .get(rng.random_range(0..floor_tiles.len()))
             .copied()
         {
             let (grid_x, grid_y) = idx;
-            let world_x = grid_x as f32 * TILE_SIZE + arena_offset_x + TILE_SIZE / 2.0;
-            let world_y = grid_y as f32 * TILE_SIZE + arena_offset_y + TILE_SIZE / 2.0;
+            let position = tile_to_world(&arena_grid, grid_x, grid_y);
 
-            commands.spawn(EnemyBundle::new(Vec3::new(world_x, world_y, 0.0)));
+            spawner.spawn_regular(position, &mut rng);
         }
     }
-    info!(
-        "Spawned {} enemies.",
-        MAX_ENEMIES_SPAWN.min(floor_tiles.len())
-    );
+    info!("Spawned {} enemies.", max_enemies.min(floor_tiles.len()));
 }
 
-fn check_aabb_collision(pos1: Vec2, size1: Vec2, pos2: Vec2, size2: Vec2) -> bool {
-    let half_size1 = size1 / 2.0;
-    let half_size2 = size2 / 2.0;
+fn spawn_room_enemies(spawner: &mut EnemySpawner, arena_grid: &ArenaGrid, rng: &mut impl Rng) {
+    let spawning = &spawner.archetypes.enemies.spawning;
+    let (per_room, per_boss_room) = (spawning.per_room, spawning.per_boss_room);
+
+    let mut spawned = 0;
+    for (room_index, room) in arena_grid.rooms.iter().enumerate() {
+        if !room.kind.has_enemies() {
+            continue;
+        }
+
+        let count = if room.kind == RoomKind::Boss {
+            per_boss_room
+        } else {
+            per_room
+        };
 
-    let min1 = pos1 - half_size1;
-    let max1 = pos1 + half_size1;
-    let min2 = pos2 - half_size2;
-    let max2 = pos2 + half_size2;
+        for index in 0..count {
+            let grid_x = room.bounds.x + rng.random_range(0..room.bounds.width);
+            let grid_y = room.bounds.y + rng.random_range(0..room.bounds.height);
+            let position = tile_to_world(arena_grid, grid_x, grid_y);
 
-    (min1.x < max2.x && max1.x > min2.x) && (min1.y < max2.y && max1.y > min2.y)
+            let enemy = if room.kind == RoomKind::Boss && index == 0 {
+                let boss = spawner.archetypes.enemies.spawning.boss.clone();
+                spawner.spawn(&boss, position, rng)
+            } else {
+                spawner.spawn_regular(position, rng)
+            };
+            spawner
+                .commands
+                .entity(enemy)
+                .insert(RoomOccupant(room_index));
+            spawned += 1;
+        }
+    }
+    info!("Spawned {} enemies across dungeon rooms.", spawned);
 }
 
-fn get_nearby_wall_positions_world(
-    object_pos_world: &Vec2,
-    object_size: Vec2,
-    arena_grid: &Res<ArenaGrid>,
-) -> Vec<Vec2> {
-    let mut wall_positions = Vec::new();
-    let total_arena_width_pixels = ARENA_WIDTH_TILES as f32 * TILE_SIZE;
-    let total_arena_height_pixels = ARENA_HEIGHT_TILES as f32 * TILE_SIZE;
-    let arena_offset_x = -total_arena_width_pixels / 2.0;
-    let arena_offset_y = -total_arena_height_pixels / 2.0;
-    let object_half_size = object_size / 2.0;
-    let search_min_world = *object_pos_world - object_half_size - Vec2::splat(TILE_SIZE * 0.5);
-    let search_max_world = *object_pos_world + object_half_size + Vec2::splat(TILE_SIZE * 0.5);
-    let start_x_grid = ((search_min_world.x - arena_offset_x) / TILE_SIZE).floor() as i32;
-    let end_x_grid = ((search_max_world.x - arena_offset_x) / TILE_SIZE).ceil() as i32;
-    let start_y_grid = ((search_min_world.y - arena_offset_y) / TILE_SIZE).floor() as i32;
-    let end_y_grid = ((search_max_world.y - arena_offset_y) / TILE_SIZE).ceil() as i32;
-
-    for gy in start_y_grid.max(0)..=end_y_grid.min(ARENA_HEIGHT_TILES as i32 - 1) {
-        for gx in start_x_grid.max(0)..=end_x_grid.min(ARENA_WIDTH_TILES as i32 - 1) {
-            let gy_usize = gy as usize;
-            let gx_usize = gx as usize;
-            if arena_grid.grid[gy_usize][gx_usize] == TileType::Wall {
-                let wall_world_x = gx_usize as f32 * TILE_SIZE + arena_offset_x + TILE_SIZE / 2.0;
-                let wall_world_y = gy_usize as f32 * TILE_SIZE + arena_offset_y + TILE_SIZE / 2.0;
-                wall_positions.push(Vec2::new(wall_world_x, wall_world_y));
-            }
+/// Spawns enemies from their archetypes.
+pub(crate) struct EnemySpawner<'a, 'w, 's> {
+    pub commands: &'a mut Commands<'w, 's>,
+    pub archetypes: &'a Archetypes,
+    pub asset_server: &'a AssetServer,
+}
+
+impl EnemySpawner<'_, '_, '_> {
+    /// Spawns a melee enemy, or with the spawn table's ranged chance one carrying a weapon.
+    fn spawn_regular(&mut self, position: Vec2, rng: &mut impl Rng) -> Entity {
+        let spawning = &self.archetypes.enemies.spawning;
+        let pool = if rng.random_bool(spawning.ranged_chance) {
+            &spawning.ranged
+        } else {
+            &spawning.melee
+        };
+        let id = pool[rng.random_range(0..pool.len())].clone();
+        self.spawn(&id, position, rng)
+    }
+
+    pub fn spawn(&mut self, id: &str, position: Vec2, rng: &mut impl Rng) -> Entity {
+        let archetype = self.archetypes.enemy(id);
+        let mut enemy = self.commands.spawn((
+            EnemyBundle::new(archetype, position.extend(0.0), rng.random_range(0.0..TAU)),
+            EnemyKind(id.to_string()),
+        ));
+        if let Some(weapon) = &archetype.weapon {
+            enemy.insert(EnemyWeapon::new(weapon));
+        }
+        if let Some(pattern) = &archetype.pattern {
+            enemy.insert(PendingPattern(self.asset_server.load(pattern.clone())));
         }
+        enemy.id()
     }
-    wall_positions
 }
 
-fn enemy_movement_system(
-    mut enemy_query: Query<(&mut Transform, &Speed, &Sprite), (With<Enemy>, Without<Player>)>,
+#[allow(clippy::type_complexity)]
+pub fn enemy_movement_system(
+    mut enemy_query: Query<
+        (
+            &mut Transform,
+            &Speed,
+            &Sprite,
+            &mut AgentPath,
+            &EnemyAi,
+            &Perception,
+            Option<&EnemyWeapon>,
+        ),
+        (With<Enemy>, Without<Player>, Without<Stunned>),
+    >,
     player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
     time: Res<Time>,
     arena_grid: Res<ArenaGrid>,
+    flow_field: Res<FlowField>,
 ) {
-    if let Ok(player_transform) = player_query.single() {
-        let player_pos = player_transform.translation.truncate();
-
-        for (mut enemy_transform, enemy_speed, enemy_sprite) in enemy_query.iter_mut() {
-            let enemy_current_pos = enemy_transform.translation.truncate();
-            let direction_to_player = (player_pos - enemy_current_pos).normalize_or_zero();
-
-            if direction_to_player != Vec2::ZERO {
-                let move_amount_total = direction_to_player * enemy_speed.0 * time.delta_secs();
-                let enemy_size = enemy_sprite
-                    .custom_size
-                    .unwrap_or(Vec2::splat(ENEMY_SPRITE_SIZE));
-
-                let next_pos_x = enemy_current_pos + Vec2::new(move_amount_total.x, 0.0);
-                let mut collision_x = false;
-                if move_amount_total.x.abs() > f32::EPSILON {
-                    for wall_pos_world in
-                        get_nearby_wall_positions_world(&next_pos_x, enemy_size, &arena_grid)
-                    {
-                        if check_aabb_collision(
-                            next_pos_x,
-                            enemy_size,
-                            wall_pos_world,
-                            Vec2::splat(TILE_SIZE),
-                        ) {
-                            collision_x = true;
-                            break;
-                        }
-                    }
-                }
-                if !collision_x {
-                    enemy_transform.translation.x += move_amount_total.x;
-                }
+    let player_pos = player_query
+        .single()
+        .ok()
+        .map(|transform| transform.translation.truncate());
 
-                let enemy_current_pos_after_x = enemy_transform.translation.truncate();
-                let next_pos_y = enemy_current_pos_after_x + Vec2::new(0.0, move_amount_total.y);
-                let mut collision_y = false;
-                if move_amount_total.y.abs() > f32::EPSILON {
-                    for wall_pos_world in
-                        get_nearby_wall_positions_world(&next_pos_y, enemy_size, &arena_grid)
-                    {
-                        if check_aabb_collision(
-                            next_pos_y,
-                            enemy_size,
-                            wall_pos_world,
-                            Vec2::splat(TILE_SIZE),
-                        ) {
-                            collision_y = true;
-                            break;
-                        }
-                    }
-                }
-                if !collision_y {
-                    enemy_transform.translation.y += move_amount_total.y;
-                }
+    for (mut enemy_transform, enemy_speed, enemy_sprite, mut agent_path, ai, perception, weapon) in
+        enemy_query.iter_mut()
+    {
+        let enemy_current_pos = enemy_transform.translation.truncate();
+        let holding_range = ai.state == AiState::Chase
+            && perception.sees_player
+            && player_pos.zip(weapon).is_some_and(|(player_pos, weapon)| {
+                weapon.holds_at(enemy_current_pos.distance(player_pos))
+            });
+        let destination = if holding_range {
+            None
+        } else {
+            ai.destination(enemy_current_pos, player_pos, perception.sees_player)
+        };
+        let Some(destination) = destination else {
+            continue;
+        };
 
-                let final_enemy_pos = enemy_transform.translation.truncate();
-                let final_direction_to_player = (player_pos - final_enemy_pos).normalize_or_zero();
-                if final_direction_to_player != Vec2::ZERO {
-                    let angle = final_direction_to_player
-                        .y
-                        .atan2(final_direction_to_player.x);
-                    enemy_transform.rotation = Quat::from_rotation_z(angle);
-                }
+        let waypoint = if ai.uses_pathfinding() {
+            next_waypoint(
+                &arena_grid,
+                &flow_field,
+                &mut agent_path,
+                enemy_current_pos,
+                destination,
+            )
+        } else {
+            destination
+        };
+        let direction_to_waypoint = (waypoint - enemy_current_pos).normalize_or_zero();
+
+        if direction_to_waypoint != Vec2::ZERO {
+            let move_amount_total =
+                direction_to_waypoint * enemy_speed.0 * ai.speed_fraction() * time.delta_secs();
+            let enemy_size = enemy_sprite
+                .custom_size
+                .unwrap_or(Vec2::splat(ENEMY_SPRITE_SIZE));
+
+            let result = move_and_slide(
+                &arena_grid,
+                enemy_current_pos,
+                enemy_size,
+                move_amount_total,
+            );
+            enemy_transform.translation.x = result.position.x;
+            enemy_transform.translation.y = result.position.y;
+        }
+
+        let facing = match player_pos {
+            Some(player_pos) if perception.sees_player && ai.state != AiState::Flee => {
+                (player_pos - enemy_transform.translation.truncate()).normalize_or_zero()
             }
+            _ => direction_to_waypoint,
+        };
+        if facing != Vec2::ZERO {
+            enemy_transform.rotation = Quat::from_rotation_z(facing.y.atan2(facing.x));
         }
     }
 }
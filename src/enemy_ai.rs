@@ -0,0 +1,551 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::Rng;
+use rand::rngs::StdRng;
+
+use crate::GameState;
+use crate::arena::{ArenaGrid, TILE_SIZE, setup_arena};
+use crate::blank::Stunned;
+use crate::collision::{is_solid_tile, raycast_tiles, tile_to_world, world_to_tile};
+use crate::enemy::{Enemy, enemy_movement_system};
+use crate::floor::{CurrentFloor, SetupFloor};
+use crate::player::{Health, Player};
+use crate::seed::{RngStream, RunSeed};
+
+const ENEMY_VISION_RANGE: f32 = 12.0 * TILE_SIZE;
+const ENEMY_VISION_HALF_ANGLE_DEGREES: f32 = 55.0;
+
+const MIN_IDLE_SECONDS: f32 = 1.5;
+const MAX_IDLE_SECONDS: f32 = 4.0;
+const MAX_PATROL_SECONDS: f32 = 8.0;
+const ALERT_SECONDS: f32 = 5.0;
+const LOSE_TRACK_SECONDS: f32 = 3.0;
+const FLEE_SECONDS: f32 = 2.5;
+
+const PATROL_RADIUS_TILES: i32 = 6;
+const PATROL_TARGET_ATTEMPTS: usize = 10;
+const ARRIVE_DISTANCE: f32 = TILE_SIZE * 0.5;
+const ATTACK_RANGE: f32 = 2.0 * TILE_SIZE;
+const ATTACK_EXIT_RANGE: f32 = 3.0 * TILE_SIZE;
+const FLEE_HEALTH_FRACTION: f32 = 0.25;
+
+const PATROL_SPEED_FRACTION: f32 = 0.5;
+const ALERT_SPEED_FRACTION: f32 = 0.8;
+const ATTACK_SPEED_FRACTION: f32 = 1.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AiState {
+    #[default]
+    Idle,
+    Patrol,
+    Alert,
+    Chase,
+    Attack,
+    Flee,
+}
+
+/// A sound enemies within `radius` of `position` can hear, such as a gunshot.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct NoiseEvent {
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+/// What an enemy noticed this frame, refreshed by [`enemy_perception_system`].
+#[derive(Component)]
+pub struct Perception {
+    pub vision_range: f32,
+    pub vision_half_angle: f32,
+    pub sees_player: bool,
+    pub heard: Option<Vec2>,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            vision_range: ENEMY_VISION_RANGE,
+            vision_half_angle: ENEMY_VISION_HALF_ANGLE_DEGREES.to_radians(),
+            sees_player: false,
+            heard: None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct EnemyAi {
+    pub state: AiState,
+    /// Where the enemy is heading while patrolling or investigating.
+    pub target: Option<Vec2>,
+    pub last_known_player: Option<Vec2>,
+    state_timer: Timer,
+    lost_track: Timer,
+    has_fled: bool,
+}
+
+impl Default for EnemyAi {
+    fn default() -> Self {
+        Self {
+            state: AiState::Idle,
+            target: None,
+            last_known_player: None,
+            state_timer: Timer::from_seconds(MIN_IDLE_SECONDS, TimerMode::Once),
+            lost_track: Timer::from_seconds(LOSE_TRACK_SECONDS, TimerMode::Once),
+            has_fled: false,
+        }
+    }
+}
+
+/// Everything the state machine looks at for one enemy on one frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Observation {
+    pub position: Vec2,
+    pub player: Option<Vec2>,
+    pub sees_player: bool,
+    pub heard: Option<Vec2>,
+    pub health_fraction: f32,
+}
+
+impl EnemyAi {
+    /// Advances the state machine by `delta`. Entering [`AiState::Patrol`] leaves `target`
+    /// empty for the caller to fill in, since picking one needs the arena.
+    pub fn update(&mut self, observation: &Observation, delta: Duration, rng: &mut impl Rng) {
+        self.state_timer.tick(delta);
+
+        let player_distance = observation.player.map_or(f32::INFINITY, |player| {
+            player.distance(observation.position)
+        });
+
+        if observation.sees_player {
+            self.last_known_player = observation.player;
+            self.lost_track.reset();
+        } else if let Some(heard) = observation.heard {
+            if matches!(self.state, AiState::Chase | AiState::Attack) {
+                self.last_known_player = Some(heard);
+            }
+        } else {
+            self.lost_track.tick(delta);
+        }
+
+        let wants_to_flee = !self.has_fled && observation.health_fraction <= FLEE_HEALTH_FRACTION;
+
+        match self.state {
+            AiState::Idle | AiState::Patrol => {
+                if observation.sees_player {
+                    self.enter(AiState::Chase, rng);
+                } else if let Some(heard) = observation.heard {
+                    self.investigate(heard, rng);
+                } else if self.state == AiState::Idle && self.state_timer.finished() {
+                    self.enter(AiState::Patrol, rng);
+                } else if self.state == AiState::Patrol
+                    && (self.state_timer.finished()
+                        || self.target.is_some_and(|target| {
+                            target.distance(observation.position) <= ARRIVE_DISTANCE
+                        }))
+                {
+                    self.enter(AiState::Idle, rng);
+                }
+            }
+            AiState::Alert => {
+                if observation.sees_player {
+                    self.enter(AiState::Chase, rng);
+                } else if let Some(heard) = observation.heard {
+                    self.investigate(heard, rng);
+                } else if self.state_timer.finished()
+                    || self.target.is_none_or(|target| {
+                        target.distance(observation.position) <= ARRIVE_DISTANCE
+                    })
+                {
+                    self.enter(AiState::Idle, rng);
+                }
+            }
+            AiState::Chase | AiState::Attack => {
+                if wants_to_flee {
+                    self.has_fled = true;
+                    self.enter(AiState::Flee, rng);
+                } else if self.lost_track.finished() {
+                    if let Some(last_known) = self.last_known_player {
+                        self.investigate(last_known, rng);
+                    } else {
+                        self.enter(AiState::Idle, rng);
+                    }
+                } else if self.state == AiState::Chase
+                    && observation.sees_player
+                    && player_distance <= ATTACK_RANGE
+                {
+                    self.enter(AiState::Attack, rng);
+                } else if self.state == AiState::Attack
+                    && (!observation.sees_player || player_distance > ATTACK_EXIT_RANGE)
+                {
+                    self.enter(AiState::Chase, rng);
+                }
+            }
+            AiState::Flee => {
+                if self.state_timer.finished() {
+                    if observation.sees_player {
+                        self.enter(AiState::Chase, rng);
+                    } else if let Some(last_known) = self.last_known_player {
+                        self.investigate(last_known, rng);
+                    } else {
+                        self.enter(AiState::Idle, rng);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Where the enemy wants to move this frame, or `None` to stand still.
+    pub fn destination(
+        &self,
+        position: Vec2,
+        player: Option<Vec2>,
+        sees_player: bool,
+    ) -> Option<Vec2> {
+        match self.state {
+            AiState::Idle => None,
+            AiState::Patrol | AiState::Alert => self.target,
+            AiState::Chase => {
+                if sees_player {
+                    player
+                } else {
+                    self.last_known_player
+                }
+            }
+            AiState::Attack => player,
+            AiState::Flee => {
+                let threat = player.or(self.last_known_player)?;
+                Some(position + (position - threat).normalize_or_zero() * TILE_SIZE)
+            }
+        }
+    }
+
+    /// Attack lunges and fleeing run in a straight line; everything else follows the arena paths.
+    pub fn uses_pathfinding(&self) -> bool {
+        !matches!(self.state, AiState::Attack | AiState::Flee)
+    }
+
+    pub fn speed_fraction(&self) -> f32 {
+        match self.state {
+            AiState::Patrol => PATROL_SPEED_FRACTION,
+            AiState::Alert => ALERT_SPEED_FRACTION,
+            AiState::Attack => ATTACK_SPEED_FRACTION,
+            AiState::Idle | AiState::Chase | AiState::Flee => 1.0,
+        }
+    }
+
+    fn investigate(&mut self, position: Vec2, rng: &mut impl Rng) {
+        self.enter(AiState::Alert, rng);
+        self.target = Some(position);
+    }
+
+    fn enter(&mut self, state: AiState, rng: &mut impl Rng) {
+        let seconds = match state {
+            AiState::Idle => rng.random_range(MIN_IDLE_SECONDS..=MAX_IDLE_SECONDS),
+            AiState::Patrol => MAX_PATROL_SECONDS,
+            AiState::Alert => ALERT_SECONDS,
+            AiState::Flee => FLEE_SECONDS,
+            AiState::Chase | AiState::Attack => 0.0,
+        };
+
+        self.state = state;
+        self.target = None;
+        self.state_timer = Timer::from_seconds(seconds, TimerMode::Once);
+        if state == AiState::Chase {
+            self.lost_track.reset();
+        }
+    }
+}
+
+/// Whether `target` is inside the cone of `range` and `half_angle` around `facing` from `eye`
+/// with no wall tile in between.
+pub fn can_see(
+    arena_grid: &ArenaGrid,
+    eye: Vec2,
+    facing: Vec2,
+    target: Vec2,
+    range: f32,
+    half_angle: f32,
+) -> bool {
+    let to_target = target - eye;
+    let distance = to_target.length();
+    if distance > range {
+        return false;
+    }
+    if distance <= f32::EPSILON {
+        return true;
+    }
+
+    let direction = to_target / distance;
+    if facing.angle_to(direction).abs() > half_angle {
+        return false;
+    }
+
+    raycast_tiles(arena_grid, eye, direction, distance).is_none()
+}
+
+/// Seeded RNG for enemy decisions so a run seed replays the same behaviour.
+#[derive(Resource)]
+struct AiRng(StdRng);
+
+pub struct EnemyAiPlugin;
+
+impl Plugin for EnemyAiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<NoiseEvent>()
+            .add_systems(SetupFloor, setup_ai_rng.after(setup_arena))
+            .add_systems(
+                FixedUpdate,
+                (enemy_perception_system, enemy_ai_state_system)
+                    .chain()
+                    .before(enemy_movement_system)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+fn setup_ai_rng(mut commands: Commands, run_seed: Res<RunSeed>, floor: Res<CurrentFloor>) {
+    commands.insert_resource(AiRng(run_seed.rng(RngStream::EnemyBehaviour, floor.0)));
+}
+
+pub fn enemy_perception_system(
+    mut enemy_query: Query<(&Transform, &mut Perception), With<Enemy>>,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
+    arena_grid: Res<ArenaGrid>,
+    mut noise_events: EventReader<NoiseEvent>,
+) {
+    let noises: Vec<NoiseEvent> = noise_events.read().copied().collect();
+    let player_pos = player_query
+        .single()
+        .ok()
+        .map(|transform| transform.translation.truncate());
+
+    for (transform, mut perception) in enemy_query.iter_mut() {
+        let eye = transform.translation.truncate();
+        let facing = (transform.rotation * Vec3::X).truncate();
+
+        perception.sees_player = player_pos.is_some_and(|player| {
+            can_see(
+                &arena_grid,
+                eye,
+                facing,
+                player,
+                perception.vision_range,
+                perception.vision_half_angle,
+            )
+        });
+        perception.heard = noises
+            .iter()
+            .filter(|noise| noise.position.distance(eye) <= noise.radius)
+            .min_by(|a, b| {
+                a.position
+                    .distance(eye)
+                    .total_cmp(&b.position.distance(eye))
+            })
+            .map(|noise| noise.position);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn enemy_ai_state_system(
+    mut enemy_query: Query<
+        (&Transform, &Health, &Perception, &mut EnemyAi),
+        (With<Enemy>, Without<Stunned>),
+    >,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
+    arena_grid: Res<ArenaGrid>,
+    mut ai_rng: ResMut<AiRng>,
+    time: Res<Time>,
+) {
+    let player_pos = player_query
+        .single()
+        .ok()
+        .map(|transform| transform.translation.truncate());
+
+    for (transform, health, perception, mut ai) in enemy_query.iter_mut() {
+        let position = transform.translation.truncate();
+        let previous_state = ai.state;
+
+        ai.update(
+            &Observation {
+                position,
+                player: player_pos,
+                sees_player: perception.sees_player,
+                heard: perception.heard,
+                health_fraction: health.current / health.max,
+            },
+            time.delta(),
+            &mut ai_rng.0,
+        );
+
+        if ai.state == AiState::Patrol && ai.target.is_none() {
+            ai.target = pick_patrol_target(&arena_grid, position, &mut ai_rng.0);
+        }
+
+        if ai.state != previous_state {
+            debug!("Enemy AI {:?} -> {:?}", previous_state, ai.state);
+        }
+    }
+}
+
+/// A random open tile near `position`, kept inside the same dungeon room when there is one.
+fn pick_patrol_target(arena_grid: &ArenaGrid, position: Vec2, rng: &mut impl Rng) -> Option<Vec2> {
+    let origin = world_to_tile(arena_grid, position);
+    let origin_room = (origin.min_element() >= 0)
+        .then(|| arena_grid.room_at(origin.x as usize, origin.y as usize))
+        .flatten();
+
+    (0..PATROL_TARGET_ATTEMPTS).find_map(|_| {
+        let tile = origin
+            + IVec2::new(
+                rng.random_range(-PATROL_RADIUS_TILES..=PATROL_RADIUS_TILES),
+                rng.random_range(-PATROL_RADIUS_TILES..=PATROL_RADIUS_TILES),
+            );
+        if is_solid_tile(arena_grid, tile) {
+            return None;
+        }
+
+        let (x, y) = (tile.x as usize, tile.y as usize);
+        (arena_grid.is_reachable(x, y) && arena_grid.room_at(x, y) == origin_room)
+            .then(|| tile_to_world(arena_grid, x, y))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::arena::TileType;
+
+    fn open_room_with_pillar() -> ArenaGrid {
+        let mut grid = vec![vec![TileType::Floor; 20]; 9];
+        for row in grid.iter_mut() {
+            row[0] = TileType::Wall;
+            row[19] = TileType::Wall;
+        }
+        grid[0] = vec![TileType::Wall; 20];
+        grid[8] = vec![TileType::Wall; 20];
+        grid[4][10] = TileType::Wall;
+
+        ArenaGrid::from_tiles(grid)
+    }
+
+    fn observe(position: Vec2, player: Vec2, sees_player: bool) -> Observation {
+        Observation {
+            position,
+            player: Some(player),
+            sees_player,
+            heard: None,
+            health_fraction: 1.0,
+        }
+    }
+
+    #[test]
+    fn vision_respects_range_cone_and_walls() {
+        let arena_grid = open_room_with_pillar();
+        let eye = tile_to_world(&arena_grid, 5, 4);
+        let half_angle = 45f32.to_radians();
+
+        let ahead = tile_to_world(&arena_grid, 8, 4);
+        assert!(can_see(&arena_grid, eye, Vec2::X, ahead, 100.0, half_angle));
+        assert!(!can_see(
+            &arena_grid,
+            eye,
+            -Vec2::X,
+            ahead,
+            100.0,
+            half_angle
+        ));
+        assert!(!can_see(&arena_grid, eye, Vec2::X, ahead, 30.0, half_angle));
+
+        let behind_pillar = tile_to_world(&arena_grid, 14, 4);
+        assert!(!can_see(
+            &arena_grid,
+            eye,
+            Vec2::X,
+            behind_pillar,
+            200.0,
+            half_angle
+        ));
+    }
+
+    #[test]
+    fn idle_enemy_only_aggroes_on_detection() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut ai = EnemyAi::default();
+        let player = Vec2::new(100.0, 0.0);
+        let step = Duration::from_millis(100);
+
+        ai.update(&observe(Vec2::ZERO, player, false), step, &mut rng);
+        assert_eq!(ai.state, AiState::Idle);
+
+        ai.update(&observe(Vec2::ZERO, player, true), step, &mut rng);
+        assert_eq!(ai.state, AiState::Chase);
+        assert_eq!(ai.last_known_player, Some(player));
+    }
+
+    #[test]
+    fn gunshots_send_idle_enemies_to_investigate() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut ai = EnemyAi::default();
+        let shot = Vec2::new(-60.0, 30.0);
+
+        let mut observation = observe(Vec2::ZERO, shot, false);
+        observation.heard = Some(shot);
+        ai.update(&observation, Duration::from_millis(16), &mut rng);
+
+        assert_eq!(ai.state, AiState::Alert);
+        assert_eq!(ai.destination(Vec2::ZERO, Some(shot), false), Some(shot));
+    }
+
+    #[test]
+    fn chasing_enemy_loses_track_after_timeout() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut ai = EnemyAi::default();
+        let player = Vec2::new(100.0, 0.0);
+        let step = Duration::from_millis(100);
+
+        ai.update(&observe(Vec2::ZERO, player, true), step, &mut rng);
+        assert_eq!(ai.state, AiState::Chase);
+
+        let hidden = observe(Vec2::ZERO, Vec2::new(300.0, 0.0), false);
+        for _ in 0..(LOSE_TRACK_SECONDS * 10.0) as usize - 1 {
+            ai.update(&hidden, step, &mut rng);
+            assert_eq!(ai.state, AiState::Chase);
+        }
+        ai.update(&hidden, step, &mut rng);
+        assert_eq!(ai.state, AiState::Alert);
+        assert_eq!(ai.target, Some(player));
+
+        for _ in 0..(ALERT_SECONDS * 10.0) as usize {
+            ai.update(&hidden, step, &mut rng);
+        }
+        assert_eq!(ai.state, AiState::Idle);
+    }
+
+    #[test]
+    fn close_enemy_attacks_and_wounded_enemy_flees_once() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut ai = EnemyAi::default();
+        let player = Vec2::new(ATTACK_RANGE * 0.5, 0.0);
+        let step = Duration::from_millis(100);
+
+        ai.update(&observe(Vec2::ZERO, player, true), step, &mut rng);
+        ai.update(&observe(Vec2::ZERO, player, true), step, &mut rng);
+        assert_eq!(ai.state, AiState::Attack);
+
+        let mut wounded = observe(Vec2::ZERO, player, true);
+        wounded.health_fraction = FLEE_HEALTH_FRACTION;
+        ai.update(&wounded, step, &mut rng);
+        assert_eq!(ai.state, AiState::Flee);
+        let away = ai.destination(Vec2::ZERO, Some(player), true).unwrap();
+        assert!(away.x < 0.0);
+
+        for _ in 0..(FLEE_SECONDS * 10.0) as usize {
+            ai.update(&wounded, step, &mut rng);
+        }
+        assert_eq!(ai.state, AiState::Chase);
+        ai.update(&wounded, step, &mut rng);
+        assert_eq!(ai.state, AiState::Attack);
+    }
+}
@@ -0,0 +1,238 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::archetype::Archetypes;
+use crate::blank::Stunned;
+use crate::enemy::{Enemy, enemy_movement_system};
+use crate::enemy_ai::{AiState, EnemyAi, Perception};
+use crate::player::{Faction, Player, ProjectileBundle, Velocity};
+
+/// Ranged enemies stop advancing once the player is this fraction of their range away.
+const HOLD_RANGE_FRACTION: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum AimMode {
+    /// Straight at the player's current position.
+    Aimed,
+    /// At where the player will be if they keep moving at their current velocity.
+    Leading,
+    /// `count` shots fanned evenly across `arc_degrees`, centred on the player.
+    Spread { count: usize, arc_degrees: f32 },
+}
+
+/// An enemy weapon as written in the enemy archetype file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyWeaponStats {
+    pub aim: AimMode,
+    pub projectile_speed: f32,
+    pub projectile_damage: f32,
+    pub range: f32,
+    pub cooldown_seconds: f32,
+}
+
+#[derive(Component)]
+pub struct EnemyWeapon {
+    pub aim: AimMode,
+    pub projectile_speed: f32,
+    pub projectile_damage: f32,
+    pub range: f32,
+    pub cooldown: Timer,
+}
+
+impl EnemyWeapon {
+    pub fn new(stats: &EnemyWeaponStats) -> Self {
+        Self {
+            aim: stats.aim,
+            projectile_speed: stats.projectile_speed,
+            projectile_damage: stats.projectile_damage,
+            range: stats.range,
+            cooldown: Timer::from_seconds(stats.cooldown_seconds, TimerMode::Once),
+        }
+    }
+
+    /// Whether an enemy carrying this weapon should stop closing in at `distance`.
+    pub fn holds_at(&self, distance: f32) -> bool {
+        distance <= self.range * HOLD_RANGE_FRACTION
+    }
+
+    pub fn shot_directions(&self, origin: Vec2, target: Vec2, target_velocity: Vec2) -> Vec<Vec2> {
+        let aimed = (target - origin).normalize_or_zero();
+        match self.aim {
+            AimMode::Aimed => vec![aimed],
+            AimMode::Leading => vec![intercept_direction(
+                origin,
+                target,
+                target_velocity,
+                self.projectile_speed,
+            )],
+            AimMode::Spread { count, arc_degrees } => {
+                if count <= 1 {
+                    return vec![aimed];
+                }
+                let step = arc_degrees.to_radians() / (count - 1) as f32;
+                let first = -arc_degrees.to_radians() / 2.0;
+                (0..count)
+                    .map(|index| Vec2::from_angle(first + step * index as f32).rotate(aimed))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Direction to fire a projectile of `projectile_speed` from `origin` so it meets a target
+/// moving at constant `target_velocity`. Falls back to aiming straight at the target when it
+/// can outrun the shot.
+pub fn intercept_direction(
+    origin: Vec2,
+    target: Vec2,
+    target_velocity: Vec2,
+    projectile_speed: f32,
+) -> Vec2 {
+    let offset = target - origin;
+    let a = target_velocity.length_squared() - projectile_speed * projectile_speed;
+    let b = 2.0 * offset.dot(target_velocity);
+    let c = offset.length_squared();
+
+    let time = if a.abs() < f32::EPSILON {
+        (b < 0.0).then(|| -c / b)
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            None
+        } else {
+            let root = discriminant.sqrt();
+            [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+                .into_iter()
+                .filter(|time| *time > 0.0)
+                .min_by(f32::total_cmp)
+        }
+    };
+
+    match time {
+        Some(time) => (offset + target_velocity * time).normalize_or_zero(),
+        None => offset.normalize_or_zero(),
+    }
+}
+
+pub struct EnemyWeaponPlugin;
+
+impl Plugin for EnemyWeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            enemy_shooting_system
+                .after(enemy_movement_system)
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn enemy_shooting_system(
+    mut commands: Commands,
+    mut enemy_query: Query<
+        (&Transform, &EnemyAi, &Perception, &mut EnemyWeapon),
+        (With<Enemy>, Without<Stunned>),
+    >,
+    player_query: Query<(&Transform, &Velocity), With<Player>>,
+    time: Res<Time>,
+    archetypes: Res<Archetypes>,
+) {
+    let Ok((player_transform, player_velocity)) = player_query.single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+    let projectile = archetypes.projectile(Faction::Enemy);
+
+    for (enemy_transform, ai, perception, mut weapon) in enemy_query.iter_mut() {
+        let engaged =
+            matches!(ai.state, AiState::Chase | AiState::Attack) && perception.sees_player;
+        if !engaged {
+            continue;
+        }
+
+        weapon.cooldown.tick(time.delta());
+        let origin = enemy_transform.translation.truncate();
+        if !weapon.cooldown.finished() || origin.distance(player_pos) > weapon.range {
+            continue;
+        }
+
+        for direction in weapon.shot_directions(origin, player_pos, player_velocity.0) {
+            commands.spawn(ProjectileBundle::new(
+                projectile,
+                Faction::Enemy,
+                origin + direction * projectile.spawn_offset,
+                direction,
+                weapon.projectile_speed,
+                weapon.projectile_damage,
+            ));
+        }
+        weapon.cooldown.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_shots_meet_a_moving_target() {
+        let origin = Vec2::ZERO;
+        let target = Vec2::new(200.0, 0.0);
+        let velocity = Vec2::new(0.0, 100.0);
+        let speed = 300.0;
+
+        let direction = intercept_direction(origin, target, velocity, speed);
+        let time = (0..2000)
+            .map(|step| step as f32 * 0.001)
+            .min_by(|a, b| {
+                let miss =
+                    |t: f32| (origin + direction * speed * t).distance(target + velocity * t);
+                miss(*a).total_cmp(&miss(*b))
+            })
+            .unwrap();
+        let miss = (origin + direction * speed * time).distance(target + velocity * time);
+        assert!(miss < 1.0, "missed by {miss}");
+    }
+
+    #[test]
+    fn leading_falls_back_to_aimed_when_the_target_outruns_the_shot() {
+        let direction = intercept_direction(
+            Vec2::ZERO,
+            Vec2::new(100.0, 0.0),
+            Vec2::new(500.0, 0.0),
+            100.0,
+        );
+        assert_eq!(direction, Vec2::X);
+    }
+
+    #[test]
+    fn spread_fans_evenly_around_the_target() {
+        let weapon = EnemyWeapon::new(&EnemyWeaponStats {
+            aim: AimMode::Spread {
+                count: 5,
+                arc_degrees: 40.0,
+            },
+            projectile_speed: 150.0,
+            projectile_damage: 8.0,
+            range: 150.0,
+            cooldown_seconds: 1.8,
+        });
+        let directions = weapon.shot_directions(Vec2::ZERO, Vec2::new(0.0, 50.0), Vec2::ZERO);
+
+        assert_eq!(directions.len(), 5);
+        let half_arc = 20f32.to_radians();
+        assert!((Vec2::Y.angle_to(directions[0]) + half_arc).abs() < 1e-4);
+        assert!((Vec2::Y.angle_to(directions[4]) - half_arc).abs() < 1e-4);
+        assert!(directions[2].abs_diff_eq(Vec2::Y, 1e-4));
+    }
+
+    #[test]
+    fn projectiles_only_hit_the_other_faction() {
+        assert!(Faction::Enemy.can_hit(true));
+        assert!(!Faction::Enemy.can_hit(false));
+        assert!(Faction::Player.can_hit(false));
+        assert!(!Faction::Player.can_hit(true));
+    }
+}
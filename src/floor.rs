@@ -0,0 +1,313 @@
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+
+use crate::arena::{ArenaGrid, TILE_SIZE};
+use crate::collision::{check_aabb_collision, tile_to_world, world_to_tile};
+use crate::combat::EnemyDeathEvent;
+use crate::enemy::Enemy;
+use crate::player::Player;
+use crate::seed::RunSeed;
+use crate::{GameState, InRun};
+
+const PLAYER_FALLBACK_SIZE: f32 = 10.0;
+const STAIRS_SIZE: f32 = TILE_SIZE * 0.8;
+const STAIRS_COLOR: Color = Color::srgb(0.85, 0.75, 0.3);
+/// How far from the spawn the stairs go on a floor without enemies.
+const STAIRS_SPAWN_DISTANCE_TILES: usize = 3;
+
+/// Builds a floor: the arena, its enemies and loot, and the player placed at the spawn. Runs
+/// when a run starts and again for every floor after it.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SetupFloor;
+
+/// Which floor of the run the player is on, starting at 1.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrentFloor(pub u32);
+
+impl Default for CurrentFloor {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+/// Tags entities that belong to the current floor. They're despawned when the player moves on
+/// to the next floor and, like everything else in the run, when the run ends.
+#[derive(Component, Default)]
+#[require(StateScoped<InRun>)]
+pub struct FloorEntity;
+
+/// Appears once a floor's enemies are all dead, or straight away on a floor without any. Walking
+/// onto it leads to the next floor.
+#[derive(Component)]
+#[require(FloorEntity)]
+pub struct Stairs;
+
+pub struct FloorPlugin;
+
+impl Plugin for FloorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurrentFloor>()
+            .enable_state_scoped_entities::<InRun>()
+            .add_systems(OnEnter(InRun), start_first_floor)
+            .add_systems(OnExit(InRun), end_run)
+            .add_systems(
+                FixedUpdate,
+                (spawn_stairs_system, advance_floor.run_if(player_on_stairs))
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+fn start_first_floor(world: &mut World) {
+    world.insert_resource(CurrentFloor::default());
+    setup_floor(world);
+}
+
+/// Entities are scoped to the run and go by themselves; this resets what's left.
+fn end_run(mut commands: Commands, mut run_seed: ResMut<RunSeed>) {
+    commands.remove_resource::<ArenaGrid>();
+    run_seed.reroll();
+}
+
+/// Tears down the current floor and builds it again for the current floor number.
+pub fn rebuild_floor(world: &mut World) {
+    let floor_entities: Vec<Entity> = world
+        .query_filtered::<Entity, With<FloorEntity>>()
+        .iter(world)
+        .collect();
+    for entity in floor_entities {
+        world.despawn(entity);
+    }
+    world.remove_resource::<ArenaGrid>();
+
+    setup_floor(world);
+}
+
+/// Builds the floor. One that comes without enemies has nothing to clear, so its stairs open
+/// straight away.
+fn setup_floor(world: &mut World) {
+    world.run_schedule(SetupFloor);
+
+    if world
+        .query_filtered::<(), With<Enemy>>()
+        .iter(world)
+        .next()
+        .is_some()
+    {
+        return;
+    }
+    let arena_grid = world.resource::<ArenaGrid>();
+    let (x, y) = stairs_tile_near_spawn(arena_grid);
+    let position = tile_to_world(arena_grid, x, y);
+    world.spawn(stairs(position));
+    info!("Floor has no enemies, the stairs are open.");
+}
+
+pub fn advance_floor(world: &mut World) {
+    let mut floor = world.resource_mut::<CurrentFloor>();
+    floor.0 += 1;
+    info!("Descending to floor {}.", floor.0);
+    rebuild_floor(world);
+}
+
+/// Puts the stairs down where the last enemy died.
+fn spawn_stairs_system(
+    mut commands: Commands,
+    mut death_events: EventReader<EnemyDeathEvent>,
+    enemy_query: Query<Entity, With<Enemy>>,
+    stairs_query: Query<(), With<Stairs>>,
+    arena_grid: Res<ArenaGrid>,
+) {
+    let deaths: Vec<EnemyDeathEvent> = death_events.read().copied().collect();
+    let Some(last_death) = deaths.last() else {
+        return;
+    };
+    if !stairs_query.is_empty() {
+        return;
+    }
+    // Killed enemies are despawned by command, so they can still show up here this frame.
+    let any_alive = enemy_query
+        .iter()
+        .any(|enemy| deaths.iter().all(|death| death.entity != enemy));
+    if any_alive {
+        return;
+    }
+
+    let tile = world_to_tile(&arena_grid, last_death.position).max(IVec2::ZERO);
+    let position = tile_to_world(&arena_grid, tile.x as usize, tile.y as usize);
+    commands.spawn(stairs(position));
+    info!("Floor cleared, the stairs are open.");
+}
+
+fn stairs(position: Vec2) -> impl Bundle {
+    (
+        Stairs,
+        Sprite {
+            color: STAIRS_COLOR,
+            custom_size: Some(Vec2::splat(STAIRS_SIZE)),
+            ..default()
+        },
+        Transform::from_translation(position.extend(-0.5)),
+        Visibility::Visible,
+    )
+}
+
+/// The reachable open tile closest to the spawn that's far enough away for the player not to
+/// start on the stairs.
+fn stairs_tile_near_spawn(arena_grid: &ArenaGrid) -> (usize, usize) {
+    let (spawn_x, spawn_y) = arena_grid.spawn_tile();
+    let min_distance_squared = STAIRS_SPAWN_DISTANCE_TILES.pow(2);
+    (0..arena_grid.height)
+        .flat_map(|y| (0..arena_grid.width).map(move |x| (x, y)))
+        .filter(|&(x, y)| !arena_grid.grid[y][x].is_solid() && arena_grid.is_reachable(x, y))
+        .map(|(x, y)| {
+            (
+                (x, y),
+                x.abs_diff(spawn_x).pow(2) + y.abs_diff(spawn_y).pow(2),
+            )
+        })
+        .filter(|&(_, distance_squared)| distance_squared >= min_distance_squared)
+        .min_by_key(|&(_, distance_squared)| distance_squared)
+        .map_or((spawn_x, spawn_y), |(tile, _)| tile)
+}
+
+/// True on the frame the player steps onto the stairs, so stairs that open underfoot wait for
+/// the player to step off and back on.
+fn player_on_stairs(
+    player_query: Query<(&Transform, &Sprite), With<Player>>,
+    stairs_query: Query<&Transform, With<Stairs>>,
+    mut was_on_stairs: Local<bool>,
+) -> bool {
+    let Ok((player_transform, player_sprite)) = player_query.single() else {
+        return false;
+    };
+    let player_size = player_sprite
+        .custom_size
+        .unwrap_or(Vec2::splat(PLAYER_FALLBACK_SIZE));
+
+    let on_stairs = stairs_query.iter().any(|stairs_transform| {
+        check_aabb_collision(
+            player_transform.translation.truncate(),
+            player_size,
+            stairs_transform.translation.truncate(),
+            Vec2::splat(STAIRS_SIZE),
+        )
+    });
+    let stepped_on = on_stairs && !*was_on_stairs;
+    *was_on_stairs = on_stairs;
+    stepped_on
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+    use crate::arena::TileType;
+
+    #[derive(Component)]
+    struct Marker(u32);
+
+    /// Stands in for the real floor setup: an arena, one entity for the floor, and the player.
+    fn setup_test_floor(
+        mut commands: Commands,
+        floor: Res<CurrentFloor>,
+        player_query: Query<(), With<Player>>,
+    ) {
+        let grid = vec![vec![TileType::Floor; 8]; 8];
+        commands.insert_resource(ArenaGrid::from_tiles(grid));
+        commands.spawn((FloorEntity, Marker(floor.0)));
+        if player_query.is_empty() {
+            commands.spawn(Player);
+        }
+    }
+
+    fn run_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .init_state::<GameState>()
+            .add_computed_state::<InRun>()
+            .insert_resource(RunSeed::random())
+            .add_event::<EnemyDeathEvent>()
+            .add_plugins(FloorPlugin)
+            .add_systems(SetupFloor, setup_test_floor);
+        app
+    }
+
+    fn set_state(app: &mut App, state: GameState) {
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(state);
+        app.update();
+    }
+
+    fn markers(app: &mut App) -> Vec<u32> {
+        app.world_mut()
+            .query::<&Marker>()
+            .iter(app.world())
+            .map(|marker| marker.0)
+            .collect()
+    }
+
+    fn players(app: &mut App) -> Vec<Entity> {
+        app.world_mut()
+            .query_filtered::<Entity, With<Player>>()
+            .iter(app.world())
+            .collect()
+    }
+
+    #[test]
+    fn next_floor_replaces_the_floor_but_keeps_the_player() {
+        let mut app = run_app();
+        set_state(&mut app, GameState::InGame);
+        assert_eq!(markers(&mut app), vec![1]);
+        let player = players(&mut app);
+        assert_eq!(player.len(), 1);
+
+        advance_floor(app.world_mut());
+        assert_eq!(*app.world().resource::<CurrentFloor>(), CurrentFloor(2));
+        assert_eq!(markers(&mut app), vec![2]);
+        assert_eq!(players(&mut app), player);
+    }
+
+    #[test]
+    fn leaving_the_run_clears_it_for_the_next_one() {
+        let mut app = run_app();
+        set_state(&mut app, GameState::InGame);
+        advance_floor(app.world_mut());
+
+        set_state(&mut app, GameState::Paused);
+        set_state(&mut app, GameState::InGame);
+        assert_eq!(markers(&mut app), vec![2], "pausing keeps the floor");
+
+        set_state(&mut app, GameState::GameOver);
+        assert!(markers(&mut app).is_empty());
+        assert!(players(&mut app).is_empty());
+        assert!(!app.world().contains_resource::<ArenaGrid>());
+
+        set_state(&mut app, GameState::InGame);
+        assert_eq!(markers(&mut app), vec![1]);
+        assert_eq!(players(&mut app).len(), 1);
+    }
+
+    fn stairs_positions(app: &mut App) -> Vec<Vec2> {
+        app.world_mut()
+            .query_filtered::<&Transform, With<Stairs>>()
+            .iter(app.world())
+            .map(|transform| transform.translation.truncate())
+            .collect()
+    }
+
+    #[test]
+    fn a_floor_without_enemies_opens_the_stairs_away_from_the_spawn() {
+        let mut app = run_app();
+        set_state(&mut app, GameState::InGame);
+        let arena_grid = app.world().resource::<ArenaGrid>();
+        let (spawn_x, spawn_y) = arena_grid.spawn_tile();
+        let spawn = tile_to_world(arena_grid, spawn_x, spawn_y);
+        let stairs = stairs_positions(&mut app);
+        assert_eq!(stairs.len(), 1);
+        assert!(stairs[0].distance(spawn) >= STAIRS_SPAWN_DISTANCE_TILES as f32 * TILE_SIZE);
+    }
+}
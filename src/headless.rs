@@ -0,0 +1,453 @@
+use std::time::{Duration, Instant};
+
+use bevy::asset::AssetPlugin;
+use bevy::ecs::query::QueryFilter;
+use bevy::ecs::system::RunSystemOnce;
+use bevy::input::ButtonState;
+use bevy::input::InputPlugin;
+use bevy::input::gamepad::{
+    GamepadConnection, GamepadConnectionEvent, RawGamepadAxisChangedEvent,
+    RawGamepadButtonChangedEvent, RawGamepadEvent,
+};
+use bevy::input::keyboard::{Key, KeyboardInput, NativeKey};
+use bevy::input::mouse::MouseButtonInput;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use crate::archetype::Archetypes;
+use crate::arena::{ArenaGrid, ArenaMode, TileType};
+use crate::collision::tile_to_world;
+use crate::enemy::EnemySpawner;
+use crate::floor::FloorEntity;
+use crate::player::Player;
+use crate::replay::{Playback, Recorder, Replay, ReplayResult, state_hash};
+use crate::seed::{RunSeed, SeedSource};
+use crate::simulation::{Interpolated, SIMULATION_HZ};
+use crate::weapon::StartingWeapon;
+use crate::{GameState, GameplayPlugin};
+
+const DEFAULT_FRAMES_PER_SECOND: f64 = SIMULATION_HZ;
+/// How long [`HeadlessBuilder::build`] waits for the archetype files before giving up.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sets up a [`HeadlessApp`]. Everything not chosen here comes from the defaults rather than the
+/// command line, so a test sees the same run wherever it's started from.
+#[derive(Debug, Clone)]
+pub struct HeadlessBuilder {
+    seed: u64,
+    arena_mode: ArenaMode,
+    starting_weapon: Option<String>,
+    timestep: Duration,
+    record: bool,
+    replay: Option<Replay>,
+}
+
+impl Default for HeadlessBuilder {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            arena_mode: ArenaMode::default(),
+            starting_weapon: None,
+            timestep: Duration::from_secs_f64(1.0 / DEFAULT_FRAMES_PER_SECOND),
+            record: false,
+            replay: None,
+        }
+    }
+}
+
+impl HeadlessBuilder {
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn arena_mode(mut self, arena_mode: ArenaMode) -> Self {
+        self.arena_mode = arena_mode;
+        self
+    }
+
+    pub fn starting_weapon(mut self, id: &str) -> Self {
+        self.starting_weapon = Some(id.to_string());
+        self
+    }
+
+    /// How much game time every [`HeadlessApp::step`] advances, the headless stand-in for the
+    /// frame rate. Gameplay still steps at [`SIMULATION_HZ`] whatever this is.
+    pub fn timestep(mut self, timestep: Duration) -> Self {
+        self.timestep = timestep;
+        self
+    }
+
+    /// Records the run, see [`HeadlessApp::recording`].
+    pub fn record(mut self) -> Self {
+        self.record = true;
+        self
+    }
+
+    /// Plays `replay` back. Its seed, arena mode and starting weapon replace the ones set here.
+    pub fn replay(mut self, replay: Replay) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    /// Loads the archetypes and starts a run on the first floor.
+    ///
+    /// # Panics
+    ///
+    /// If the archetype files fail to load or validate, or a replay was recorded with other
+    /// archetypes.
+    pub fn build(self) -> HeadlessApp {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            StatesPlugin,
+            InputPlugin,
+        ))
+        .add_plugins(GameplayPlugin)
+        .insert_resource(RunSeed {
+            value: self.seed,
+            source: SeedSource::CommandLine,
+        })
+        .insert_resource(self.arena_mode)
+        .insert_resource(StartingWeapon(self.starting_weapon))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(self.timestep));
+        if self.record {
+            app.init_resource::<Recorder>();
+        }
+
+        let mut headless = HeadlessApp {
+            app,
+            timestep: self.timestep,
+        };
+        headless.wait_for_archetypes();
+        if let Some(replay) = self.replay {
+            Playback::begin(headless.world_mut(), replay)
+                .unwrap_or_else(|error| panic!("cannot play the replay back: {error}"));
+        }
+        headless.start_run();
+        headless
+    }
+}
+
+/// The game without a window, renderer or menus, stepped by hand with a fixed timestep and
+/// driven by scripted input. Meant for gameplay tests.
+pub struct HeadlessApp {
+    app: App,
+    timestep: Duration,
+}
+
+impl HeadlessApp {
+    pub fn builder() -> HeadlessBuilder {
+        HeadlessBuilder::default()
+    }
+
+    pub fn app(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    pub fn world(&self) -> &World {
+        self.app.world()
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+
+    /// Runs one frame.
+    pub fn step(&mut self) {
+        self.app.update();
+    }
+
+    pub fn run_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.step();
+        }
+    }
+
+    /// Runs as many frames as it takes to cover `seconds` of game time.
+    pub fn run_for(&mut self, seconds: f32) {
+        let frames = (seconds / self.timestep.as_secs_f32()).ceil() as usize;
+        self.run_frames(frames);
+    }
+
+    /// Fixed steps the simulation has taken since the app started.
+    pub fn fixed_steps(&self) -> u64 {
+        let time = self.world().resource::<Time<Fixed>>();
+        (time.elapsed().as_nanos() / time.timestep().as_nanos()) as u64
+    }
+
+    /// Runs frames until the simulation has taken `steps` more fixed steps.
+    ///
+    /// # Panics
+    ///
+    /// If a frame that runs several steps carries it past the target.
+    pub fn run_steps(&mut self, steps: u64) {
+        let target = self.fixed_steps() + steps;
+        while self.fixed_steps() < target {
+            self.step();
+        }
+        assert_eq!(self.fixed_steps(), target, "overshot the requested steps");
+    }
+
+    pub fn state(&self) -> GameState {
+        *self.world().resource::<State<GameState>>().get()
+    }
+
+    /// Requests a state change and runs the frame that applies it.
+    pub fn set_state(&mut self, state: GameState) {
+        self.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(state);
+        self.step();
+    }
+
+    pub fn press_key(&mut self, key_code: KeyCode) {
+        self.send_key(key_code, ButtonState::Pressed);
+    }
+
+    pub fn release_key(&mut self, key_code: KeyCode) {
+        self.send_key(key_code, ButtonState::Released);
+    }
+
+    /// Presses `key_code` for a single frame.
+    pub fn tap_key(&mut self, key_code: KeyCode) {
+        self.press_key(key_code);
+        self.step();
+        self.release_key(key_code);
+    }
+
+    pub fn press_mouse(&mut self, button: MouseButton) {
+        self.send_mouse(button, ButtonState::Pressed);
+    }
+
+    pub fn release_mouse(&mut self, button: MouseButton) {
+        self.send_mouse(button, ButtonState::Released);
+    }
+
+    /// Plugs in a gamepad and runs the frame that connects it.
+    pub fn connect_gamepad(&mut self) -> Entity {
+        let gamepad = self.world_mut().spawn_empty().id();
+        self.world_mut().send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected {
+                name: "Headless gamepad".to_string(),
+                vendor_id: None,
+                product_id: None,
+            },
+        ));
+        self.step();
+        gamepad
+    }
+
+    pub fn press_gamepad(&mut self, gamepad: Entity, button: GamepadButton) {
+        self.send_gamepad(RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(
+            gamepad, button, 1.0,
+        )));
+    }
+
+    pub fn release_gamepad(&mut self, gamepad: Entity, button: GamepadButton) {
+        self.send_gamepad(RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(
+            gamepad, button, 0.0,
+        )));
+    }
+
+    /// Holds the left stick at `position`, each axis from -1 to 1.
+    pub fn set_left_stick(&mut self, gamepad: Entity, position: Vec2) {
+        self.send_stick(
+            gamepad,
+            GamepadAxis::LeftStickX,
+            GamepadAxis::LeftStickY,
+            position,
+        );
+    }
+
+    /// Holds the right stick at `position`, each axis from -1 to 1.
+    pub fn set_right_stick(&mut self, gamepad: Entity, position: Vec2) {
+        self.send_stick(
+            gamepad,
+            GamepadAxis::RightStickX,
+            GamepadAxis::RightStickY,
+            position,
+        );
+    }
+
+    /// Turns the player towards `target`, standing in for the mouse cursor.
+    pub fn aim_at(&mut self, target: Vec2) {
+        let player = self.player();
+        let mut transform = self.world_mut().get_mut::<Transform>(player).unwrap();
+        let direction = target - transform.translation.truncate();
+        transform.rotation = Quat::from_rotation_z(direction.y.atan2(direction.x));
+    }
+
+    /// # Panics
+    ///
+    /// If there's no player, for example after the run has ended.
+    pub fn player(&mut self) -> Entity {
+        self.world_mut()
+            .query_filtered::<Entity, With<Player>>()
+            .single(self.app.world())
+            .expect("the run should have a player")
+    }
+
+    pub fn player_position(&mut self) -> Vec2 {
+        let player = self.player();
+        self.position(player)
+    }
+
+    /// Where the simulation has `entity`, rather than where it was last drawn.
+    pub fn position(&self, entity: Entity) -> Vec2 {
+        let entity = self.world().entity(entity);
+        let transform = entity
+            .get::<Transform>()
+            .expect("entity should have a transform");
+        let translation = match entity.get::<Interpolated>() {
+            Some(interpolated) => interpolated.simulated_translation(transform),
+            None => transform.translation,
+        };
+        translation.truncate()
+    }
+
+    /// How many entities match `F`.
+    pub fn count<F: QueryFilter>(&mut self) -> usize {
+        self.world_mut()
+            .query_filtered::<(), F>()
+            .iter(self.app.world())
+            .count()
+    }
+
+    /// See [`replay::state_hash`](crate::replay::state_hash).
+    pub fn state_hash(&mut self) -> u64 {
+        state_hash(self.world_mut())
+    }
+
+    /// The run so far, if the app was built to record it.
+    pub fn recording(&self) -> Option<&Replay> {
+        self.world().get_resource::<Recorder>()?.replay()
+    }
+
+    /// How playback went, once the replay has run out or the run has ended.
+    pub fn replay_result(&self) -> Option<ReplayResult> {
+        self.world().get_resource::<ReplayResult>().copied()
+    }
+
+    /// Swaps the generated floor for an empty room of `width` by `height` tiles walled in on
+    /// every side, and puts the player in the middle. Returns the player's new position.
+    pub fn use_open_arena(&mut self, width: usize, height: usize) -> Vec2 {
+        let floor_entities: Vec<Entity> = self
+            .world_mut()
+            .query_filtered::<Entity, With<FloorEntity>>()
+            .iter(self.app.world())
+            .collect();
+        for entity in floor_entities {
+            self.world_mut().despawn(entity);
+        }
+
+        let grid: Vec<Vec<TileType>> = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                            TileType::Wall
+                        } else {
+                            TileType::Floor
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let arena_grid = ArenaGrid::from_tiles(grid);
+        let center = tile_to_world(&arena_grid, width / 2, height / 2);
+        self.world_mut().insert_resource(arena_grid);
+
+        let player = self.player();
+        self.world_mut()
+            .get_mut::<Transform>(player)
+            .unwrap()
+            .translation = center.extend(0.0);
+        center
+    }
+
+    /// Spawns an enemy from its archetype id, the same way floor setup does.
+    pub fn spawn_enemy(&mut self, id: &str, position: Vec2) -> Entity {
+        let id = id.to_string();
+        self.world_mut()
+            .run_system_once(
+                move |mut commands: Commands,
+                      archetypes: Res<Archetypes>,
+                      asset_server: Res<AssetServer>| {
+                    EnemySpawner {
+                        commands: &mut commands,
+                        archetypes: &archetypes,
+                        asset_server: &asset_server,
+                    }
+                    .spawn(&id, position, &mut StdRng::seed_from_u64(0))
+                },
+            )
+            .expect("the run should have archetypes loaded")
+    }
+
+    fn send_key(&mut self, key_code: KeyCode, state: ButtonState) {
+        self.world_mut().send_event(KeyboardInput {
+            key_code,
+            logical_key: Key::Unidentified(NativeKey::Unidentified),
+            state,
+            text: None,
+            repeat: false,
+            window: Entity::PLACEHOLDER,
+        });
+    }
+
+    fn send_gamepad(&mut self, event: RawGamepadEvent) {
+        self.world_mut().send_event(event);
+    }
+
+    fn send_stick(&mut self, gamepad: Entity, x: GamepadAxis, y: GamepadAxis, position: Vec2) {
+        for (axis, value) in [(x, position.x), (y, position.y)] {
+            self.send_gamepad(RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                gamepad, axis, value,
+            )));
+        }
+    }
+
+    fn send_mouse(&mut self, button: MouseButton, state: ButtonState) {
+        self.world_mut().send_event(MouseButtonInput {
+            button,
+            state,
+            window: Entity::PLACEHOLDER,
+        });
+    }
+
+    /// Enters the run on a frame that advances no time, with nothing left over from loading,
+    /// so the run's first fixed step comes at the same point whatever the frame rate.
+    fn start_run(&mut self) {
+        let world = self.world_mut();
+        let overstep = world.resource::<Time<Fixed>>().overstep();
+        world
+            .resource_mut::<Time<Fixed>>()
+            .discard_overstep(overstep);
+        world.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        self.set_state(GameState::InGame);
+        let timestep = self.timestep;
+        self.world_mut()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+    }
+
+    fn wait_for_archetypes(&mut self) {
+        let started = Instant::now();
+        while self.state() == GameState::Loading {
+            self.step();
+            if let Some(exit) = self.app.should_exit() {
+                panic!("archetypes failed to load: {exit:?}");
+            }
+            assert!(
+                started.elapsed() < LOAD_TIMEOUT,
+                "archetypes took too long to load"
+            );
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
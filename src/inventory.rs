@@ -0,0 +1,390 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand::seq::IndexedRandom;
+
+use crate::GameState;
+use crate::action::Action;
+use crate::archetype::Archetypes;
+use crate::arena::{ArenaGrid, TileType, setup_arena};
+use crate::collision::tile_to_world;
+use crate::dungeon::RoomKind;
+use crate::floor::{CurrentFloor, FloorEntity, SetupFloor};
+use crate::player::{Player, player_shooting_system};
+use crate::seed::{RngStream, RunSeed};
+use crate::simulation::FixedInput;
+use crate::weapon::Weapon;
+
+/// One slot per [`Action::Slot`].
+const MAX_WEAPONS: usize = 9;
+const WEAPON_PICKUPS_PER_FLOOR: usize = 2;
+const MIN_PICKUP_DISTANCE_FROM_SPAWN_TILES: f32 = 8.0;
+const PICKUP_RADIUS: f32 = 14.0;
+const PICKUP_SPRITE_SIZE: f32 = 8.0;
+const PICKUP_COLOR: Color = Color::srgb(0.95, 0.8, 0.25);
+
+/// The player's guns. The first slot holds the infinite-ammo starter, which can't be dropped.
+#[derive(Component, Debug)]
+pub struct Inventory {
+    weapons: Vec<Weapon>,
+    active: usize,
+}
+
+impl Inventory {
+    pub fn new(starter: Weapon) -> Self {
+        Self {
+            weapons: vec![starter],
+            active: 0,
+        }
+    }
+
+    pub fn weapons(&self) -> &[Weapon] {
+        &self.weapons
+    }
+
+    pub fn weapons_mut(&mut self) -> impl Iterator<Item = &mut Weapon> {
+        self.weapons.iter_mut()
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn active(&self) -> &Weapon {
+        &self.weapons[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Weapon {
+        &mut self.weapons[self.active]
+    }
+
+    /// Switches to slot `index`. Returns whether the active weapon changed.
+    pub fn select(&mut self, index: usize) -> bool {
+        if index >= self.weapons.len() || index == self.active {
+            return false;
+        }
+        self.weapons[self.active].holster();
+        self.active = index;
+        true
+    }
+
+    /// Moves `step` slots along, wrapping around at either end.
+    pub fn cycle(&mut self, step: i32) -> bool {
+        let count = self.weapons.len() as i32;
+        self.select((self.active as i32 + step).rem_euclid(count) as usize)
+    }
+
+    /// Takes a weapon off the floor. A gun already carried only donates its ammo. With every
+    /// slot full the active weapon is swapped out and returned to be dropped; if that is the
+    /// starter the pickup is handed back untouched.
+    pub fn pick_up(&mut self, weapon: Weapon) -> Result<Option<Weapon>, Weapon> {
+        if let Some(owned) = self
+            .weapons
+            .iter_mut()
+            .find(|owned| owned.stats.name == weapon.stats.name)
+        {
+            owned.add_ammo(weapon.ammo_in_magazine + weapon.reserve_ammo.unwrap_or(0));
+            return Ok(None);
+        }
+
+        if self.weapons.len() < MAX_WEAPONS {
+            self.weapons.push(weapon);
+            self.select(self.weapons.len() - 1);
+            return Ok(None);
+        }
+
+        if self.active().has_infinite_ammo() {
+            return Err(weapon);
+        }
+        let mut dropped = std::mem::replace(&mut self.weapons[self.active], weapon);
+        dropped.holster();
+        Ok(Some(dropped))
+    }
+
+    /// Removes the active weapon, unless it is the starter.
+    pub fn drop_active(&mut self) -> Option<Weapon> {
+        if self.active().has_infinite_ammo() {
+            return None;
+        }
+
+        let mut dropped = self.weapons.remove(self.active);
+        dropped.holster();
+        self.active = self.active.saturating_sub(1);
+        Some(dropped)
+    }
+}
+
+/// A gun lying in the arena, waiting to be picked up with the interact key.
+#[derive(Component)]
+#[require(FloorEntity)]
+pub struct WeaponPickup {
+    pub weapon: Weapon,
+}
+
+fn weapon_pickup_bundle(weapon: Weapon, position: Vec2) -> impl Bundle {
+    (
+        WeaponPickup { weapon },
+        Sprite {
+            color: PICKUP_COLOR,
+            custom_size: Some(Vec2::splat(PICKUP_SPRITE_SIZE)),
+            ..default()
+        },
+        Transform::from_translation(position.extend(-0.5)),
+        Visibility::Visible,
+    )
+}
+
+fn random_loot(archetypes: &Archetypes, rng: &mut impl Rng) -> Weapon {
+    let loot = &archetypes.weapons.loot;
+    let id = &loot[rng.random_range(0..loot.len())];
+    Weapon::new(archetypes.weapon(id).clone())
+}
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(SetupFloor, spawn_weapon_pickups.after(setup_arena))
+            .add_systems(
+                FixedUpdate,
+                (
+                    weapon_switch_system,
+                    weapon_pickup_system,
+                    weapon_drop_system,
+                )
+                    .chain()
+                    .before(player_shooting_system)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Puts a gun in every treasure and shop room, or a few on open floor away from the spawn.
+fn spawn_weapon_pickups(
+    mut commands: Commands,
+    arena_grid: Res<ArenaGrid>,
+    run_seed: Res<RunSeed>,
+    floor: Res<CurrentFloor>,
+    archetypes: Res<Archetypes>,
+) {
+    if archetypes.weapons.loot.is_empty() {
+        return;
+    }
+    let mut rng = run_seed.rng(RngStream::Loot, floor.0);
+
+    let loot_rooms: Vec<_> = arena_grid
+        .rooms
+        .iter()
+        .filter(|room| matches!(room.kind, RoomKind::Treasure | RoomKind::Shop))
+        .collect();
+    if !loot_rooms.is_empty() {
+        for room in loot_rooms {
+            let (x, y) = room.bounds.center();
+            let position = tile_to_world(&arena_grid, x, y);
+            commands.spawn(weapon_pickup_bundle(
+                random_loot(&archetypes, &mut rng),
+                position,
+            ));
+        }
+        return;
+    }
+
+    let (spawn_x, spawn_y) = arena_grid.spawn_tile();
+    let mut floor_tiles = Vec::new();
+    for (y, row) in arena_grid.grid.iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            let distance = Vec2::new(x as f32 - spawn_x as f32, y as f32 - spawn_y as f32);
+            if *tile == TileType::Floor
+                && arena_grid.is_reachable(x, y)
+                && distance.length() >= MIN_PICKUP_DISTANCE_FROM_SPAWN_TILES
+            {
+                floor_tiles.push((x, y));
+            }
+        }
+    }
+    let pickup_tiles: Vec<(usize, usize)> = floor_tiles
+        .choose_multiple(&mut rng, WEAPON_PICKUPS_PER_FLOOR)
+        .copied()
+        .collect();
+    for (x, y) in pickup_tiles {
+        let position = tile_to_world(&arena_grid, x, y);
+        commands.spawn(weapon_pickup_bundle(
+            random_loot(&archetypes, &mut rng),
+            position,
+        ));
+    }
+}
+
+fn weapon_switch_system(
+    mut player_query: Query<&mut Inventory, With<Player>>,
+    actions: Res<FixedInput<Action>>,
+) {
+    let Ok(mut inventory) = player_query.single_mut() else {
+        return;
+    };
+
+    let switched = if let Some(slot) =
+        (0..MAX_WEAPONS).find(|slot| actions.just_pressed(Action::Slot(*slot as u8 + 1)))
+    {
+        inventory.select(slot)
+    } else if actions.just_pressed(Action::PreviousWeapon) {
+        inventory.cycle(-1)
+    } else if actions.just_pressed(Action::NextWeapon) {
+        inventory.cycle(1)
+    } else {
+        false
+    };
+
+    if switched {
+        let weapon = inventory.active();
+        info!(
+            "Switched to slot {}/{}: {} ({} / {}).",
+            inventory.active_index() + 1,
+            inventory.weapons().len(),
+            weapon.stats.name,
+            weapon.ammo_in_magazine,
+            weapon
+                .reserve_ammo
+                .map_or_else(|| "inf".to_string(), |reserve| reserve.to_string())
+        );
+    }
+}
+
+fn weapon_pickup_system(
+    mut commands: Commands,
+    mut player_query: Query<(&Transform, &mut Inventory), With<Player>>,
+    mut pickup_query: Query<(Entity, &Transform, &mut WeaponPickup), Without<Player>>,
+    actions: Res<FixedInput<Action>>,
+) {
+    if !actions.just_pressed(Action::Interact) {
+        return;
+    }
+    let Ok((player_transform, mut inventory)) = player_query.single_mut() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+
+    let nearest = pickup_query
+        .iter_mut()
+        .map(|(entity, transform, pickup)| {
+            let distance = transform.translation.truncate().distance(player_pos);
+            (entity, distance, pickup)
+        })
+        .filter(|(_, distance, _)| *distance <= PICKUP_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    let Some((entity, _, mut pickup)) = nearest else {
+        return;
+    };
+
+    let weapon = pickup.weapon.clone();
+    let name = weapon.stats.name.clone();
+    match inventory.pick_up(weapon) {
+        Ok(swapped) => {
+            commands.entity(entity).despawn();
+            if let Some(dropped) = swapped {
+                commands.spawn(weapon_pickup_bundle(dropped, player_pos));
+            }
+            info!("Picked up {name}.");
+        }
+        Err(weapon) => pickup.weapon = weapon,
+    }
+}
+
+fn weapon_drop_system(
+    mut commands: Commands,
+    mut player_query: Query<(&Transform, &mut Inventory), With<Player>>,
+    actions: Res<FixedInput<Action>>,
+) {
+    if !actions.just_pressed(Action::DropWeapon) {
+        return;
+    }
+    let Ok((player_transform, mut inventory)) = player_query.single_mut() else {
+        return;
+    };
+
+    if let Some(dropped) = inventory.drop_active() {
+        info!("Dropped {}.", dropped.stats.name);
+        commands.spawn(weapon_pickup_bundle(
+            dropped,
+            player_transform.translation.truncate(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weapon::WeaponStats;
+
+    fn stats(id: &str) -> WeaponStats {
+        Archetypes::shipped().weapon(id).clone()
+    }
+
+    fn inventory_with(extra: usize) -> Inventory {
+        let mut inventory = Inventory::new(Weapon::starter(stats("pistol")));
+        for index in 0..extra {
+            let stats = WeaponStats {
+                name: format!("Gun {index}"),
+                ..stats("rifle")
+            };
+            assert!(matches!(inventory.pick_up(Weapon::new(stats)), Ok(None)));
+        }
+        inventory
+    }
+
+    #[test]
+    fn picking_up_a_new_gun_equips_it() {
+        let inventory = inventory_with(2);
+        assert_eq!(inventory.weapons().len(), 3);
+        assert_eq!(inventory.active_index(), 2);
+    }
+
+    #[test]
+    fn duplicate_pickups_only_add_ammo() {
+        let mut inventory = Inventory::new(Weapon::starter(stats("pistol")));
+        inventory.pick_up(Weapon::new(stats("shotgun"))).unwrap();
+        let reserve = inventory.active().reserve_ammo.unwrap();
+
+        inventory.pick_up(Weapon::new(stats("shotgun"))).unwrap();
+        let shotgun = stats("shotgun");
+        assert_eq!(inventory.weapons().len(), 2);
+        assert_eq!(
+            inventory.active().reserve_ammo,
+            Some(reserve + shotgun.magazine_size + shotgun.reserve_ammo)
+        );
+    }
+
+    #[test]
+    fn switching_wraps_and_ignores_empty_slots() {
+        let mut inventory = inventory_with(2);
+        assert!(inventory.cycle(1));
+        assert_eq!(inventory.active_index(), 0);
+        assert!(inventory.cycle(-1));
+        assert_eq!(inventory.active_index(), 2);
+        assert!(!inventory.select(5));
+        assert!(inventory.select(1));
+    }
+
+    #[test]
+    fn full_inventory_swaps_out_the_active_gun() {
+        let mut inventory = inventory_with(MAX_WEAPONS - 1);
+        let swapped = inventory
+            .pick_up(Weapon::new(stats("railgun")))
+            .unwrap()
+            .expect("a gun should be swapped out");
+        assert_eq!(swapped.stats.name, format!("Gun {}", MAX_WEAPONS - 2));
+        assert_eq!(inventory.active().stats.name, "Railgun");
+
+        inventory.select(0);
+        let refused = inventory.pick_up(Weapon::new(stats("shotgun")));
+        assert!(refused.is_err(), "the starter is never swapped out");
+    }
+
+    #[test]
+    fn the_starter_cannot_be_dropped() {
+        let mut inventory = inventory_with(1);
+        assert!(inventory.drop_active().is_some());
+        assert_eq!(inventory.active_index(), 0);
+        assert!(inventory.drop_active().is_none());
+        assert_eq!(inventory.weapons().len(), 1);
+    }
+}
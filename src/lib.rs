@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+
+pub mod action;
+use action::ActionPlugin;
+
+pub mod archetype;
+use archetype::ArchetypePlugin;
+
+pub mod player;
+use player::PlayerPlugin;
+
+pub mod dodge;
+use dodge::DodgePlugin;
+
+pub mod blank;
+use blank::BlankPlugin;
+
+pub mod weapon;
+use weapon::WeaponPlugin;
+
+pub mod inventory;
+use inventory::InventoryPlugin;
+
+pub mod arena;
+pub mod arena_cave;
+pub mod arena_rooms;
+use arena::ArenaPlugin;
+
+pub mod tilemap;
+
+pub mod enemy;
+use enemy::EnemyPlugin;
+
+pub mod enemy_ai;
+use enemy_ai::EnemyAiPlugin;
+
+pub mod enemy_weapon;
+use enemy_weapon::EnemyWeaponPlugin;
+
+pub mod bullet_pattern;
+use bullet_pattern::BulletPatternPlugin;
+
+pub mod dungeon;
+use dungeon::DungeonPlugin;
+
+pub mod pathfinding;
+use pathfinding::PathfindingPlugin;
+
+pub mod combat;
+use combat::CombatPlugin;
+
+pub mod tuning;
+
+pub mod seed;
+use seed::SeedPlugin;
+
+pub mod floor;
+use floor::FloorPlugin;
+
+pub mod simulation;
+use simulation::SimulationPlugin;
+
+pub mod replay;
+use replay::ReplayPlugin;
+
+#[cfg(feature = "headless")]
+pub mod headless;
+pub mod menu;
+
+mod cli;
+pub mod collision;
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[states(scoped_entities)]
+pub enum GameState {
+    /// Waiting for the archetype files to load.
+    #[default]
+    Loading,
+    MainMenu,
+    InGame,
+    Paused,
+    GameOver,
+}
+
+/// Active for the whole of a run, paused or not. Everything spawned for the run is scoped to it,
+/// so pausing leaves the floor alone and leaving the run clears it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct InRun;
+
+impl ComputedStates for InRun {
+    type SourceStates = GameState;
+
+    fn compute(state: GameState) -> Option<Self> {
+        matches!(state, GameState::InGame | GameState::Paused).then_some(InRun)
+    }
+}
+
+/// The game itself: states, world generation, the player, enemies and combat. Windowing,
+/// rendering, menus, tuning-file watching and the input config file are left to the binary so
+/// the game can also run headless.
+pub struct GameplayPlugin;
+
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .add_computed_state::<InRun>()
+            .add_plugins(ActionPlugin)
+            .add_plugins(SimulationPlugin)
+            .add_plugins(SeedPlugin)
+            .add_plugins(ArchetypePlugin)
+            .add_plugins(PlayerPlugin)
+            .add_plugins(DodgePlugin)
+            .add_plugins(BlankPlugin)
+            .add_plugins(WeaponPlugin)
+            .add_plugins(InventoryPlugin)
+            .add_plugins(EnemyPlugin)
+            .add_plugins(ArenaPlugin)
+            .add_plugins(DungeonPlugin)
+            .add_plugins(PathfindingPlugin)
+            .add_plugins(EnemyAiPlugin)
+            .add_plugins(EnemyWeaponPlugin)
+            .add_plugins(BulletPatternPlugin)
+            .add_plugins(CombatPlugin)
+            .add_plugins(FloorPlugin)
+            .add_plugins(ReplayPlugin);
+    }
+}
@@ -1,22 +1,10 @@
 use bevy::prelude::*;
 
-mod player;
-use player::PlayerPlugin;
-
-mod arena;
-use arena::ArenaPlugin;
-
-mod enemy;
-use enemy::EnemyPlugin;
-
-#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-pub enum GameState {
-    MainMenu,
-    #[default]
-    InGame,
-    Paused,
-    GameOver,
-}
+use rusty_gungeon::GameplayPlugin;
+use rusty_gungeon::action::InputConfigPlugin;
+use rusty_gungeon::menu::MenuPlugin;
+use rusty_gungeon::tilemap::TilemapPlugin;
+use rusty_gungeon::tuning::TuningPlugin;
 
 fn main() {
     App::new()
@@ -29,25 +17,16 @@ fn main() {
             }),
             ..default()
         }))
-        .add_plugins(PlayerPlugin)
-        .add_plugins(EnemyPlugin)
-        .add_plugins(ArenaPlugin)
+        .add_plugins(GameplayPlugin)
+        .add_plugins(InputConfigPlugin)
+        .add_plugins(TilemapPlugin)
+        .add_plugins(TuningPlugin)
+        .add_plugins(MenuPlugin)
         .insert_resource(ClearColor(Color::srgb(0.04, 0.04, 0.06)))
-        .init_state::<GameState>()
         .add_systems(Startup, setup_camera)
-        .add_systems(OnEnter(GameState::MainMenu), setup_main_menu_stub)
-        .add_systems(OnEnter(GameState::InGame), setup_ingame_stub)
         .run();
 }
 
 fn setup_camera(mut commands: Commands) {
     commands.spawn(Camera2d);
 }
-
-fn setup_main_menu_stub() {
-    info!("entered mainmenu state (stub)");
-}
-
-fn setup_ingame_stub() {
-    info!("entered ingame state (stub)");
-}
@@ -0,0 +1,719 @@
+use bevy::input::ButtonState;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::mouse::AccumulatedMouseScroll;
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowMode};
+
+use crate::action::{Action, Binding, InputBindings};
+use crate::archetype::Archetypes;
+use crate::arena::ArenaMode;
+use crate::combat::EnemyDeathEvent;
+use crate::floor::CurrentFloor;
+use crate::seed::{RunSeed, SeedSource};
+use crate::weapon::StartingWeapon;
+use crate::{GameState, InRun};
+
+const CANCEL_REBIND_KEY: KeyCode = KeyCode::Escape;
+/// Long enough for any `u64` the field could hold without overflowing.
+const MAX_SEED_DIGITS: usize = 19;
+
+const MENU_BACKGROUND: Color = Color::srgb(0.04, 0.04, 0.06);
+const OVERLAY_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+const TEXT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+const HINT_COLOR: Color = Color::srgb(0.55, 0.55, 0.6);
+const BUTTON_COLOR: Color = Color::srgb(0.15, 0.15, 0.2);
+const BUTTON_HOVERED_COLOR: Color = Color::srgb(0.25, 0.25, 0.32);
+const BUTTON_PRESSED_COLOR: Color = Color::srgb(0.35, 0.5, 0.75);
+
+const TITLE_FONT_SIZE: f32 = 48.0;
+const BUTTON_FONT_SIZE: f32 = 22.0;
+const TEXT_FONT_SIZE: f32 = 20.0;
+const HINT_FONT_SIZE: f32 = 16.0;
+const BUTTON_WIDTH: f32 = 320.0;
+const BUTTON_HEIGHT: f32 = 44.0;
+const ROW_GAP: f32 = 12.0;
+const CONTROLS_COLUMNS: u16 = 3;
+
+/// Which page of the main menu is showing.
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(GameState = GameState::MainMenu)]
+#[states(scoped_entities)]
+pub enum MenuScreen {
+    #[default]
+    Main,
+    Settings,
+    Controls,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Setting {
+    ArenaMode,
+    StartingWeapon,
+    Fullscreen,
+    AimAssist,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuAction {
+    StartRun,
+    OpenSettings,
+    Cycle(Setting),
+    CloseSettings,
+    OpenControls,
+    CloseControls,
+    /// Waits for the next key or button and binds it to the action.
+    Rebind(Action),
+    ResetControls,
+    Resume,
+    Retry,
+    MainMenu,
+    Quit,
+}
+
+/// The text showing a setting's current value.
+#[derive(Component)]
+struct SettingText(Setting);
+
+#[derive(Component)]
+struct SeedText;
+
+/// The text listing an action's bindings.
+#[derive(Component)]
+struct BindingText(Action);
+
+/// The action waiting for a new binding on the controls screen, if any.
+#[derive(Resource, Debug, Default)]
+struct Rebinding(Option<Action>);
+
+/// Digits typed into the main menu's seed field. Empty means a random seed.
+#[derive(Resource, Debug, Default)]
+struct SeedEntry(String);
+
+impl SeedEntry {
+    /// Applies one key press. Only digits are accepted; Backspace removes the last one.
+    fn edit(&mut self, key: &Key) {
+        match key {
+            Key::Backspace => {
+                self.0.pop();
+            }
+            Key::Character(text) => {
+                for digit in text.chars().filter(char::is_ascii_digit) {
+                    if self.0.len() < MAX_SEED_DIGITS {
+                        self.0.push(digit);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// What the game over screen reports about the run that just ended.
+#[derive(Resource, Debug, Default)]
+pub struct RunStats {
+    pub seed: u64,
+    pub floor: u32,
+    pub seconds: f32,
+    pub kills: u32,
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<MenuScreen>()
+            .init_resource::<SeedEntry>()
+            .init_resource::<RunStats>()
+            .init_resource::<Rebinding>()
+            .add_systems(OnEnter(MenuScreen::Main), spawn_main_menu)
+            .add_systems(OnEnter(MenuScreen::Settings), spawn_settings_menu)
+            .add_systems(OnEnter(MenuScreen::Controls), spawn_controls_menu)
+            .add_systems(OnExit(MenuScreen::Controls), cancel_rebinding)
+            .add_systems(OnEnter(GameState::Paused), spawn_pause_menu)
+            .add_systems(OnEnter(GameState::GameOver), spawn_game_over_screen)
+            .add_systems(OnEnter(InRun), reset_run_stats)
+            .add_systems(Update, (button_color_system, menu_action_system))
+            .add_systems(
+                Update,
+                (seed_entry_system, start_run_on_enter).run_if(in_state(MenuScreen::Main)),
+            )
+            .add_systems(
+                Update,
+                setting_text_system
+                    .run_if(in_state(MenuScreen::Settings).or(in_state(MenuScreen::Controls))),
+            )
+            .add_systems(
+                Update,
+                (
+                    rebind_system
+                        .after(menu_action_system)
+                        .run_if(|rebinding: Res<Rebinding>| rebinding.0.is_some()),
+                    binding_text_system,
+                )
+                    .chain()
+                    .run_if(in_state(MenuScreen::Controls)),
+            )
+            .add_systems(Update, toggle_pause_system.run_if(in_state(InRun)))
+            .add_systems(Update, track_run_stats.run_if(in_state(GameState::InGame)))
+            .add_systems(
+                Update,
+                retry_on_key_system.run_if(in_state(GameState::GameOver)),
+            );
+    }
+}
+
+fn screen(background: Color) -> impl Bundle {
+    (
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            row_gap: Val::Px(ROW_GAP),
+            ..default()
+        },
+        BackgroundColor(background),
+    )
+}
+
+fn text(value: impl Into<String>, font_size: f32, color: Color) -> impl Bundle {
+    (
+        Text::new(value),
+        TextFont {
+            font_size,
+            ..default()
+        },
+        TextColor(color),
+    )
+}
+
+fn button(action: MenuAction, label: impl Bundle) -> impl Bundle {
+    (
+        Button,
+        action,
+        Node {
+            width: Val::Px(BUTTON_WIDTH),
+            height: Val::Px(BUTTON_HEIGHT),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        BackgroundColor(BUTTON_COLOR),
+        children![label],
+    )
+}
+
+fn text_button(action: MenuAction, label: &str) -> impl Bundle {
+    button(action, text(label, BUTTON_FONT_SIZE, TEXT_COLOR))
+}
+
+fn seed_label(entry: &SeedEntry) -> String {
+    if entry.0.is_empty() {
+        "Seed: random".to_string()
+    } else {
+        format!("Seed: {}", entry.0)
+    }
+}
+
+fn spawn_main_menu(
+    mut commands: Commands,
+    mut seed_entry: ResMut<SeedEntry>,
+    run_seed: Res<RunSeed>,
+) {
+    seed_entry.0 = match run_seed.source {
+        SeedSource::Random => String::new(),
+        _ => run_seed.value.to_string(),
+    };
+
+    commands.spawn((
+        StateScoped(MenuScreen::Main),
+        screen(MENU_BACKGROUND),
+        children![
+            text("Rusty Gungeon", TITLE_FONT_SIZE, TEXT_COLOR),
+            (
+                SeedText,
+                text(seed_label(&seed_entry), TEXT_FONT_SIZE, TEXT_COLOR)
+            ),
+            text(
+                "Type digits to pick a seed, Backspace to clear it.",
+                HINT_FONT_SIZE,
+                HINT_COLOR,
+            ),
+            text_button(MenuAction::StartRun, "Start run"),
+            text_button(MenuAction::OpenSettings, "Settings"),
+            text_button(MenuAction::Quit, "Quit"),
+        ],
+    ));
+}
+
+fn spawn_settings_menu(mut commands: Commands) {
+    let setting_button = |setting| {
+        button(
+            MenuAction::Cycle(setting),
+            (SettingText(setting), text("", BUTTON_FONT_SIZE, TEXT_COLOR)),
+        )
+    };
+
+    commands.spawn((
+        StateScoped(MenuScreen::Settings),
+        screen(MENU_BACKGROUND),
+        children![
+            text("Settings", TITLE_FONT_SIZE, TEXT_COLOR),
+            setting_button(Setting::ArenaMode),
+            setting_button(Setting::StartingWeapon),
+            setting_button(Setting::Fullscreen),
+            text_button(MenuAction::OpenControls, "Controls"),
+            text_button(MenuAction::CloseSettings, "Back"),
+        ],
+    ));
+}
+
+fn binding_label(action: Action, bindings: &InputBindings, rebinding: &Rebinding) -> String {
+    if rebinding.0 == Some(action) {
+        return format!("{}: press a key or button", action.label());
+    }
+    let bound: Vec<String> = bindings
+        .bindings(action)
+        .iter()
+        .map(|binding| binding.label())
+        .collect();
+    format!("{}: {}", action.label(), bound.join(", "))
+}
+
+fn spawn_controls_menu(mut commands: Commands) {
+    let grid = commands
+        .spawn(Node {
+            display: Display::Grid,
+            grid_template_columns: RepeatedGridTrack::auto(CONTROLS_COLUMNS),
+            row_gap: Val::Px(ROW_GAP),
+            column_gap: Val::Px(ROW_GAP),
+            ..default()
+        })
+        .with_children(|grid| {
+            for action in Action::ALL {
+                grid.spawn(button(
+                    MenuAction::Rebind(action),
+                    (BindingText(action), text("", HINT_FONT_SIZE, TEXT_COLOR)),
+                ));
+            }
+        })
+        .id();
+    let footer = commands
+        .spawn((
+            Node {
+                column_gap: Val::Px(ROW_GAP),
+                ..default()
+            },
+            children![
+                button(
+                    MenuAction::Cycle(Setting::AimAssist),
+                    (
+                        SettingText(Setting::AimAssist),
+                        text("", BUTTON_FONT_SIZE, TEXT_COLOR)
+                    ),
+                ),
+                text_button(MenuAction::ResetControls, "Reset to defaults"),
+                text_button(MenuAction::CloseControls, "Back"),
+            ],
+        ))
+        .id();
+
+    commands
+        .spawn((
+            StateScoped(MenuScreen::Controls),
+            screen(MENU_BACKGROUND),
+            children![
+                text("Controls", TITLE_FONT_SIZE, TEXT_COLOR),
+                text(
+                    "Click an action, then press its new key or button. Escape cancels.",
+                    HINT_FONT_SIZE,
+                    HINT_COLOR,
+                ),
+            ],
+        ))
+        .add_children(&[grid, footer]);
+}
+
+fn spawn_pause_menu(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(GameState::Paused),
+        screen(OVERLAY_BACKGROUND),
+        children![
+            text("Paused", TITLE_FONT_SIZE, TEXT_COLOR),
+            text_button(MenuAction::Resume, "Resume"),
+            text_button(MenuAction::MainMenu, "Main menu"),
+            text_button(MenuAction::Quit, "Quit"),
+        ],
+    ));
+}
+
+fn spawn_game_over_screen(mut commands: Commands, stats: Res<RunStats>) {
+    let seconds = stats.seconds as u32;
+    let summary = format!(
+        "Reached floor {} in {}:{:02} and killed {} enemies.",
+        stats.floor,
+        seconds / 60,
+        seconds % 60,
+        stats.kills
+    );
+
+    commands.spawn((
+        StateScoped(GameState::GameOver),
+        screen(MENU_BACKGROUND),
+        children![
+            text("Game over", TITLE_FONT_SIZE, TEXT_COLOR),
+            text(summary, TEXT_FONT_SIZE, TEXT_COLOR),
+            text(format!("Seed {}", stats.seed), HINT_FONT_SIZE, HINT_COLOR),
+            text_button(MenuAction::Retry, "Retry"),
+            text_button(MenuAction::MainMenu, "Main menu"),
+            text_button(MenuAction::Quit, "Quit"),
+        ],
+    ));
+}
+
+#[allow(clippy::type_complexity)]
+fn button_color_system(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut background) in button_query.iter_mut() {
+        background.0 = match interaction {
+            Interaction::Pressed => BUTTON_PRESSED_COLOR,
+            Interaction::Hovered => BUTTON_HOVERED_COLOR,
+            Interaction::None => BUTTON_COLOR,
+        };
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn menu_action_system(
+    button_query: Query<(&Interaction, &MenuAction), Changed<Interaction>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_screen: ResMut<NextState<MenuScreen>>,
+    mut app_exit: EventWriter<AppExit>,
+    mut run_seed: ResMut<RunSeed>,
+    seed_entry: Res<SeedEntry>,
+    mut arena_mode: ResMut<ArenaMode>,
+    mut starting_weapon: ResMut<StartingWeapon>,
+    archetypes: Res<Archetypes>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut bindings: ResMut<InputBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for (interaction, action) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match action {
+            MenuAction::StartRun => {
+                run_seed.apply_entry(&seed_entry.0);
+                info!("Run seed: {} ({:?})", run_seed.value, run_seed.source);
+                next_state.set(GameState::InGame);
+            }
+            MenuAction::OpenSettings => next_screen.set(MenuScreen::Settings),
+            MenuAction::CloseSettings => next_screen.set(MenuScreen::Main),
+            MenuAction::OpenControls => next_screen.set(MenuScreen::Controls),
+            MenuAction::CloseControls => next_screen.set(MenuScreen::Settings),
+            MenuAction::Rebind(action) => rebinding.0 = Some(*action),
+            MenuAction::ResetControls => *bindings = InputBindings::default(),
+            MenuAction::Cycle(Setting::AimAssist) => bindings.aim_assist = !bindings.aim_assist,
+            MenuAction::Cycle(Setting::ArenaMode) => *arena_mode = arena_mode.next(),
+            MenuAction::Cycle(Setting::StartingWeapon) => {
+                starting_weapon.0 = next_weapon_id(&archetypes, starting_weapon.0.as_deref());
+            }
+            MenuAction::Cycle(Setting::Fullscreen) => {
+                if let Ok(mut window) = window_query.single_mut() {
+                    window.mode = match window.mode {
+                        WindowMode::Windowed => {
+                            WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+                        }
+                        _ => WindowMode::Windowed,
+                    };
+                }
+            }
+            MenuAction::Resume | MenuAction::Retry => next_state.set(GameState::InGame),
+            MenuAction::MainMenu => next_state.set(GameState::MainMenu),
+            MenuAction::Quit => {
+                app_exit.write(AppExit::Success);
+            }
+        }
+    }
+}
+
+/// Steps through the archetype's default gun followed by every weapon id in order.
+fn next_weapon_id(archetypes: &Archetypes, current: Option<&str>) -> Option<String> {
+    let mut ids = archetypes.weapons.weapons.keys();
+    match current {
+        None => ids.next().cloned(),
+        Some(current) => ids.skip_while(|id| *id != current).nth(1).cloned(),
+    }
+}
+
+fn setting_text_system(
+    mut text_query: Query<(&mut Text, &SettingText)>,
+    arena_mode: Res<ArenaMode>,
+    starting_weapon: Res<StartingWeapon>,
+    archetypes: Res<Archetypes>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    bindings: Res<InputBindings>,
+) {
+    for (mut text, setting) in text_query.iter_mut() {
+        let value = match setting.0 {
+            Setting::ArenaMode => format!("Arena: {}", arena_mode.name()),
+            Setting::StartingWeapon => match &starting_weapon.0 {
+                Some(id) => format!("Starting weapon: {id}"),
+                None => format!(
+                    "Starting weapon: default ({})",
+                    archetypes.player.starting_weapon
+                ),
+            },
+            Setting::Fullscreen => {
+                let fullscreen = window_query
+                    .single()
+                    .is_ok_and(|window| window.mode != WindowMode::Windowed);
+                format!("Fullscreen: {}", if fullscreen { "on" } else { "off" })
+            }
+            Setting::AimAssist => format!(
+                "Aim assist: {}",
+                if bindings.aim_assist { "on" } else { "off" }
+            ),
+        };
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
+
+/// Binds the first key, mouse button, wheel turn or gamepad button pressed to the action
+/// waiting for one. Ignores the frame the rebind was asked for, so the click on the action's
+/// button isn't taken as its new binding.
+fn rebind_system(
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<InputBindings>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mouse_scroll: Res<AccumulatedMouseScroll>,
+    gamepad_query: Query<&Gamepad>,
+) {
+    let Some(action) = rebinding.0 else {
+        return;
+    };
+    if rebinding.is_changed() {
+        return;
+    }
+    if keyboard_input.just_pressed(CANCEL_REBIND_KEY) {
+        rebinding.0 = None;
+        return;
+    }
+
+    let binding = keyboard_input
+        .get_just_pressed()
+        .next()
+        .map(|key| Binding::Key(*key))
+        .or_else(|| {
+            mouse_button_input
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Mouse(*button))
+        })
+        .or_else(|| {
+            gamepad_query
+                .iter()
+                .find_map(|gamepad| gamepad.get_just_pressed().next())
+                .map(|button| Binding::Gamepad(*button))
+        })
+        .or(match mouse_scroll.delta.y {
+            delta if delta > 0.0 => Some(Binding::WheelUp),
+            delta if delta < 0.0 => Some(Binding::WheelDown),
+            _ => None,
+        });
+    if let Some(binding) = binding {
+        bindings.rebind(action, binding);
+        rebinding.0 = None;
+    }
+}
+
+fn cancel_rebinding(mut rebinding: ResMut<Rebinding>) {
+    rebinding.0 = None;
+}
+
+fn binding_text_system(
+    mut text_query: Query<(&mut Text, &BindingText)>,
+    bindings: Res<InputBindings>,
+    rebinding: Res<Rebinding>,
+) {
+    for (mut text, binding_text) in text_query.iter_mut() {
+        let value = binding_label(binding_text.0, &bindings, &rebinding);
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
+
+fn seed_entry_system(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut seed_entry: ResMut<SeedEntry>,
+    mut seed_text_query: Query<&mut Text, With<SeedText>>,
+) {
+    for event in keyboard_events.read() {
+        if event.state == ButtonState::Pressed {
+            seed_entry.edit(&event.logical_key);
+        }
+    }
+
+    if seed_entry.is_changed() {
+        for mut text in seed_text_query.iter_mut() {
+            text.0 = seed_label(&seed_entry);
+        }
+    }
+}
+
+fn start_run_on_enter(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut run_seed: ResMut<RunSeed>,
+    seed_entry: Res<SeedEntry>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        run_seed.apply_entry(&seed_entry.0);
+        info!("Run seed: {} ({:?})", run_seed.value, run_seed.source);
+        next_state.set(GameState::InGame);
+    }
+}
+
+/// [`Action::Pause`] pauses the run and resumes it again. Everything gated on [`GameState::InGame`] stops
+/// while paused, but the floor stays as it was.
+fn toggle_pause_system(
+    actions: Res<ButtonInput<Action>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+    match state.get() {
+        GameState::InGame => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::InGame),
+        _ => {}
+    }
+}
+
+fn retry_on_key_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyR) || keyboard_input.just_pressed(KeyCode::Enter) {
+        next_state.set(GameState::InGame);
+    }
+}
+
+fn reset_run_stats(mut stats: ResMut<RunStats>, run_seed: Res<RunSeed>) {
+    *stats = RunStats {
+        seed: run_seed.value,
+        ..default()
+    };
+}
+
+fn track_run_stats(
+    mut stats: ResMut<RunStats>,
+    mut death_events: EventReader<EnemyDeathEvent>,
+    floor: Res<CurrentFloor>,
+    time: Res<Time>,
+) {
+    stats.floor = floor.0;
+    stats.seconds += time.delta_secs();
+    stats.kills += death_events.read().count() as u32;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Counters {
+        run_setups: u32,
+        gameplay_frames: u32,
+    }
+
+    fn press(app: &mut App, action: Action) {
+        app.world_mut()
+            .resource_mut::<ButtonInput<Action>>()
+            .press(action);
+        app.update();
+        let mut input = app.world_mut().resource_mut::<ButtonInput<Action>>();
+        input.release(action);
+        input.clear();
+    }
+
+    #[test]
+    fn pausing_freezes_gameplay_without_rebuilding_the_floor() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .init_state::<GameState>()
+            .add_computed_state::<InRun>()
+            .init_resource::<ButtonInput<Action>>()
+            .init_resource::<Counters>()
+            .add_systems(OnEnter(InRun), |mut counters: ResMut<Counters>| {
+                counters.run_setups += 1;
+            })
+            .add_systems(
+                Update,
+                (|mut counters: ResMut<Counters>| counters.gameplay_frames += 1)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(Update, toggle_pause_system.run_if(in_state(InRun)));
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::InGame);
+        app.update();
+        app.update();
+
+        press(&mut app, Action::Pause);
+        app.update();
+        let frames_before_pause = app.world().resource::<Counters>().gameplay_frames;
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(
+            *app.world().resource::<State<GameState>>().get(),
+            GameState::Paused
+        );
+        assert_eq!(
+            app.world().resource::<Counters>().gameplay_frames,
+            frames_before_pause
+        );
+
+        press(&mut app, Action::Pause);
+        app.update();
+        assert_eq!(
+            *app.world().resource::<State<GameState>>().get(),
+            GameState::InGame
+        );
+        let counters = app.world().resource::<Counters>();
+        assert!(counters.gameplay_frames > frames_before_pause);
+        assert_eq!(counters.run_setups, 1);
+    }
+
+    #[test]
+    fn seed_field_takes_digits_only() {
+        let mut entry = SeedEntry::default();
+        for key in ["4", "x", "2", "-"] {
+            entry.edit(&Key::Character(key.into()));
+        }
+        entry.edit(&Key::Enter);
+        assert_eq!(entry.0, "42");
+
+        entry.edit(&Key::Backspace);
+        assert_eq!(entry.0, "4");
+
+        entry.edit(&Key::Character("9".repeat(30).as_str().into()));
+        assert_eq!(entry.0.len(), MAX_SEED_DIGITS);
+        assert!(entry.0.parse::<u64>().is_ok());
+    }
+}
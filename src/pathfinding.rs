@@ -0,0 +1,431 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::arena::ArenaGrid;
+use crate::collision::{is_solid_tile, tile_to_world, world_to_tile};
+use crate::enemy::enemy_movement_system;
+use crate::player::Player;
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// Tiles farther than this from the player are left out of the flow field; agents out there
+/// fall back to their own A* path.
+const FLOW_FIELD_MAX_COST: u32 = 40 * STRAIGHT_COST;
+
+const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// Cost-to-target for every tile within range of a single target tile, built with Dijkstra
+/// over the walkable tiles of an [`ArenaGrid`].
+#[derive(Resource, Default)]
+pub struct FlowField {
+    width: usize,
+    height: usize,
+    costs: Vec<u32>,
+    target: Option<(usize, usize)>,
+}
+
+impl FlowField {
+    pub fn build(arena_grid: &ArenaGrid, target: (usize, usize), max_cost: u32) -> Self {
+        let width = arena_grid.width;
+        let height = arena_grid.height;
+        let mut costs = vec![u32::MAX; width * height];
+        let mut open = BinaryHeap::new();
+
+        if is_walkable(arena_grid, target.0 as i32, target.1 as i32) {
+            costs[target.1 * width + target.0] = 0;
+            open.push(Reverse((0, target)));
+        }
+
+        while let Some(Reverse((cost, (x, y)))) = open.pop() {
+            if cost > costs[y * width + x] {
+                continue;
+            }
+            for ((nx, ny), step) in walkable_neighbors(arena_grid, x, y) {
+                let next_cost = cost + step;
+                let index = ny * width + nx;
+                if next_cost <= max_cost && next_cost < costs[index] {
+                    costs[index] = next_cost;
+                    open.push(Reverse((next_cost, (nx, ny))));
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            costs,
+            target: Some(target),
+        }
+    }
+
+    pub fn target(&self) -> Option<(usize, usize)> {
+        self.target
+    }
+
+    pub fn cost(&self, x: usize, y: usize) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let cost = self.costs[y * self.width + x];
+        (cost != u32::MAX).then_some(cost)
+    }
+
+    /// The neighbouring tile one step downhill towards the target, if `(x, y)` is covered.
+    pub fn next_tile(&self, arena_grid: &ArenaGrid, x: usize, y: usize) -> Option<(usize, usize)> {
+        let current = self.cost(x, y)?;
+        if current == 0 {
+            return None;
+        }
+
+        walkable_neighbors(arena_grid, x, y)
+            .filter_map(|(tile, step)| {
+                let cost = self.cost(tile.0, tile.1)?;
+                (cost < current).then_some((tile, cost + step))
+            })
+            .min_by_key(|&(_, total)| total)
+            .map(|(tile, _)| tile)
+    }
+}
+
+/// Per-agent A* path, stored reversed so the next tile to visit is last.
+#[derive(Component, Default)]
+pub struct AgentPath {
+    goal: Option<(usize, usize)>,
+    tiles: Vec<(usize, usize)>,
+}
+
+/// Shortest 8-connected path from `start` to `goal`, both included, without cutting corners.
+pub fn find_path(
+    arena_grid: &ArenaGrid,
+    start: (usize, usize),
+    goal: (usize, usize),
+) -> Option<Vec<(usize, usize)>> {
+    if !is_walkable(arena_grid, start.0 as i32, start.1 as i32)
+        || !is_walkable(arena_grid, goal.0 as i32, goal.1 as i32)
+    {
+        return None;
+    }
+
+    let width = arena_grid.width;
+    let index = |(x, y): (usize, usize)| y * width + x;
+    let mut best_cost = vec![u32::MAX; width * arena_grid.height];
+    let mut came_from = vec![None; width * arena_grid.height];
+    let mut open = BinaryHeap::new();
+
+    best_cost[index(start)] = 0;
+    open.push(Reverse((octile_distance(start, goal), 0, start)));
+
+    while let Some(Reverse((_, cost, tile))) = open.pop() {
+        if tile == goal {
+            let mut path = vec![goal];
+            let mut current = goal;
+            while let Some(previous) = came_from[index(current)] {
+                path.push(previous);
+                current = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost > best_cost[index(tile)] {
+            continue;
+        }
+
+        for (neighbor, step) in walkable_neighbors(arena_grid, tile.0, tile.1) {
+            let next_cost = cost + step;
+            if next_cost < best_cost[index(neighbor)] {
+                best_cost[index(neighbor)] = next_cost;
+                came_from[index(neighbor)] = Some(tile);
+                open.push(Reverse((
+                    next_cost + octile_distance(neighbor, goal),
+                    next_cost,
+                    neighbor,
+                )));
+            }
+        }
+    }
+
+    None
+}
+
+/// World position an agent at `position` should head for next on its way to `goal`. Uses the
+/// shared flow field when it covers the agent, otherwise the agent's own A* path, and finally
+/// walks straight at the goal when neither has a route.
+pub fn next_waypoint(
+    arena_grid: &ArenaGrid,
+    flow_field: &FlowField,
+    agent_path: &mut AgentPath,
+    position: Vec2,
+    goal: Vec2,
+) -> Vec2 {
+    let tile = world_to_tile(arena_grid, position);
+    let goal_tile = world_to_tile(arena_grid, goal);
+    if tile == goal_tile || tile.min_element() < 0 || goal_tile.min_element() < 0 {
+        return goal;
+    }
+
+    let tile = (tile.x as usize, tile.y as usize);
+    let goal_tile = (goal_tile.x as usize, goal_tile.y as usize);
+
+    if flow_field.target() == Some(goal_tile)
+        && let Some(next) = flow_field.next_tile(arena_grid, tile.0, tile.1)
+    {
+        agent_path.goal = None;
+        agent_path.tiles.clear();
+        return tile_to_world(arena_grid, next.0, next.1);
+    }
+
+    if agent_path.goal != Some(goal_tile) {
+        agent_path.goal = Some(goal_tile);
+        agent_path.tiles = find_path(arena_grid, tile, goal_tile)
+            .map(|mut path| {
+                path.reverse();
+                path
+            })
+            .unwrap_or_default();
+    }
+
+    while agent_path.tiles.last() == Some(&tile) {
+        agent_path.tiles.pop();
+    }
+
+    match agent_path.tiles.last() {
+        Some(&(x, y)) => tile_to_world(arena_grid, x, y),
+        None => goal,
+    }
+}
+
+fn is_walkable(arena_grid: &ArenaGrid, x: i32, y: i32) -> bool {
+    !is_solid_tile(arena_grid, IVec2::new(x, y))
+}
+
+/// Walkable 8-connected neighbours with their step cost. Diagonal steps are only allowed when
+/// both orthogonal tiles they pass between are walkable.
+fn walkable_neighbors(
+    arena_grid: &ArenaGrid,
+    x: usize,
+    y: usize,
+) -> impl Iterator<Item = ((usize, usize), u32)> + '_ {
+    let (x, y) = (x as i32, y as i32);
+    NEIGHBOR_OFFSETS.into_iter().filter_map(move |(dx, dy)| {
+        let (nx, ny) = (x + dx, y + dy);
+        if !is_walkable(arena_grid, nx, ny) {
+            return None;
+        }
+
+        let diagonal = dx != 0 && dy != 0;
+        if diagonal && (!is_walkable(arena_grid, nx, y) || !is_walkable(arena_grid, x, ny)) {
+            return None;
+        }
+
+        let step = if diagonal {
+            DIAGONAL_COST
+        } else {
+            STRAIGHT_COST
+        };
+        Some(((nx as usize, ny as usize), step))
+    })
+}
+
+fn octile_distance(a: (usize, usize), b: (usize, usize)) -> u32 {
+    let dx = a.0.abs_diff(b.0) as u32;
+    let dy = a.1.abs_diff(b.1) as u32;
+    STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+}
+
+pub struct PathfindingPlugin;
+
+impl Plugin for PathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlowField>().add_systems(
+            FixedUpdate,
+            update_flow_field_system
+                .before(enemy_movement_system)
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+fn update_flow_field_system(
+    player_query: Query<&Transform, With<Player>>,
+    arena_grid: Res<ArenaGrid>,
+    mut flow_field: ResMut<FlowField>,
+) {
+    let Ok(player_transform) = player_query.single() else {
+        return;
+    };
+
+    let tile = world_to_tile(&arena_grid, player_transform.translation.truncate());
+    if tile.min_element() < 0 {
+        return;
+    }
+    let target = (tile.x as usize, tile.y as usize);
+
+    if flow_field.target() == Some(target) && !arena_grid.is_changed() {
+        return;
+    }
+
+    *flow_field = FlowField::build(&arena_grid, target, FLOW_FIELD_MAX_COST);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::archetype::Archetypes;
+    use crate::arena::{ARENA_HEIGHT_TILES, ARENA_WIDTH_TILES, ArenaKind, TILE_SIZE, TileType};
+    use crate::collision::move_and_slide;
+
+    const SEEDS: std::ops::Range<u64> = 0..6;
+    const KINDS: [ArenaKind; 3] = [ArenaKind::Cave, ArenaKind::Room, ArenaKind::Dungeon];
+
+    fn generated_grid(kind: ArenaKind, seed: u64) -> ArenaGrid {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let generator = kind.generator(&Archetypes::shipped().arena);
+        ArenaGrid::generate(
+            generator.as_ref(),
+            ARENA_WIDTH_TILES,
+            ARENA_HEIGHT_TILES,
+            &mut rng,
+        )
+    }
+
+    fn reachable_tiles(arena_grid: &ArenaGrid) -> Vec<(usize, usize)> {
+        let mut tiles = Vec::new();
+        for y in 0..arena_grid.height {
+            for x in 0..arena_grid.width {
+                if !arena_grid.grid[y][x].is_solid() && arena_grid.is_reachable(x, y) {
+                    tiles.push((x, y));
+                }
+            }
+        }
+        tiles
+    }
+
+    #[test]
+    fn flow_field_leads_every_reachable_tile_to_the_player() {
+        for kind in KINDS {
+            for seed in SEEDS {
+                let arena_grid = generated_grid(kind, seed);
+                let target = arena_grid.spawn_tile();
+                let field = FlowField::build(&arena_grid, target, u32::MAX);
+
+                for start in reachable_tiles(&arena_grid) {
+                    let mut tile = start;
+                    let mut steps = 0;
+                    while tile != target {
+                        tile = field
+                            .next_tile(&arena_grid, tile.0, tile.1)
+                            .unwrap_or_else(|| panic!("{kind:?}/{seed}: stuck at {tile:?}"));
+                        steps += 1;
+                        assert!(steps <= arena_grid.width * arena_grid.height);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn astar_matches_flow_field_costs() {
+        for kind in KINDS {
+            for seed in SEEDS {
+                let arena_grid = generated_grid(kind, seed);
+                let goal = arena_grid.spawn_tile();
+                let field = FlowField::build(&arena_grid, goal, u32::MAX);
+
+                for start in reachable_tiles(&arena_grid).into_iter().step_by(37) {
+                    let path = find_path(&arena_grid, start, goal)
+                        .unwrap_or_else(|| panic!("{kind:?}/{seed}: no path from {start:?}"));
+                    assert_eq!(path.first(), Some(&start));
+                    assert_eq!(path.last(), Some(&goal));
+
+                    let cost: u32 = path
+                        .windows(2)
+                        .map(|pair| {
+                            walkable_neighbors(&arena_grid, pair[0].0, pair[0].1)
+                                .find(|&(tile, _)| tile == pair[1])
+                                .map(|(_, step)| step)
+                                .expect("path steps must be walkable neighbours")
+                        })
+                        .sum();
+                    assert_eq!(Some(cost), field.cost(start.0, start.1));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sealed_tiles_have_no_path() {
+        let grid = vec![
+            vec![TileType::Wall; 5],
+            vec![
+                TileType::Wall,
+                TileType::Floor,
+                TileType::Wall,
+                TileType::Floor,
+                TileType::Wall,
+            ],
+            vec![TileType::Wall; 5],
+        ];
+        let arena_grid = ArenaGrid::from_tiles(grid);
+
+        assert!(find_path(&arena_grid, (1, 1), (3, 1)).is_none());
+        assert!(
+            FlowField::build(&arena_grid, (1, 1), u32::MAX)
+                .cost(3, 1)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn simulated_enemies_reach_the_player() {
+        const ENEMY_SIZE: f32 = 10.0;
+        const STEP: f32 = 75.0 / 60.0;
+
+        for kind in KINDS {
+            for seed in 0..3 {
+                let arena_grid = generated_grid(kind, seed);
+                let (goal_x, goal_y) = arena_grid.spawn_tile();
+                let goal = tile_to_world(&arena_grid, goal_x, goal_y);
+                let field = FlowField::build(&arena_grid, (goal_x, goal_y), FLOW_FIELD_MAX_COST);
+
+                for start in reachable_tiles(&arena_grid).into_iter().step_by(97) {
+                    let mut position = tile_to_world(&arena_grid, start.0, start.1);
+                    let mut agent_path = AgentPath::default();
+                    let max_steps =
+                        (arena_grid.width * arena_grid.height) as f32 * TILE_SIZE / STEP;
+
+                    let mut steps = 0.0;
+                    while position.distance(goal) > TILE_SIZE {
+                        let waypoint =
+                            next_waypoint(&arena_grid, &field, &mut agent_path, position, goal);
+                        let delta = (waypoint - position).normalize_or_zero() * STEP;
+                        position =
+                            move_and_slide(&arena_grid, position, Vec2::splat(ENEMY_SIZE), delta)
+                                .position;
+
+                        steps += 1.0;
+                        assert!(
+                            steps < max_steps,
+                            "{kind:?}/{seed}: enemy from {start:?} stuck at {position:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
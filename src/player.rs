@@ -1,29 +1,42 @@
-use crate::GameState;
-use crate::arena::{ARENA_HEIGHT_TILES, ARENA_WIDTH_TILES, ArenaGrid, TILE_SIZE, TileType};
-use bevy::{prelude::*, window::PrimaryWindow};
+use crate::action::{Action, AimInput, InputBindings, MoveInput, assisted_aim};
+use crate::archetype::{Archetypes, PlayerArchetype, ProjectileArchetype};
+use crate::arena::{ArenaGrid, setup_arena};
+use crate::blank::Blanks;
+use crate::collision::{move_and_slide, raycast_aabb, raycast_tiles, tile_to_world};
+use crate::combat::{Invulnerability, ProjectileImpactEvent};
+use crate::dodge::DodgeRoll;
+use crate::enemy::Enemy;
+use crate::enemy_ai::NoiseEvent;
+use crate::floor::{FloorEntity, SetupFloor};
+use crate::inventory::Inventory;
+use crate::replay::Playback;
+use crate::simulation::{FixedInput, Interpolated};
+use crate::weapon::{StartingWeapon, TriggerInput, Weapon, WeaponRng};
+use crate::{GameState, InRun};
+use bevy::prelude::*;
 
 pub struct PlayerPlugin;
 
-const PLAYER_DEFAULT_HEALTH: f32 = 100.0;
-const PLAYER_DEFAULT_SPEED: f32 = 150.0;
+/// Sizes used for collision if a sprite has no explicit size.
 const PLAYER_SPRITE_SIZE: f32 = 10.0;
-
-const WEAPON_DEFAULT_PROJECTILE_SPEED: f32 = 400.0;
-const WEAPON_DEFAULT_PROJECTILE_DAMAGE: f32 = 10.0;
-
 const PROJECTILE_SPRITE_WIDTH: f32 = 10.0;
 const PROJECTILE_SPRITE_HEIGHT: f32 = 4.0;
-const PROJECTILE_LIFETIME_SECONDS: f32 = 2.0;
-const PROJECTILE_SPAWN_OFFSET: f32 = 5.0;
+
+const GUNSHOT_NOISE_RADIUS: f32 = 250.0;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::InGame), spawn_player)
+        app.add_systems(SetupFloor, spawn_player.after(setup_arena))
             .add_systems(
                 Update,
+                player_aiming_system
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(not(resource_exists::<Playback>)),
+            )
+            .add_systems(
+                FixedUpdate,
                 (
                     player_movement_system,
-                    player_aiming_system,
                     player_shooting_system,
                     projectile_movement_system,
                     projectile_lifetime_system,
@@ -33,7 +46,9 @@ impl Plugin for PlayerPlugin {
     }
 }
 
+/// The player outlives floors, so it's scoped to the whole run.
 #[derive(Component)]
+#[require(StateScoped<InRun>, Interpolated)]
 pub struct Player;
 
 #[derive(Component)]
@@ -45,18 +60,34 @@ pub struct Health {
 #[derive(Component)]
 pub struct Speed(pub f32);
 
-#[derive(Component)]
-pub struct Weapon {
-    pub projectile_speed: f32,
-    pub projectile_damage: f32,
+/// Distance per second actually moved last frame, after wall sliding.
+#[derive(Component, Default)]
+pub struct Velocity(pub Vec2);
+
+/// Who fired a projectile; it only damages the other side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Enemy,
+}
+
+impl Faction {
+    pub fn can_hit(self, target_is_player: bool) -> bool {
+        match self {
+            Faction::Player => !target_is_player,
+            Faction::Enemy => target_is_player,
+        }
+    }
 }
 
 #[derive(Component)]
+#[require(FloorEntity, Interpolated)]
 pub struct Projectile {
     pub direction: Vec2,
     pub speed: f32,
     pub lifetime: Timer,
     pub damage: f32,
+    pub faction: Faction,
 }
 
 #[derive(Bundle)]
@@ -67,271 +98,326 @@ pub struct ProjectileBundle {
     visibility: Visibility,
 }
 
+impl ProjectileBundle {
+    pub fn new(
+        archetype: &ProjectileArchetype,
+        faction: Faction,
+        position: Vec2,
+        direction: Vec2,
+        speed: f32,
+        damage: f32,
+    ) -> Self {
+        Self {
+            data: Projectile {
+                direction,
+                speed,
+                lifetime: Timer::from_seconds(archetype.lifetime_seconds, TimerMode::Once),
+                damage,
+                faction,
+            },
+            sprite: Sprite {
+                color: archetype.color.into(),
+                custom_size: Some(Vec2::new(archetype.size.0, archetype.size.1)),
+                ..default()
+            },
+            transform: Transform {
+                translation: position.extend(0.0),
+                rotation: Quat::from_rotation_z(direction.y.atan2(direction.x)),
+                scale: Vec3::ONE,
+            },
+            visibility: Visibility::Visible,
+        }
+    }
+
+    pub fn with_lifetime(mut self, seconds: f32) -> Self {
+        self.data.lifetime = Timer::from_seconds(seconds, TimerMode::Once);
+        self
+    }
+}
+
 #[derive(Bundle)]
 pub struct PlayerBundle {
     player_marker: Player,
     health: Health,
     speed: Speed,
+    velocity: Velocity,
     sprite: Sprite,
     transform: Transform,
     visibility: Visibility,
-    weapon: Weapon,
+    inventory: Inventory,
+    invulnerability: Invulnerability,
+    dodge: DodgeRoll,
+    blanks: Blanks,
 }
 
-impl Default for PlayerBundle {
-    fn default() -> Self {
+impl PlayerBundle {
+    pub fn new(archetype: &PlayerArchetype, starting_weapon: Weapon, position: Vec2) -> Self {
         Self {
             player_marker: Player,
             health: Health {
-                current: PLAYER_DEFAULT_HEALTH,
-                max: PLAYER_DEFAULT_HEALTH,
+                current: archetype.health,
+                max: archetype.health,
             },
-            speed: Speed(PLAYER_DEFAULT_SPEED),
+            speed: Speed(archetype.speed),
+            velocity: Velocity::default(),
             sprite: Sprite {
-                color: Color::srgb(0.25, 0.5, 0.75),
-                custom_size: Some(Vec2::splat(PLAYER_SPRITE_SIZE)),
+                color: archetype.color.into(),
+                custom_size: Some(Vec2::splat(archetype.size)),
                 ..default()
             },
-            transform: Transform::default(),
+            transform: Transform::from_translation(position.extend(0.0)),
             visibility: Visibility::Visible,
-            weapon: Weapon::default(),
+            inventory: Inventory::new(starting_weapon),
+            invulnerability: Invulnerability::new(archetype.invulnerability_seconds),
+            dodge: DodgeRoll::new(archetype.dodge.clone()),
+            blanks: Blanks::new(&archetype.blank),
         }
     }
 }
 
-impl Default for Weapon {
-    fn default() -> Self {
-        Self {
-            projectile_speed: WEAPON_DEFAULT_PROJECTILE_SPEED,
-            projectile_damage: WEAPON_DEFAULT_PROJECTILE_DAMAGE,
-        }
+/// Spawns the player for a new run, or moves the one already in the run to the new floor's
+/// spawn with a fresh set of blanks.
+#[allow(clippy::type_complexity)]
+fn spawn_player(
+    mut commands: Commands,
+    mut player_query: Query<
+        (&mut Transform, &mut Velocity, &mut DodgeRoll, &mut Blanks),
+        With<Player>,
+    >,
+    arena_grid: Res<ArenaGrid>,
+    archetypes: Res<Archetypes>,
+    starting_weapon: Res<StartingWeapon>,
+) {
+    let (spawn_x, spawn_y) = arena_grid.spawn_tile();
+    let spawn_position = tile_to_world(&arena_grid, spawn_x, spawn_y);
+
+    if let Ok((mut transform, mut velocity, mut dodge, mut blanks)) = player_query.single_mut() {
+        transform.translation = spawn_position.extend(transform.translation.z);
+        velocity.0 = Vec2::ZERO;
+        *dodge = DodgeRoll::new(archetypes.player.dodge.clone());
+        *blanks = Blanks::new(&archetypes.player.blank);
+        return;
     }
-}
 
-fn spawn_player(mut commands: Commands) {
-    commands.spawn(PlayerBundle::default());
-}
-
-fn check_aabb_collision(pos1: Vec2, size1: Vec2, pos2: Vec2, size2: Vec2) -> bool {
-    let half_size1 = size1 / 2.0;
-    let half_size2 = size2 / 2.0;
+    let weapon_id = starting_weapon
+        .0
+        .as_deref()
+        .unwrap_or(&archetypes.player.starting_weapon);
 
-    let min1 = pos1 - half_size1;
-    let max1 = pos1 + half_size1;
-    let min2 = pos2 - half_size2;
-    let max2 = pos2 + half_size2;
-
-    (min1.x < max2.x && max1.x > min2.x) && (min1.y < max2.y && max1.y > min2.y)
+    commands.spawn(PlayerBundle::new(
+        &archetypes.player,
+        Weapon::starter(archetypes.weapon(weapon_id).clone()),
+        spawn_position,
+    ));
 }
 
-fn player_movement_system(
-    mut player_query: Query<(&mut Transform, &Speed, &Sprite), With<Player>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+#[allow(clippy::type_complexity)]
+pub fn player_movement_system(
+    mut player_query: Query<
+        (&mut Transform, &Speed, &mut Velocity, &Sprite, &DodgeRoll),
+        With<Player>,
+    >,
+    move_input: Res<MoveInput>,
     time: Res<Time>,
     arena_grid: Res<ArenaGrid>,
 ) {
-    if let Ok((mut transform, speed, player)) = player_query.single_mut() {
-        let mut direction = Vec3::ZERO;
-
-        if keyboard_input.pressed(KeyCode::KeyW) || keyboard_input.pressed(KeyCode::ArrowUp) {
-            direction.y += 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::KeyS) || keyboard_input.pressed(KeyCode::ArrowDown) {
-            direction.y -= 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::KeyA) || keyboard_input.pressed(KeyCode::ArrowLeft) {
-            direction.x -= 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::KeyD) || keyboard_input.pressed(KeyCode::ArrowRight) {
-            direction.x += 1.0;
+    if let Ok((mut transform, speed, mut velocity, player, dodge)) = player_query.single_mut() {
+        if dodge.is_rolling() {
+            return;
         }
+        velocity.0 = Vec2::ZERO;
 
-        if direction.length_squared() > 0.0 {
-            direction = direction.normalize();
+        let direction = move_input.0;
 
+        if direction != Vec2::ZERO {
             let move_amount = direction * speed.0 * time.delta_secs();
 
             let player_size = player
                 .custom_size
                 .unwrap_or(Vec2::splat(PLAYER_SPRITE_SIZE));
 
-            let current_pos = transform.translation.truncate();
-
-            let next_pos_x = current_pos + Vec2::new(move_amount.x, 0.0);
-            let mut collision_x = false;
-            if move_amount.x != 0.0 {
-                for wall_pos_world in
-                    get_nearby_wall_positions_world(&next_pos_x, player_size, &arena_grid)
-                {
-                    if check_aabb_collision(
-                        next_pos_x,
-                        player_size,
-                        wall_pos_world,
-                        Vec2::splat(TILE_SIZE),
-                    ) {
-                        collision_x = true;
-                        break;
-                    }
-                }
-            }
-            if !collision_x {
-                transform.translation.x += move_amount.x;
-            }
-
-            let current_pos_after_x_move = transform.translation.truncate();
-            let next_pos_y = current_pos_after_x_move + Vec2::new(0.0, move_amount.y);
-            let mut collision_y = false;
-            if move_amount.y != 0.0 {
-                for wall_pos_world in
-                    get_nearby_wall_positions_world(&next_pos_y, player_size, &arena_grid)
-                {
-                    if check_aabb_collision(
-                        next_pos_y,
-                        player_size,
-                        wall_pos_world,
-                        Vec2::splat(TILE_SIZE),
-                    ) {
-                        collision_y = true;
-                        break;
-                    }
-                }
-            }
-            if !collision_y {
-                transform.translation.y += move_amount.y;
-            }
-        }
-    }
-}
-
-fn get_nearby_wall_positions_world(
-    object_pos_world: &Vec2,
-    object_size: Vec2,
-    arena_grid: &Res<ArenaGrid>,
-) -> Vec<Vec2> {
-    let mut wall_positions = Vec::new();
-
-    let total_arena_width_pixels = ARENA_WIDTH_TILES as f32 * TILE_SIZE;
-    let total_arena_height_pixels = ARENA_HEIGHT_TILES as f32 * TILE_SIZE;
-    let arena_offset_x = -total_arena_width_pixels / 2.0;
-    let arena_offset_y = -total_arena_height_pixels / 2.0;
-
-    let object_half_size = object_size / 2.0;
-    let search_min_world = *object_pos_world - object_half_size - Vec2::splat(TILE_SIZE); //check
-    let search_max_world = *object_pos_world + object_half_size + Vec2::splat(TILE_SIZE); //check
-
-    let start_x_grid = ((search_min_world.x - arena_offset_x) / TILE_SIZE).floor() as i32;
-    let end_x_grid = ((search_max_world.x - arena_offset_x) / TILE_SIZE).ceil() as i32;
-    let start_y_grid = ((search_min_world.y - arena_offset_y) / TILE_SIZE).floor() as i32;
-    let end_y_grid = ((search_max_world.y - arena_offset_y) / TILE_SIZE).ceil() as i32;
-
-    for gy in start_y_grid.max(0)..=end_y_grid.min(ARENA_HEIGHT_TILES as i32 - 1) {
-        for gx in start_x_grid.max(0)..=end_x_grid.min(ARENA_WIDTH_TILES as i32 - 1) {
-            let gy_usize = gy as usize;
-            let gx_usize = gx as usize;
-            if arena_grid.grid[gy_usize][gx_usize] == TileType::Wall {
-                let wall_world_x = gx_usize as f32 * TILE_SIZE + arena_offset_x + TILE_SIZE / 2.0;
-                let wall_world_y = gy_usize as f32 * TILE_SIZE + arena_offset_y + TILE_SIZE / 2.0;
-                wall_positions.push(Vec2::new(wall_world_x, wall_world_y));
+            let result = move_and_slide(
+                &arena_grid,
+                transform.translation.truncate(),
+                player_size,
+                move_amount,
+            );
+            if time.delta_secs() > 0.0 {
+                velocity.0 =
+                    (result.position - transform.translation.truncate()) / time.delta_secs();
             }
+            transform.translation.x = result.position.x;
+            transform.translation.y = result.position.y;
         }
     }
-    wall_positions
 }
 
+/// Faces the cursor, or the right stick's direction bent towards enemies by aim assist.
 fn player_aiming_system(
     mut player_query: Query<&mut Transform, With<Player>>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    enemy_query: Query<&Transform, (With<Enemy>, Without<Player>)>,
+    aim_input: Res<AimInput>,
+    bindings: Res<InputBindings>,
+    arena_grid: Res<ArenaGrid>,
 ) {
-    if let Ok(mut player_transform) = player_query.single_mut() {
-        if let Ok(primary_window) = window_query.single() {
-            if let Some(cursor_position) = primary_window.cursor_position() {
-                if let Ok((camera, camera_global_transform)) = camera_query.single() {
-                    if let Ok(world_position) =
-                        camera.viewport_to_world_2d(camera_global_transform, cursor_position)
-                    {
-                        let direction_to_cursor =
-                            world_position - player_transform.translation.truncate();
-                        let angle = direction_to_cursor.y.atan2(direction_to_cursor.x);
-                        player_transform.rotation = Quat::from_rotation_z(angle);
-                    }
-                }
-            }
-        }
-    }
+    let Ok(mut player_transform) = player_query.single_mut() else {
+        return;
+    };
+    let position = player_transform.translation.truncate();
+    let direction = match *aim_input {
+        AimInput::None => return,
+        AimInput::Cursor(cursor) => cursor - position,
+        AimInput::Stick(stick) if bindings.aim_assist => assisted_aim(
+            position,
+            stick,
+            enemy_query
+                .iter()
+                .map(|transform| transform.translation.truncate()),
+            &arena_grid,
+        ),
+        AimInput::Stick(stick) => stick,
+    };
+    player_transform.rotation = Quat::from_rotation_z(direction.y.atan2(direction.x));
 }
 
-fn player_shooting_system(
+#[allow(clippy::too_many_arguments)]
+pub fn player_shooting_system(
     mut commands: Commands,
-    player_query: Query<(&Transform, &Weapon), With<Player>>,
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mut player_query: Query<(&mut Transform, &mut Inventory, &Sprite, &DodgeRoll), With<Player>>,
+    actions: Res<FixedInput<Action>>,
+    time: Res<Time>,
+    arena_grid: Res<ArenaGrid>,
+    archetypes: Res<Archetypes>,
+    mut weapon_rng: ResMut<WeaponRng>,
+    mut noise_events: EventWriter<NoiseEvent>,
 ) {
-    if let Ok((player_transform, weapon)) = player_query.single() {
-        if mouse_button_input.just_pressed(MouseButton::Left) {
-            let projectile_direction_3d = player_transform.rotation * Vec3::X;
-
-            commands.spawn(ProjectileBundle {
-                data: Projectile {
-                    direction: projectile_direction_3d.truncate(),
-                    speed: weapon.projectile_speed,
-                    lifetime: Timer::from_seconds(PROJECTILE_LIFETIME_SECONDS, TimerMode::Once),
-                    damage: weapon.projectile_damage,
-                },
-                sprite: Sprite {
-                    color: Color::WHITE,
-                    custom_size: Some(Vec2::new(PROJECTILE_SPRITE_WIDTH, PROJECTILE_SPRITE_HEIGHT)),
-                    ..default()
-                },
-                transform: Transform {
-                    translation: player_transform.translation
-                        + projectile_direction_3d * PROJECTILE_SPAWN_OFFSET,
-                    rotation: player_transform.rotation,
-                    scale: Vec3::ONE,
-                },
-                visibility: Visibility::Visible,
-            });
+    let Ok((mut player_transform, mut inventory, player_sprite, dodge)) = player_query.single_mut()
+    else {
+        return;
+    };
+    let weapon = inventory.active_mut();
+    let projectile = archetypes.projectile(Faction::Player);
+
+    // No shooting mid-roll; the weapon still ticks so reloads and cooldowns carry on.
+    let trigger = if dodge.is_rolling() {
+        TriggerInput::default()
+    } else {
+        TriggerInput {
+            pressed: actions.pressed(Action::Fire),
+            just_pressed: actions.just_pressed(Action::Fire),
+            just_released: actions.just_released(Action::Fire),
+            reload: actions.just_pressed(Action::Reload),
+        }
+    };
+    let shots = weapon.update(trigger, time.delta_secs());
+    let aim = (player_transform.rotation * Vec3::X).truncate();
+
+    for _ in 0..shots {
+        let origin = player_transform.translation.truncate() + aim * projectile.spawn_offset;
+        for direction in weapon.shot_directions(aim, &mut weapon_rng.0) {
+            commands.spawn(ProjectileBundle::new(
+                projectile,
+                Faction::Player,
+                origin,
+                direction,
+                weapon.stats.projectile_speed,
+                weapon.stats.projectile_damage,
+            ));
+        }
+        noise_events.write(NoiseEvent {
+            position: player_transform.translation.truncate(),
+            radius: GUNSHOT_NOISE_RADIUS,
+        });
+
+        if weapon.stats.recoil > 0.0 {
+            let player_size = player_sprite
+                .custom_size
+                .unwrap_or(Vec2::splat(PLAYER_SPRITE_SIZE));
+            let result = move_and_slide(
+                &arena_grid,
+                player_transform.translation.truncate(),
+                player_size,
+                -aim * weapon.stats.recoil,
+            );
+            player_transform.translation.x = result.position.x;
+            player_transform.translation.y = result.position.y;
         }
     }
 }
 
-fn projectile_movement_system(
+#[allow(clippy::type_complexity)]
+pub fn projectile_movement_system(
     mut commands: Commands,
     mut projectile_query: Query<(Entity, &mut Transform, &Projectile, &Sprite)>,
+    target_query: Query<
+        (Entity, &Transform, &Sprite, Has<Player>, Option<&DodgeRoll>),
+        (Or<(With<Enemy>, With<Player>)>, Without<Projectile>),
+    >,
     time: Res<Time>,
     arena_grid: Res<ArenaGrid>,
+    mut impact_events: EventWriter<ProjectileImpactEvent>,
 ) {
     for (entity, mut transform, projectile_data, projectile_sprite) in projectile_query.iter_mut() {
-        let movement_vector = projectile_data.direction * projectile_data.speed * time.delta_secs();
-        let next_pos_2d = transform.translation.truncate() + movement_vector;
+        let direction = projectile_data.direction.normalize_or_zero();
+        let travel = projectile_data.speed * time.delta_secs();
+        let origin = transform.translation.truncate();
 
         let projectile_size = projectile_sprite
             .custom_size
             .unwrap_or(Vec2::new(PROJECTILE_SPRITE_WIDTH, PROJECTILE_SPRITE_HEIGHT));
 
-        let mut collision_detected = false;
-        for wall_pos_world in
-            get_nearby_wall_positions_world(&next_pos_2d, projectile_size, &arena_grid)
+        let mut nearest_hit =
+            raycast_tiles(&arena_grid, origin, direction, travel).map(|hit| (hit, None));
+
+        for (target_entity, target_transform, target_sprite, is_player, dodge) in
+            target_query.iter()
         {
-            if check_aabb_collision(
-                next_pos_2d,
-                projectile_size,
-                wall_pos_world,
-                Vec2::splat(TILE_SIZE),
-            ) {
-                collision_detected = true;
-                break;
+            if !projectile_data.faction.can_hit(is_player) {
+                continue;
+            }
+            // Rolling through a bullet is the point of the dodge.
+            if dodge.is_some_and(DodgeRoll::is_invulnerable) {
+                continue;
+            }
+
+            let target_size = target_sprite.custom_size.unwrap_or(projectile_size);
+            let Some(hit) = raycast_aabb(
+                origin,
+                direction,
+                travel,
+                target_transform.translation.truncate(),
+                (target_size + projectile_size) / 2.0,
+            ) else {
+                continue;
+            };
+
+            if nearest_hit.is_none_or(|(nearest, _)| hit.distance < nearest.distance) {
+                nearest_hit = Some((hit, Some(target_entity)));
             }
         }
 
-        if collision_detected {
-            commands.entity(entity).despawn();
-        } else {
-            transform.translation += Vec3::new(movement_vector.x, movement_vector.y, 0.0);
+        match nearest_hit {
+            Some((hit, target)) => {
+                transform.translation.x = hit.point.x;
+                transform.translation.y = hit.point.y;
+                impact_events.write(ProjectileImpactEvent {
+                    target,
+                    point: hit.point,
+                    normal: hit.normal,
+                    damage: projectile_data.damage,
+                });
+                commands.entity(entity).despawn();
+            }
+            None => {
+                let movement_vector = direction * travel;
+                transform.translation.x += movement_vector.x;
+                transform.translation.y += movement_vector.y;
+            }
         }
     }
 }
 
-fn projectile_lifetime_system(
+pub fn projectile_lifetime_system(
     mut commands: Commands,
     mut projectile_query: Query<(Entity, &mut Projectile)>,
     time: Res<Time>,
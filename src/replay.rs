@@ -0,0 +1,693 @@
+use std::fmt;
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::action::{Action, MoveInput};
+use crate::archetype::Archetypes;
+use crate::arena::ArenaMode;
+use crate::cli;
+use crate::enemy::Enemy;
+use crate::floor::CurrentFloor;
+use crate::player::{Health, Player, Projectile};
+use crate::seed::{RunSeed, SeedSource};
+use crate::simulation::{FixedInput, Interpolated};
+use crate::weapon::StartingWeapon;
+use crate::{GameState, InRun};
+
+const RECORD_ARG: &str = "--record";
+const REPLAY_ARG: &str = "--replay";
+/// Bumped whenever the file layout or [`Action::ALL`] change.
+const REPLAY_VERSION: u32 = 3;
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// What the player did during one fixed step.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TickInput {
+    /// Actions held during the step, one bit per entry in [`Action::ALL`].
+    held: u32,
+    /// Actions that started since the previous step.
+    pressed: u32,
+    /// Actions that stopped since the previous step.
+    released: u32,
+    /// [`MoveInput`] for the step.
+    movement: Vec2,
+    /// Aim angle in radians.
+    aim: f32,
+}
+
+impl TickInput {
+    fn capture(actions: &ButtonInput<Action>, movement: MoveInput, aim: Quat) -> Self {
+        let mut input = Self {
+            held: 0,
+            pressed: 0,
+            released: 0,
+            movement: movement.0,
+            aim: {
+                let direction = (aim * Vec3::X).truncate();
+                direction.y.atan2(direction.x)
+            },
+        };
+        for (bit, action) in Action::ALL.into_iter().enumerate() {
+            input.held |= (actions.pressed(action) as u32) << bit;
+            input.pressed |= (actions.just_pressed(action) as u32) << bit;
+            input.released |= (actions.just_released(action) as u32) << bit;
+        }
+        input
+    }
+
+    fn aim_rotation(&self) -> Quat {
+        Quat::from_rotation_z(self.aim)
+    }
+
+    /// Rebuilds the step's action state. Releasing before pressing covers an action stopped and
+    /// started again within one step.
+    fn apply(&self, actions: &mut ButtonInput<Action>, movement: &mut MoveInput) {
+        *actions = ButtonInput::default();
+        for (bit, action) in Action::ALL.into_iter().enumerate() {
+            if self.released & (1 << bit) != 0 {
+                actions.press(action);
+                actions.release(action);
+            }
+            if self.held & (1 << bit) != 0 {
+                actions.press(action);
+            }
+            if self.pressed & (1 << bit) == 0 {
+                actions.clear_just_pressed(action);
+            }
+        }
+        movement.0 = self.movement;
+    }
+}
+
+/// A recorded run: the settings it started with and the player's input on every step.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub arena_mode: String,
+    pub starting_weapon: Option<String>,
+    /// [`archetypes_hash`] of the archetypes the run was played with.
+    pub archetypes_hash: u64,
+    /// Steps recorded.
+    pub ticks: u32,
+    /// [`state_hash`] after the last step.
+    pub final_hash: u64,
+    /// Each step's input, with runs of identical steps stored once next to their length.
+    inputs: Vec<(u32, TickInput)>,
+}
+
+impl Replay {
+    fn new(
+        seed: u64,
+        arena_mode: ArenaMode,
+        starting_weapon: Option<String>,
+        archetypes: &Archetypes,
+    ) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            arena_mode: arena_mode.name().to_string(),
+            starting_weapon,
+            archetypes_hash: archetypes_hash(archetypes),
+            ticks: 0,
+            final_hash: 0,
+            inputs: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, input: TickInput) {
+        match self.inputs.last_mut() {
+            Some((count, last)) if *last == input => *count += 1,
+            _ => self.inputs.push((1, input)),
+        }
+        self.ticks += 1;
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let replay: Self = ron::from_str(&fs::read_to_string(path)?)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        if ArenaMode::from_name(&replay.arena_mode).is_none() {
+            return Err(ReplayError::ArenaMode(replay.arena_mode));
+        }
+        let ticks: u64 = replay
+            .inputs
+            .iter()
+            .map(|(count, _)| u64::from(*count))
+            .sum();
+        if ticks != u64::from(replay.ticks) {
+            return Err(ReplayError::TickCount {
+                expected: replay.ticks,
+                found: ticks,
+            });
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        fs::write(path, ron::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+    Version(u32),
+    ArenaMode(String),
+    TickCount { expected: u32, found: u64 },
+    Archetypes { recorded: u64, loaded: u64 },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "could not access replay file: {error}"),
+            ReplayError::Parse(error) => write!(f, "invalid replay file: {error}"),
+            ReplayError::Write(error) => write!(f, "could not write replay: {error}"),
+            ReplayError::Version(version) => write!(
+                f,
+                "replay is version {version}, this build plays version {REPLAY_VERSION}"
+            ),
+            ReplayError::ArenaMode(mode) => write!(f, "replay has unknown arena mode '{mode}'"),
+            ReplayError::TickCount { expected, found } => {
+                write!(
+                    f,
+                    "replay should have {expected} ticks of input, found {found}"
+                )
+            }
+            ReplayError::Archetypes { recorded, loaded } => write!(
+                f,
+                "replay was recorded with other archetypes ({recorded:016x}) than the ones \
+                 loaded ({loaded:016x}), so it would not play back the same"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(error: std::io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for ReplayError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        ReplayError::Parse(error)
+    }
+}
+
+impl From<ron::Error> for ReplayError {
+    fn from(error: ron::Error) -> Self {
+        ReplayError::Write(error)
+    }
+}
+
+/// Records every run. With a path, each run is written there when it ends.
+#[derive(Resource, Debug, Default)]
+pub struct Recorder {
+    path: Option<PathBuf>,
+    replay: Option<Replay>,
+    hashed_ticks: u32,
+}
+
+impl Recorder {
+    pub fn to_file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..default()
+        }
+    }
+
+    /// The current run's recording, or the last one's once it has ended.
+    pub fn replay(&self) -> Option<&Replay> {
+        self.replay.as_ref()
+    }
+}
+
+/// Plays a replay's input back in place of the player's.
+#[derive(Resource, Debug)]
+pub struct Playback {
+    replay: Replay,
+    run: usize,
+    ticks_into_run: u32,
+    ticks_played: u32,
+}
+
+impl Playback {
+    /// Sets up the run the replay was recorded on and queues its input. The run itself still
+    /// has to be started.
+    ///
+    /// Fails if the loaded [`Archetypes`] aren't the ones the replay was recorded with.
+    ///
+    /// # Panics
+    ///
+    /// If the archetypes haven't loaded yet.
+    pub fn begin(world: &mut World, replay: Replay) -> Result<(), ReplayError> {
+        let loaded = archetypes_hash(world.resource::<Archetypes>());
+        if replay.archetypes_hash != loaded {
+            return Err(ReplayError::Archetypes {
+                recorded: replay.archetypes_hash,
+                loaded,
+            });
+        }
+
+        world.insert_resource(RunSeed {
+            value: replay.seed,
+            source: SeedSource::Replay,
+        });
+        world.insert_resource(ArenaMode::from_name(&replay.arena_mode).unwrap_or_default());
+        world.insert_resource(StartingWeapon(replay.starting_weapon.clone()));
+        world.insert_resource(Playback {
+            replay,
+            run: 0,
+            ticks_into_run: 0,
+            ticks_played: 0,
+        });
+        Ok(())
+    }
+
+    fn next(&mut self) -> Option<TickInput> {
+        let (count, input) = *self.replay.inputs.get(self.run)?;
+        self.ticks_into_run += 1;
+        if self.ticks_into_run == count {
+            self.run += 1;
+            self.ticks_into_run = 0;
+        }
+        self.ticks_played += 1;
+        Some(input)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.ticks_played == self.replay.ticks
+    }
+
+    fn result(&self, hash: u64) -> ReplayResult {
+        ReplayResult {
+            ticks_played: self.ticks_played,
+            ticks_recorded: self.replay.ticks,
+            recorded_hash: self.replay.final_hash,
+            hash,
+        }
+    }
+}
+
+/// How the last playback ended.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayResult {
+    pub ticks_played: u32,
+    pub ticks_recorded: u32,
+    pub recorded_hash: u64,
+    pub hash: u64,
+}
+
+impl ReplayResult {
+    /// Whether the replay ran to the end and finished where the recording did.
+    pub fn matched(&self) -> bool {
+        self.ticks_played == self.ticks_recorded && self.hash == self.recorded_hash
+    }
+}
+
+/// A replay loaded from the command line, waiting for the archetypes it's checked against.
+#[derive(Resource)]
+struct PendingReplay(Replay);
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(path) = cli::arg_value(RECORD_ARG) {
+            info!("Recording runs to {path}.");
+            app.insert_resource(Recorder::to_file(path));
+        }
+        if let Some(path) = cli::arg_value(REPLAY_ARG) {
+            match Replay::load(&path) {
+                Ok(replay) => {
+                    info!("Playing back {} ticks from {path}.", replay.ticks);
+                    app.insert_resource(PendingReplay(replay));
+                }
+                Err(error) => error!("Could not load replay {path}: {error}"),
+            }
+        }
+
+        app.add_systems(
+            OnEnter(InRun),
+            start_recording.run_if(resource_exists::<Recorder>),
+        )
+        .add_systems(
+            OnExit(InRun),
+            (
+                save_recording.run_if(resource_exists::<Recorder>),
+                end_playback_early.run_if(resource_exists::<Playback>),
+            ),
+        )
+        .add_systems(
+            Update,
+            start_playback
+                .run_if(in_state(GameState::MainMenu))
+                .run_if(resource_exists::<PendingReplay>),
+        )
+        .add_systems(
+            FixedPreUpdate,
+            (
+                play_tick.run_if(resource_exists::<Playback>),
+                record_tick.run_if(resource_exists::<Recorder>),
+            )
+                .chain()
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            FixedPostUpdate,
+            (
+                hash_recorded_tick.run_if(resource_exists::<Recorder>),
+                finish_playback.run_if(resource_exists::<Playback>),
+            )
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            Last,
+            save_recording
+                .run_if(on_event::<AppExit>)
+                .run_if(in_state(InRun))
+                .run_if(resource_exists::<Recorder>),
+        );
+    }
+}
+
+/// A fingerprint of the simulation: the floor, and where everyone is and how hurt. Runs that end
+/// with the same hash ended in the same place.
+#[allow(clippy::type_complexity)]
+pub fn state_hash(world: &mut World) -> u64 {
+    let mut query = world.query_filtered::<(
+        &Transform,
+        Option<&Interpolated>,
+        Option<&Health>,
+        Has<Player>,
+        Has<Enemy>,
+    ), Or<(With<Player>, With<Enemy>, With<Projectile>)>>();
+
+    // Sorted so the hash doesn't depend on the order entities happen to be stored in.
+    let mut entity_hashes: Vec<u64> = query
+        .iter(world)
+        .map(|(transform, interpolated, health, is_player, is_enemy)| {
+            let translation = interpolated.map_or(transform.translation, |interpolated| {
+                interpolated.simulated_translation(transform)
+            });
+            let mut hasher = StateHasher::default();
+            hasher.write_u8(is_player as u8 | (is_enemy as u8) << 1);
+            hasher.write_u32(translation.x.to_bits());
+            hasher.write_u32(translation.y.to_bits());
+            hasher.write_u32(health.map_or(0, |health| health.current.to_bits()));
+            hasher.finish()
+        })
+        .collect();
+    entity_hashes.sort_unstable();
+
+    let mut hasher = StateHasher::default();
+    hasher.write_u32(world.resource::<CurrentFloor>().0);
+    for entity_hash in entity_hashes {
+        hasher.write_u64(entity_hash);
+    }
+    hasher.finish()
+}
+
+/// A fingerprint of the tuning a run plays with. Hashes the debug form, which is stable since
+/// every map in the archetypes is ordered.
+pub fn archetypes_hash(archetypes: &Archetypes) -> u64 {
+    let mut hasher = StateHasher::default();
+    hasher.write(format!("{archetypes:?}").as_bytes());
+    hasher.finish()
+}
+
+/// FNV-1a over little-endian bytes, so a hash means the same on every machine and build.
+struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+}
+
+fn start_recording(
+    mut recorder: ResMut<Recorder>,
+    run_seed: Res<RunSeed>,
+    arena_mode: Res<ArenaMode>,
+    starting_weapon: Res<StartingWeapon>,
+    archetypes: Res<Archetypes>,
+) {
+    recorder.replay = Some(Replay::new(
+        run_seed.value,
+        *arena_mode,
+        starting_weapon.0.clone(),
+        &archetypes,
+    ));
+    recorder.hashed_ticks = 0;
+}
+
+fn save_recording(recorder: Res<Recorder>) {
+    let (Some(path), Some(replay)) = (&recorder.path, &recorder.replay) else {
+        return;
+    };
+    match replay.save(path) {
+        Ok(()) => info!(
+            "Saved {} ticks of replay to {}.",
+            replay.ticks,
+            path.display()
+        ),
+        Err(error) => error!("Could not save replay to {}: {error}", path.display()),
+    }
+}
+
+fn start_playback(world: &mut World) {
+    let Some(PendingReplay(replay)) = world.remove_resource::<PendingReplay>() else {
+        return;
+    };
+    match Playback::begin(world, replay) {
+        Ok(()) => world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::InGame),
+        Err(error) => error!("Could not play back the replay: {error}"),
+    }
+}
+
+fn play_tick(
+    mut playback: ResMut<Playback>,
+    mut actions: ResMut<FixedInput<Action>>,
+    mut move_input: ResMut<MoveInput>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+) {
+    let Ok(mut transform) = player_query.single_mut() else {
+        return;
+    };
+    let Some(input) = playback.next() else {
+        return;
+    };
+    input.apply(&mut actions, &mut move_input);
+    transform.rotation = input.aim_rotation();
+}
+
+fn record_tick(
+    mut recorder: ResMut<Recorder>,
+    actions: Res<FixedInput<Action>>,
+    move_input: Res<MoveInput>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+) {
+    let Ok(mut transform) = player_query.single_mut() else {
+        return;
+    };
+    let Some(replay) = recorder.replay.as_mut() else {
+        return;
+    };
+    let input = TickInput::capture(&actions, *move_input, transform.rotation);
+    // Aim exactly as the replay will, so both shoot the same way down to the last bit.
+    transform.rotation = input.aim_rotation();
+    replay.push(input);
+}
+
+fn hash_recorded_tick(world: &mut World) {
+    let recorder = world.resource::<Recorder>();
+    let Some(ticks) = recorder.replay.as_ref().map(|replay| replay.ticks) else {
+        return;
+    };
+    if recorder.hashed_ticks == ticks {
+        return;
+    }
+
+    let hash = state_hash(world);
+    let mut recorder = world.resource_mut::<Recorder>();
+    recorder.hashed_ticks = ticks;
+    if let Some(replay) = recorder.replay.as_mut() {
+        replay.final_hash = hash;
+    }
+}
+
+fn finish_playback(world: &mut World) {
+    if !world.resource::<Playback>().is_finished() {
+        return;
+    }
+    let hash = state_hash(world);
+    report_playback(world, hash);
+}
+
+/// The run ended before the replay did, so it can't have matched.
+fn end_playback_early(world: &mut World) {
+    let hash = state_hash(world);
+    report_playback(world, hash);
+}
+
+fn report_playback(world: &mut World, hash: u64) {
+    let Some(playback) = world.remove_resource::<Playback>() else {
+        return;
+    };
+    let result = playback.result(hash);
+    if result.matched() {
+        info!(
+            "Replay finished after {} ticks in the recorded state {:016x}.",
+            result.ticks_played, result.hash
+        );
+    } else {
+        warn!(
+            "Replay diverged: played {} of {} ticks and ended in state {:016x}, recorded {:016x}.",
+            result.ticks_played, result.ticks_recorded, result.hash, result.recorded_hash
+        );
+    }
+    world.insert_resource(result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(held: &[Action], pressed: &[Action], released: &[Action]) -> ButtonInput<Action> {
+        let mut input = ButtonInput::default();
+        for &action in released {
+            input.press(action);
+            input.release(action);
+        }
+        for &action in held {
+            input.press(action);
+        }
+        for &action in held {
+            if !pressed.contains(&action) {
+                input.clear_just_pressed(action);
+            }
+        }
+        input
+    }
+
+    #[test]
+    fn tick_input_rebuilds_the_action_state() {
+        let actions = input(
+            &[Action::MoveUp, Action::Fire, Action::Slot(9)],
+            &[Action::Fire, Action::Dodge],
+            &[Action::Dodge, Action::MoveLeft],
+        );
+        let movement = MoveInput(Vec2::new(0.3, -0.8));
+        let aim = Quat::from_rotation_z(2.5);
+
+        let recorded = TickInput::capture(&actions, movement, aim);
+        let mut played = ButtonInput::default();
+        let mut played_movement = MoveInput::default();
+        recorded.apply(&mut played, &mut played_movement);
+
+        for action in Action::ALL {
+            assert_eq!(
+                (
+                    played.pressed(action),
+                    played.just_pressed(action),
+                    played.just_released(action)
+                ),
+                (
+                    actions.pressed(action),
+                    actions.just_pressed(action),
+                    actions.just_released(action)
+                ),
+                "{action:?}"
+            );
+        }
+        assert_eq!(played_movement, movement);
+        assert_eq!(
+            TickInput::capture(&played, played_movement, recorded.aim_rotation()),
+            recorded
+        );
+    }
+
+    #[test]
+    fn replay_files_store_repeated_ticks_once_and_load_back() {
+        let idle = TickInput::capture(
+            &ButtonInput::default(),
+            MoveInput::default(),
+            Quat::IDENTITY,
+        );
+        let walking = TickInput::capture(
+            &input(&[Action::MoveRight], &[], &[]),
+            MoveInput(Vec2::X),
+            Quat::IDENTITY,
+        );
+        let mut replay = Replay::new(
+            42,
+            ArenaMode::Room,
+            Some("rifle".to_string()),
+            &Archetypes::shipped(),
+        );
+        for tick in [idle, idle, idle, walking, walking, idle] {
+            replay.push(tick);
+        }
+        assert_eq!(replay.ticks, 6);
+        assert_eq!(replay.inputs, vec![(3, idle), (2, walking), (1, idle)]);
+
+        let path = std::env::temp_dir().join(format!("replay-{}.ron", std::process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), replay);
+    }
+
+    #[test]
+    fn playback_refuses_a_replay_recorded_with_other_archetypes() {
+        let mut world = World::new();
+        let mut archetypes = Archetypes::shipped();
+        let replay = Replay::new(7, ArenaMode::Cave, None, &archetypes);
+        archetypes.player.speed += 1.0;
+        world.insert_resource(archetypes);
+
+        let error = Playback::begin(&mut world, replay.clone()).unwrap_err();
+        assert!(matches!(error, ReplayError::Archetypes { .. }), "{error}");
+        assert!(!world.contains_resource::<Playback>());
+
+        world.insert_resource(Archetypes::shipped());
+        Playback::begin(&mut world, replay).unwrap();
+        assert!(world.contains_resource::<Playback>());
+    }
+}
@@ -0,0 +1,144 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::cli;
+
+const SEED_ARG: &str = "--seed";
+const SEED_ENV_VAR: &str = "RUSTY_GUNGEON_SEED";
+const STREAM_SALT_MULTIPLIER: u64 = 0x9E37_79B9_7F4A_7C15;
+const FLOOR_SALT_MULTIPLIER: u64 = 0xC2B2_AE3D_27D4_EB4F;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedSource {
+    CommandLine,
+    Environment,
+    /// Typed into the main menu.
+    Menu,
+    /// Taken from a replay file being played back.
+    Replay,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngStream {
+    ArenaLayout,
+    EnemySpawns,
+    EnemyBehaviour,
+    Weapons,
+    Loot,
+}
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct RunSeed {
+    pub value: u64,
+    pub source: SeedSource,
+}
+
+impl RunSeed {
+    pub fn from_args_or_env() -> Self {
+        if let Some(raw) = cli::arg_value(SEED_ARG) {
+            match raw.trim().parse() {
+                Ok(value) => {
+                    return Self {
+                        value,
+                        source: SeedSource::CommandLine,
+                    };
+                }
+                Err(_) => warn!("Ignoring invalid {SEED_ARG} value '{raw}'."),
+            }
+        }
+
+        if let Ok(raw) = std::env::var(SEED_ENV_VAR) {
+            match raw.trim().parse() {
+                Ok(value) => {
+                    return Self {
+                        value,
+                        source: SeedSource::Environment,
+                    };
+                }
+                Err(_) => warn!("Ignoring invalid {SEED_ENV_VAR} value '{raw}'."),
+            }
+        }
+
+        Self::random()
+    }
+
+    pub fn random() -> Self {
+        Self {
+            value: rand::rng().random(),
+            source: SeedSource::Random,
+        }
+    }
+
+    /// Applies the main menu's seed field. An empty field means a random seed; a chosen seed
+    /// that's already in use keeps its source.
+    pub fn apply_entry(&mut self, entry: &str) {
+        match entry.trim().parse() {
+            Ok(value) if value == self.value && self.source != SeedSource::Random => {}
+            Ok(value) => {
+                *self = Self {
+                    value,
+                    source: SeedSource::Menu,
+                }
+            }
+            Err(_) if self.source != SeedSource::Random => *self = Self::random(),
+            Err(_) => {}
+        }
+    }
+
+    /// The generator for one stream on one floor. Each floor after the first mixes in its number,
+    /// so every floor of a run is different but replays the same from the same seed.
+    pub fn rng(&self, stream: RngStream, floor: u32) -> StdRng {
+        let salt = (stream as u64 + 1).wrapping_mul(STREAM_SALT_MULTIPLIER);
+        let floor_salt = u64::from(floor.saturating_sub(1)).wrapping_mul(FLOOR_SALT_MULTIPLIER);
+        StdRng::seed_from_u64(self.value ^ salt ^ floor_salt)
+    }
+
+    pub fn reroll(&mut self) {
+        if self.source == SeedSource::Random {
+            *self = Self::random();
+            info!("Run seed: {}", self.value);
+        }
+    }
+}
+
+pub struct SeedPlugin;
+
+impl Plugin for SeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RunSeed::from_args_or_env())
+            .add_systems(Startup, log_run_seed);
+    }
+}
+
+fn log_run_seed(run_seed: Res<RunSeed>) {
+    info!("Run seed: {} ({:?})", run_seed.value, run_seed.source);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_entry_picks_or_clears_the_seed() {
+        let mut seed = RunSeed {
+            value: 7,
+            source: SeedSource::CommandLine,
+        };
+        seed.apply_entry("7");
+        assert_eq!((seed.value, seed.source), (7, SeedSource::CommandLine));
+
+        seed.apply_entry("1234");
+        assert_eq!((seed.value, seed.source), (1234, SeedSource::Menu));
+
+        seed.apply_entry("");
+        assert_eq!(seed.source, SeedSource::Random);
+        let random = seed.value;
+        seed.apply_entry("");
+        assert_eq!(seed.value, random, "an untouched random seed stays put");
+
+        seed.apply_entry("1234");
+        assert_eq!((seed.value, seed.source), (1234, SeedSource::Menu));
+    }
+}
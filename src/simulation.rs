@@ -0,0 +1,261 @@
+use std::hash::Hash;
+use std::ops::{Deref, DerefMut};
+
+use bevy::app::RunFixedMainLoopSystem;
+use bevy::ecs::component::HookContext;
+use bevy::ecs::schedule::ExecutorKind;
+use bevy::ecs::world::DeferredWorld;
+use bevy::prelude::*;
+
+use crate::action::Action;
+
+/// Gameplay steps per second, whatever the frame rate.
+pub const SIMULATION_HZ: f64 = 60.0;
+
+/// A moving entity whose [`Transform`] is drawn between its last two simulated positions, so
+/// motion stays smooth when frames and fixed steps don't line up.
+///
+/// Gameplay only ever sees the simulated translation: it's put back before each batch of fixed
+/// steps and the blended one is only in the `Transform` while the frame is drawn. Moving the
+/// entity outside the simulation, like placing the player on a new floor, snaps it there.
+#[derive(Component, Debug, Clone, Copy, Default)]
+#[require(Transform)]
+#[component(on_add = snap_to_transform)]
+pub struct Interpolated {
+    previous: Vec3,
+    current: Vec3,
+    rendered: Vec3,
+}
+
+impl Interpolated {
+    fn snap(&mut self, translation: Vec3) {
+        self.previous = translation;
+        self.current = translation;
+        self.rendered = translation;
+    }
+
+    /// Where the simulation has the entity, given its current `transform`.
+    pub fn simulated_translation(&self, transform: &Transform) -> Vec3 {
+        if transform.translation == self.rendered {
+            self.current
+        } else {
+            transform.translation
+        }
+    }
+}
+
+fn snap_to_transform(mut world: DeferredWorld, context: HookContext) {
+    let Some(translation) = world
+        .get::<Transform>(context.entity)
+        .map(|transform| transform.translation)
+    else {
+        return;
+    };
+    if let Some(mut interpolated) = world.get_mut::<Interpolated>(context.entity) {
+        interpolated.snap(translation);
+    }
+}
+
+/// [`ButtonInput`] as seen from [`FixedUpdate`], which reads [`Action`]s through
+/// `FixedInput<Action>`. Presses and releases are held until a fixed
+/// step has seen them, so a frame that runs no steps doesn't lose a tap and a frame that runs
+/// several doesn't repeat it.
+#[derive(Resource, Debug)]
+pub struct FixedInput<T: Copy + Eq + Hash + Send + Sync + 'static>(ButtonInput<T>);
+
+impl<T: Copy + Eq + Hash + Send + Sync + 'static> Default for FixedInput<T> {
+    fn default() -> Self {
+        Self(ButtonInput::default())
+    }
+}
+
+impl<T: Copy + Eq + Hash + Send + Sync + 'static> Deref for FixedInput<T> {
+    type Target = ButtonInput<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Copy + Eq + Hash + Send + Sync + 'static> DerefMut for FixedInput<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        // Systems without an explicit order could otherwise run in a different order from one
+        // step to the next, which would make replays drift.
+        app.edit_schedule(FixedUpdate, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        });
+
+        app.insert_resource(Time::<Fixed>::from_hz(SIMULATION_HZ))
+            .init_resource::<FixedInput<Action>>()
+            .add_systems(
+                RunFixedMainLoop,
+                (latch_input::<Action>, restore_simulated_translation)
+                    .in_set(RunFixedMainLoopSystem::BeforeFixedMainLoop),
+            )
+            .add_systems(FixedFirst, start_step)
+            .add_systems(FixedLast, (end_step, clear_input::<Action>))
+            .add_systems(
+                RunFixedMainLoop,
+                interpolate_translation.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
+            );
+    }
+}
+
+fn latch_input<T: Copy + Eq + Hash + Send + Sync + 'static>(
+    input: Res<ButtonInput<T>>,
+    mut fixed_input: ResMut<FixedInput<T>>,
+) {
+    for &button in input.get_just_pressed() {
+        fixed_input.press(button);
+    }
+    for &button in input.get_just_released() {
+        fixed_input.release(button);
+    }
+}
+
+fn clear_input<T: Copy + Eq + Hash + Send + Sync + 'static>(
+    mut fixed_input: ResMut<FixedInput<T>>,
+) {
+    fixed_input.clear();
+}
+
+fn restore_simulated_translation(mut query: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        if transform.translation == interpolated.rendered {
+            transform.translation = interpolated.current;
+        } else {
+            interpolated.snap(transform.translation);
+        }
+    }
+}
+
+fn start_step(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.previous = transform.translation;
+    }
+}
+
+fn end_step(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.current = transform.translation;
+    }
+}
+
+fn interpolate_translation(
+    mut query: Query<(&mut Transform, &mut Interpolated)>,
+    time: Res<Time<Fixed>>,
+) {
+    let blend = time.overstep_fraction();
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        let translation = interpolated.previous.lerp(interpolated.current, blend);
+        transform.translation = translation;
+        interpolated.rendered = translation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    const STEP_DISTANCE: f32 = 10.0;
+
+    fn app(frame: Duration) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<ButtonInput<Action>>()
+            .add_plugins(SimulationPlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(frame))
+            .add_systems(
+                FixedUpdate,
+                |mut query: Query<&mut Transform, With<Interpolated>>| {
+                    for mut transform in query.iter_mut() {
+                        transform.translation.x += STEP_DISTANCE;
+                    }
+                },
+            );
+        app
+    }
+
+    fn step() -> Duration {
+        Time::<Fixed>::from_hz(SIMULATION_HZ).timestep()
+    }
+
+    #[test]
+    fn drawn_position_blends_between_steps() {
+        let mut app = app(step() / 4);
+        let entity = app.world_mut().spawn(Interpolated::default()).id();
+
+        let mut drawn = Vec::new();
+        for _ in 0..12 {
+            app.update();
+            let transform = app.world().get::<Transform>(entity).unwrap();
+            let interpolated = app.world().get::<Interpolated>(entity).unwrap();
+            drawn.push(transform.translation.x);
+            assert_eq!(
+                interpolated.simulated_translation(transform).x % STEP_DISTANCE,
+                0.0
+            );
+        }
+        assert!(drawn.windows(2).all(|pair| pair[0] <= pair[1]), "{drawn:?}");
+        assert!(
+            drawn.iter().any(|x| x % STEP_DISTANCE != 0.0),
+            "some frames should land between steps: {drawn:?}"
+        );
+    }
+
+    #[test]
+    fn moving_an_entity_outside_the_simulation_snaps_it() {
+        let mut app = app(step() / 2);
+        let entity = app.world_mut().spawn(Interpolated::default()).id();
+        app.update();
+        app.update();
+
+        app.world_mut()
+            .get_mut::<Transform>(entity)
+            .unwrap()
+            .translation = Vec3::new(500.0, 0.0, 0.0);
+        app.update();
+        app.update();
+        let transform = app.world().get::<Transform>(entity).unwrap();
+        assert!(transform.translation.x >= 500.0);
+        assert!(transform.translation.x <= 500.0 + STEP_DISTANCE);
+    }
+
+    #[test]
+    fn taps_reach_exactly_one_step() {
+        for frame in [step() / 4, step() * 3] {
+            let mut app = app(frame);
+            app.init_resource::<Taps>().add_systems(
+                FixedUpdate,
+                |input: Res<FixedInput<Action>>, mut taps: ResMut<Taps>| {
+                    taps.0 += input.just_pressed(Action::Dodge) as u32;
+                },
+            );
+            let mut input = app.world_mut().resource_mut::<ButtonInput<Action>>();
+            input.press(Action::Dodge);
+            input.release(Action::Dodge);
+            for _ in 0..8 {
+                app.update();
+                app.world_mut()
+                    .resource_mut::<ButtonInput<Action>>()
+                    .clear();
+            }
+            assert_eq!(app.world().resource::<Taps>().0, 1, "frame {frame:?}");
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct Taps(u32);
+}
@@ -0,0 +1,248 @@
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+
+use crate::arena::{ArenaGrid, TILE_SIZE, TileType};
+use crate::collision::tile_to_world;
+use crate::floor::FloorEntity;
+
+/// Tiles along each side of a chunk. Changing a tile only rebuilds the mesh of the chunk it's in.
+const CHUNK_SIZE_TILES: usize = 16;
+/// Behind everything standing on the floor.
+const TILEMAP_Z: f32 = -1.0;
+
+const WALL_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+const FLOOR_COLOR: Color = Color::srgb(0.15, 0.15, 0.18);
+const DOOR_COLOR: Color = Color::srgb(0.45, 0.3, 0.15);
+const LOCKED_DOOR_COLOR: Color = Color::srgb(0.7, 0.15, 0.15);
+
+fn tile_color(tile: TileType) -> Color {
+    match tile {
+        TileType::Floor => FLOOR_COLOR,
+        TileType::Wall => WALL_COLOR,
+        TileType::Door => DOOR_COLOR,
+        TileType::LockedDoor => LOCKED_DOOR_COLOR,
+    }
+}
+
+/// A square of up to [`CHUNK_SIZE_TILES`] by [`CHUNK_SIZE_TILES`] tiles of the [`ArenaGrid`],
+/// drawn as one mesh.
+#[derive(Component, Debug)]
+#[require(FloorEntity)]
+pub struct TileChunk {
+    /// The chunk's bottom-left tile.
+    origin: (usize, usize),
+    /// The tiles its mesh was built from, row by row.
+    tiles: Vec<TileType>,
+}
+
+/// One white material shared by every chunk; the tile colours are in the meshes.
+#[derive(Resource)]
+struct TilemapMaterial(Handle<ColorMaterial>);
+
+impl FromWorld for TilemapMaterial {
+    fn from_world(world: &mut World) -> Self {
+        Self(
+            world
+                .resource_mut::<Assets<ColorMaterial>>()
+                .add(ColorMaterial::default()),
+        )
+    }
+}
+
+/// Draws the [`ArenaGrid`] as chunk meshes, rebuilding a chunk whenever one of its tiles changes.
+pub struct TilemapPlugin;
+
+impl Plugin for TilemapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TilemapMaterial>().add_systems(
+            PostUpdate,
+            sync_tile_chunks_system.run_if(resource_exists_and_changed::<ArenaGrid>),
+        );
+    }
+}
+
+fn chunk_origins(arena_grid: &ArenaGrid) -> impl Iterator<Item = (usize, usize)> {
+    (0..arena_grid.height)
+        .step_by(CHUNK_SIZE_TILES)
+        .flat_map(|y| {
+            (0..arena_grid.width)
+                .step_by(CHUNK_SIZE_TILES)
+                .map(move |x| (x, y))
+        })
+}
+
+/// The tiles in the chunk at `origin`, cut short at the edges of the grid.
+fn chunk_tiles(arena_grid: &ArenaGrid, (origin_x, origin_y): (usize, usize)) -> Vec<TileType> {
+    let end_x = (origin_x + CHUNK_SIZE_TILES).min(arena_grid.width);
+    let end_y = (origin_y + CHUNK_SIZE_TILES).min(arena_grid.height);
+    arena_grid.grid[origin_y..end_y]
+        .iter()
+        .flat_map(|row| row[origin_x..end_x].iter().copied())
+        .collect()
+}
+
+/// One coloured quad per tile, relative to the centre of the chunk's bottom-left tile.
+fn chunk_mesh(arena_grid: &ArenaGrid, (origin_x, origin_y): (usize, usize)) -> Mesh {
+    let end_x = (origin_x + CHUNK_SIZE_TILES).min(arena_grid.width);
+    let end_y = (origin_y + CHUNK_SIZE_TILES).min(arena_grid.height);
+    let tile_count = (end_x - origin_x) * (end_y - origin_y);
+    let half = TILE_SIZE / 2.0;
+
+    let mut positions = Vec::with_capacity(tile_count * 4);
+    let mut colors = Vec::with_capacity(tile_count * 4);
+    let mut indices = Vec::with_capacity(tile_count * 6);
+    for y in origin_y..end_y {
+        for x in origin_x..end_x {
+            let center = Vec2::new((x - origin_x) as f32, (y - origin_y) as f32) * TILE_SIZE;
+            let color = LinearRgba::from(tile_color(arena_grid.grid[y][x])).to_f32_array();
+            let first = positions.len() as u32;
+            for corner in [
+                Vec2::new(-half, -half),
+                Vec2::new(half, -half),
+                Vec2::new(half, half),
+                Vec2::new(-half, half),
+            ] {
+                positions.push((center + corner).extend(0.0).to_array());
+                colors.push(color);
+            }
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+    }
+
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+/// Spawns a fresh set of chunks for a new floor, or rebuilds the meshes of the chunks whose
+/// tiles changed, like doors locking and unlocking.
+fn sync_tile_chunks_system(
+    mut commands: Commands,
+    mut chunk_query: Query<(Entity, &mut TileChunk, &Mesh2d)>,
+    arena_grid: Res<ArenaGrid>,
+    material: Res<TilemapMaterial>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let origins: Vec<(usize, usize)> = chunk_origins(&arena_grid).collect();
+    let layout_matches = chunk_query.iter().count() == origins.len()
+        && chunk_query.iter().all(|(_, chunk, _)| {
+            origins.contains(&chunk.origin)
+                && chunk.tiles.len() == chunk_tiles(&arena_grid, chunk.origin).len()
+        });
+
+    if layout_matches {
+        for (_, mut chunk, mesh) in chunk_query.iter_mut() {
+            let tiles = chunk_tiles(&arena_grid, chunk.origin);
+            if chunk.tiles != tiles {
+                meshes.insert(mesh.id(), chunk_mesh(&arena_grid, chunk.origin));
+                chunk.tiles = tiles;
+            }
+        }
+        return;
+    }
+
+    for (entity, _, _) in chunk_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    for origin in origins {
+        let position = tile_to_world(&arena_grid, origin.0, origin.1);
+        commands.spawn((
+            TileChunk {
+                origin,
+                tiles: chunk_tiles(&arena_grid, origin),
+            },
+            Mesh2d(meshes.add(chunk_mesh(&arena_grid, origin))),
+            MeshMaterial2d(material.0.clone()),
+            Transform::from_translation(position.extend(TILEMAP_Z)),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Rebuilt(usize);
+
+    fn arena_grid(width: usize, height: usize) -> ArenaGrid {
+        let grid = vec![vec![TileType::Floor; width]; height];
+        ArenaGrid::from_tiles(grid)
+    }
+
+    fn app(arena_grid: ArenaGrid) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .add_plugins(TilemapPlugin)
+            .init_resource::<Rebuilt>()
+            .insert_resource(arena_grid)
+            .add_systems(
+                Last,
+                |chunk_query: Query<(), Changed<TileChunk>>, mut rebuilt: ResMut<Rebuilt>| {
+                    rebuilt.0 = chunk_query.iter().count();
+                },
+            );
+        app
+    }
+
+    fn vertex_count(app: &mut App) -> usize {
+        let handles: Vec<Handle<Mesh>> = app
+            .world_mut()
+            .query::<&Mesh2d>()
+            .iter(app.world())
+            .map(|mesh| mesh.0.clone())
+            .collect();
+        let meshes = app.world().resource::<Assets<Mesh>>();
+        handles
+            .iter()
+            .map(|handle| meshes.get(handle).unwrap().count_vertices())
+            .sum()
+    }
+
+    #[test]
+    fn chunks_cover_the_grid_and_only_changed_ones_rebuild() {
+        let mut app = app(arena_grid(40, 20));
+        app.update();
+        assert_eq!(app.world().resource::<Rebuilt>().0, 6);
+        assert_eq!(vertex_count(&mut app), 40 * 20 * 4);
+
+        app.world_mut().resource_mut::<ArenaGrid>().grid[5][20] = TileType::LockedDoor;
+        app.update();
+        assert_eq!(app.world().resource::<Rebuilt>().0, 1);
+
+        app.world_mut().resource_mut::<ArenaGrid>().grid[5][20] = TileType::LockedDoor;
+        app.update();
+        assert_eq!(
+            app.world().resource::<Rebuilt>().0,
+            0,
+            "nothing actually changed"
+        );
+    }
+
+    #[test]
+    fn a_new_floor_of_another_size_replaces_the_chunks() {
+        let mut app = app(arena_grid(40, 20));
+        app.update();
+
+        app.insert_resource(arena_grid(20, 10));
+        app.update();
+        app.update();
+        assert_eq!(
+            app.world_mut()
+                .query::<&TileChunk>()
+                .iter(app.world())
+                .count(),
+            2
+        );
+        assert_eq!(vertex_count(&mut app), 20 * 10 * 4);
+    }
+}
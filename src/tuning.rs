@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::input::common_conditions::input_just_pressed;
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::archetype::Archetypes;
+use crate::combat::{ContactDamage, Invulnerability};
+use crate::dodge::DodgeRoll;
+use crate::enemy::{Enemy, EnemyKind};
+use crate::enemy_weapon::EnemyWeapon;
+use crate::floor::rebuild_floor;
+use crate::inventory::{Inventory, WeaponPickup};
+use crate::player::{Health, Player, Speed};
+use crate::weapon::Weapon;
+
+const ASSET_ROOT: &str = "assets";
+const WATCHED_ASSET_DIR: &str = "archetypes";
+const WATCH_POLL_SECONDS: f32 = 0.5;
+const REGENERATE_ARENA_KEY: KeyCode = KeyCode::F5;
+
+/// Polls the tuning files for changes and asks the asset server to reload them. Bevy's own
+/// watcher needs the `file_watcher` feature; a handful of timestamps is cheap to check.
+#[derive(Resource)]
+struct TuningWatcher {
+    directory: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    poll: Timer,
+}
+
+impl TuningWatcher {
+    fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            modified: HashMap::new(),
+            poll: Timer::from_seconds(WATCH_POLL_SECONDS, TimerMode::Repeating),
+        }
+    }
+
+    /// Asset paths of the files modified since the last scan. The first scan only records
+    /// timestamps.
+    fn changed_files(&mut self) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(&self.directory) else {
+            return Vec::new();
+        };
+
+        let mut changed = Vec::new();
+        for entry in entries.flatten() {
+            let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+            let asset_path = PathBuf::from(WATCHED_ASSET_DIR).join(entry.file_name());
+            if self
+                .modified
+                .insert(asset_path.clone(), modified)
+                .is_some_and(|previous| previous != modified)
+            {
+                changed.push(asset_path);
+            }
+        }
+        changed
+    }
+}
+
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        let directory = FileAssetReader::get_base_path()
+            .join(ASSET_ROOT)
+            .join(WATCHED_ASSET_DIR);
+
+        app.insert_resource(TuningWatcher::new(directory))
+            .add_systems(
+                Update,
+                watch_tuning_files.run_if(resource_exists::<Archetypes>),
+            )
+            .add_systems(
+                Update,
+                (retune_player, retune_weapon_pickups, retune_enemies)
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(resource_exists_and_changed::<Archetypes>),
+            )
+            .add_systems(
+                Update,
+                regenerate_arena
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(input_just_pressed(REGENERATE_ARENA_KEY)),
+            );
+    }
+}
+
+fn watch_tuning_files(
+    mut watcher: ResMut<TuningWatcher>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+) {
+    if !watcher.poll.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    for asset_path in watcher.changed_files() {
+        info!("{} changed, reloading.", asset_path.display());
+        asset_server.reload(asset_path);
+    }
+}
+
+/// Keeps the same share of health when the maximum changes.
+fn rescale_health(health: &mut Health, max: f32) {
+    let fraction = if health.max > 0.0 {
+        health.current / health.max
+    } else {
+        1.0
+    };
+    health.max = max;
+    health.current = max * fraction;
+}
+
+fn retune_weapon(archetypes: &Archetypes, weapon: &mut Weapon) {
+    if let Some(stats) = archetypes.weapon_named(&weapon.stats.name) {
+        weapon.retune(stats.clone());
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn retune_player(
+    archetypes: Res<Archetypes>,
+    mut player_query: Query<
+        (
+            &mut Health,
+            &mut Speed,
+            &mut Sprite,
+            &mut Invulnerability,
+            &mut Inventory,
+            &mut DodgeRoll,
+        ),
+        With<Player>,
+    >,
+) {
+    let archetype = &archetypes.player;
+    for (mut health, mut speed, mut sprite, mut invulnerability, mut inventory, mut dodge) in
+        player_query.iter_mut()
+    {
+        rescale_health(&mut health, archetype.health);
+        speed.0 = archetype.speed;
+        sprite.color = archetype.color.into();
+        sprite.custom_size = Some(Vec2::splat(archetype.size));
+        invulnerability
+            .timer
+            .set_duration(Duration::from_secs_f32(archetype.invulnerability_seconds));
+        for weapon in inventory.weapons_mut() {
+            retune_weapon(&archetypes, weapon);
+        }
+        dodge.stats = archetype.dodge.clone();
+    }
+}
+
+fn retune_weapon_pickups(archetypes: Res<Archetypes>, mut pickup_query: Query<&mut WeaponPickup>) {
+    for mut pickup in pickup_query.iter_mut() {
+        retune_weapon(&archetypes, &mut pickup.weapon);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn retune_enemies(
+    mut commands: Commands,
+    archetypes: Res<Archetypes>,
+    mut enemy_query: Query<
+        (
+            Entity,
+            &EnemyKind,
+            &mut Health,
+            &mut Speed,
+            &mut ContactDamage,
+            &mut Sprite,
+            Option<&mut EnemyWeapon>,
+        ),
+        With<Enemy>,
+    >,
+) {
+    for (entity, kind, mut health, mut speed, mut contact_damage, mut sprite, weapon) in
+        enemy_query.iter_mut()
+    {
+        let Some(archetype) = archetypes.enemies.enemies.get(&kind.0) else {
+            continue;
+        };
+        rescale_health(&mut health, archetype.health);
+        speed.0 = archetype.speed;
+        contact_damage.0 = archetype.contact_damage;
+        sprite.color = archetype.color.into();
+        sprite.custom_size = Some(Vec2::splat(archetype.size));
+
+        match (weapon, &archetype.weapon) {
+            (Some(mut weapon), Some(stats)) => {
+                let elapsed = weapon.cooldown.elapsed();
+                *weapon = EnemyWeapon::new(stats);
+                weapon.cooldown.set_elapsed(elapsed);
+            }
+            (None, Some(stats)) => {
+                commands.entity(entity).insert(EnemyWeapon::new(stats));
+            }
+            (Some(_), None) => {
+                commands.entity(entity).remove::<EnemyWeapon>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// Rebuilds the current floor from the same seed, so arena tuning changes can be compared
+/// side by side.
+fn regenerate_arena(world: &mut World) {
+    rebuild_floor(world);
+    info!("Regenerated the arena with the current tuning.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weapon::Weapon;
+
+    fn retune_app(archetypes: Archetypes) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(archetypes)
+            .add_systems(Update, (retune_player, retune_enemies));
+        app
+    }
+
+    #[test]
+    fn live_entities_pick_up_new_stats() {
+        let mut archetypes = Archetypes::shipped();
+        let mut app = retune_app(archetypes.clone());
+
+        let mut pistol = Weapon::starter(archetypes.weapon("pistol").clone());
+        pistol.ammo_in_magazine = 2;
+        let player = app
+            .world_mut()
+            .spawn((
+                Player,
+                Health {
+                    current: 50.0,
+                    max: 100.0,
+                },
+                Speed(1.0),
+                Sprite::default(),
+                Invulnerability::new(1.0),
+                Inventory::new(pistol),
+                DodgeRoll::new(archetypes.player.dodge.clone()),
+            ))
+            .id();
+        let gunner = app
+            .world_mut()
+            .spawn((
+                Enemy,
+                EnemyKind("gunner".into()),
+                Health {
+                    current: 50.0,
+                    max: 50.0,
+                },
+                Speed(1.0),
+                ContactDamage(1.0),
+                Sprite::default(),
+            ))
+            .id();
+        app.update();
+
+        archetypes.player.health = 200.0;
+        archetypes.player.speed = 300.0;
+        archetypes
+            .weapons
+            .weapons
+            .get_mut("pistol")
+            .unwrap()
+            .projectile_damage = 99.0;
+        archetypes
+            .enemies
+            .enemies
+            .get_mut("gunner")
+            .unwrap()
+            .weapon
+            .as_mut()
+            .unwrap()
+            .range = 10.0;
+        app.insert_resource(archetypes);
+        app.update();
+
+        let world = app.world();
+        let health = world.get::<Health>(player).unwrap();
+        assert_eq!((health.current, health.max), (100.0, 200.0));
+        assert_eq!(world.get::<Speed>(player).unwrap().0, 300.0);
+        let weapon = world.get::<Inventory>(player).unwrap().active().clone();
+        assert_eq!(weapon.stats.projectile_damage, 99.0);
+        assert_eq!(weapon.ammo_in_magazine, 2);
+        assert!(weapon.has_infinite_ammo());
+        assert_eq!(world.get::<EnemyWeapon>(gunner).unwrap().range, 10.0);
+    }
+
+    #[test]
+    fn watcher_reports_only_files_changed_since_the_last_scan() {
+        let directory = std::env::temp_dir().join(format!("tuning-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let file = directory.join("test.weapons.ron");
+        std::fs::write(&file, "()").unwrap();
+
+        let mut watcher = TuningWatcher::new(directory.clone());
+        assert!(watcher.changed_files().is_empty());
+        assert!(watcher.changed_files().is_empty());
+
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(
+            watcher.changed_files(),
+            vec![PathBuf::from(WATCHED_ASSET_DIR).join("test.weapons.ron")]
+        );
+        assert!(watcher.changed_files().is_empty());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,450 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
+
+use crate::arena::setup_arena;
+use crate::cli;
+use crate::floor::{CurrentFloor, SetupFloor};
+use crate::seed::{RngStream, RunSeed};
+
+const WEAPON_ARG: &str = "--weapon";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum FireMode {
+    /// One shot per trigger pull.
+    SemiAuto,
+    /// Keeps firing at the fire rate while the trigger is held.
+    Automatic,
+    /// `shots` rounds `interval` seconds apart per trigger pull.
+    Burst { shots: u32, interval: f32 },
+    /// Fires on release once the trigger has been held for `seconds`.
+    Charge { seconds: f32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WeaponStats {
+    pub name: String,
+    pub fire_mode: FireMode,
+    /// Shots per second; for burst weapons, bursts per second.
+    pub fire_rate: f32,
+    pub magazine_size: u32,
+    pub reload_seconds: f32,
+    pub projectile_speed: f32,
+    pub projectile_damage: f32,
+    pub projectiles_per_shot: u32,
+    /// Each projectile leaves within half of this angle either side of the aim.
+    pub spread_degrees: f32,
+    /// How far each shot shoves the shooter back, in world units.
+    pub recoil: f32,
+    /// Spare rounds that come with the weapon when it is picked up.
+    pub reserve_ammo: u32,
+}
+
+impl WeaponStats {
+    fn shot_interval(&self) -> f32 {
+        1.0 / self.fire_rate.max(f32::EPSILON)
+    }
+}
+
+/// The trigger and reload buttons as seen by a weapon on one frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TriggerInput {
+    pub pressed: bool,
+    pub just_pressed: bool,
+    pub just_released: bool,
+    pub reload: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Weapon {
+    pub stats: WeaponStats,
+    pub ammo_in_magazine: u32,
+    /// Rounds left to reload from; `None` for a gun that never runs dry.
+    pub reserve_ammo: Option<u32>,
+    cooldown: f32,
+    reload_remaining: Option<f32>,
+    burst_remaining: u32,
+    burst_cooldown: f32,
+    charge: f32,
+}
+
+impl Weapon {
+    pub fn new(stats: WeaponStats) -> Self {
+        Self {
+            ammo_in_magazine: stats.magazine_size,
+            reserve_ammo: Some(stats.reserve_ammo),
+            stats,
+            cooldown: 0.0,
+            reload_remaining: None,
+            burst_remaining: 0,
+            burst_cooldown: 0.0,
+            charge: 0.0,
+        }
+    }
+
+    /// A weapon with infinite reserve ammo, like the gun every run starts with.
+    pub fn starter(stats: WeaponStats) -> Self {
+        Self {
+            reserve_ammo: None,
+            ..Self::new(stats)
+        }
+    }
+
+    pub fn has_infinite_ammo(&self) -> bool {
+        self.reserve_ammo.is_none()
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reload_remaining.is_some()
+    }
+
+    pub fn add_ammo(&mut self, rounds: u32) {
+        if let Some(reserve) = self.reserve_ammo.as_mut() {
+            *reserve += rounds;
+        }
+    }
+
+    pub fn start_reload(&mut self) {
+        if self.reload_remaining.is_none()
+            && self.ammo_in_magazine < self.stats.magazine_size
+            && self.reserve_ammo != Some(0)
+        {
+            self.reload_remaining = Some(self.stats.reload_seconds);
+            self.burst_remaining = 0;
+            self.charge = 0.0;
+        }
+    }
+
+    /// Swaps in new stats, keeping the ammo and reload progress that still fit them.
+    pub fn retune(&mut self, stats: WeaponStats) {
+        if stats.fire_mode != self.stats.fire_mode {
+            self.burst_remaining = 0;
+            self.charge = 0.0;
+        }
+        if let Some(remaining) = self.reload_remaining.as_mut() {
+            *remaining = remaining.min(stats.reload_seconds);
+        }
+        self.ammo_in_magazine = self.ammo_in_magazine.min(stats.magazine_size);
+        self.stats = stats;
+    }
+
+    /// Cancels anything in progress when the weapon is put away.
+    pub fn holster(&mut self) {
+        self.reload_remaining = None;
+        self.burst_remaining = 0;
+        self.charge = 0.0;
+    }
+
+    /// Advances the weapon by `delta` seconds and returns how many shots it fires this frame.
+    /// Each shot launches [`WeaponStats::projectiles_per_shot`] projectiles.
+    pub fn update(&mut self, trigger: TriggerInput, delta: f32) -> u32 {
+        self.cooldown -= delta;
+        self.burst_cooldown -= delta;
+
+        if let Some(remaining) = self.reload_remaining.as_mut() {
+            *remaining -= delta;
+            if *remaining > 0.0 {
+                self.cooldown = self.cooldown.max(0.0);
+                return 0;
+            }
+            self.reload_remaining = None;
+            let missing = self.stats.magazine_size - self.ammo_in_magazine;
+            let loaded = self
+                .reserve_ammo
+                .map_or(missing, |reserve| reserve.min(missing));
+            if let Some(reserve) = self.reserve_ammo.as_mut() {
+                *reserve -= loaded;
+            }
+            self.ammo_in_magazine += loaded;
+        }
+
+        if trigger.reload {
+            self.start_reload();
+            if self.is_reloading() {
+                return 0;
+            }
+        }
+
+        let interval = self.stats.shot_interval();
+        let mut shots = 0;
+
+        match self.stats.fire_mode {
+            FireMode::SemiAuto => {
+                if trigger.just_pressed && self.cooldown <= 0.0 && self.take_round() {
+                    shots += 1;
+                    self.cooldown = interval;
+                }
+            }
+            FireMode::Automatic => {
+                while trigger.pressed && self.cooldown <= 0.0 && self.take_round() {
+                    shots += 1;
+                    self.cooldown += interval;
+                }
+            }
+            FireMode::Burst {
+                shots: burst_shots,
+                interval: burst_interval,
+            } => {
+                if trigger.just_pressed && self.burst_remaining == 0 && self.cooldown <= 0.0 {
+                    self.burst_remaining = burst_shots;
+                    self.burst_cooldown = 0.0;
+                }
+                while self.burst_remaining > 0 && self.burst_cooldown <= 0.0 {
+                    if !self.take_round() {
+                        self.burst_remaining = 0;
+                        break;
+                    }
+                    shots += 1;
+                    self.burst_remaining -= 1;
+                    self.burst_cooldown += burst_interval;
+                }
+                if shots > 0 && self.burst_remaining == 0 {
+                    self.cooldown = interval;
+                }
+            }
+            FireMode::Charge { seconds } => {
+                if trigger.pressed && self.cooldown <= 0.0 && self.ammo_in_magazine > 0 {
+                    self.charge += delta;
+                }
+                if trigger.just_released {
+                    if self.charge >= seconds && self.take_round() {
+                        shots += 1;
+                        self.cooldown = interval;
+                    }
+                    self.charge = 0.0;
+                }
+            }
+        }
+
+        if !trigger.pressed || shots == 0 {
+            self.cooldown = self.cooldown.max(0.0);
+        }
+        if self.burst_remaining == 0 {
+            self.burst_cooldown = self.burst_cooldown.max(0.0);
+        }
+        if self.ammo_in_magazine == 0 && (trigger.pressed || trigger.just_released) {
+            self.start_reload();
+        }
+
+        shots
+    }
+
+    /// Directions for the projectiles of one shot aimed along `aim`.
+    pub fn shot_directions(&self, aim: Vec2, rng: &mut impl Rng) -> Vec<Vec2> {
+        let half_spread = self.stats.spread_degrees.to_radians() / 2.0;
+        (0..self.stats.projectiles_per_shot.max(1))
+            .map(|_| {
+                let angle = if half_spread > 0.0 {
+                    rng.random_range(-half_spread..=half_spread)
+                } else {
+                    0.0
+                };
+                Vec2::from_angle(angle).rotate(aim)
+            })
+            .collect()
+    }
+
+    fn take_round(&mut self) -> bool {
+        if self.ammo_in_magazine == 0 {
+            return false;
+        }
+        self.ammo_in_magazine -= 1;
+        true
+    }
+}
+
+/// Weapon id the player asked to start with through `--weapon`, checked once the weapon
+/// archetypes have loaded.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct StartingWeapon(pub Option<String>);
+
+impl StartingWeapon {
+    pub fn from_args() -> Self {
+        Self(cli::arg_value(WEAPON_ARG).map(|raw| raw.trim().to_ascii_lowercase()))
+    }
+}
+
+/// Seeded RNG for weapon spread so a run seed replays the same shots.
+#[derive(Resource)]
+pub struct WeaponRng(pub StdRng);
+
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(StartingWeapon::from_args())
+            .add_systems(SetupFloor, setup_weapon_rng.after(setup_arena));
+    }
+}
+
+fn setup_weapon_rng(mut commands: Commands, run_seed: Res<RunSeed>, floor: Res<CurrentFloor>) {
+    commands.insert_resource(WeaponRng(run_seed.rng(RngStream::Weapons, floor.0)));
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::archetype::Archetypes;
+
+    const FRAME: f32 = 1.0 / 60.0;
+
+    fn stats(id: &str) -> WeaponStats {
+        Archetypes::shipped().weapon(id).clone()
+    }
+
+    fn held() -> TriggerInput {
+        TriggerInput {
+            pressed: true,
+            ..default()
+        }
+    }
+
+    fn pulled() -> TriggerInput {
+        TriggerInput {
+            pressed: true,
+            just_pressed: true,
+            ..default()
+        }
+    }
+
+    fn released() -> TriggerInput {
+        TriggerInput {
+            just_released: true,
+            ..default()
+        }
+    }
+
+    fn frames(weapon: &mut Weapon, trigger: TriggerInput, seconds: f32) -> u32 {
+        (0..(seconds / FRAME).round() as usize)
+            .map(|_| weapon.update(trigger, FRAME))
+            .sum()
+    }
+
+    #[test]
+    fn semi_auto_fires_once_per_pull() {
+        let mut weapon = Weapon::new(stats("pistol"));
+        assert_eq!(weapon.update(pulled(), FRAME), 1);
+        assert_eq!(frames(&mut weapon, held(), 1.0), 0);
+
+        weapon.update(released(), FRAME);
+        assert_eq!(weapon.update(pulled(), FRAME), 1);
+        assert_eq!(weapon.ammo_in_magazine, 6);
+    }
+
+    #[test]
+    fn automatic_fires_at_its_fire_rate_while_held() {
+        let mut weapon = Weapon::new(stats("rifle"));
+        let fired = weapon.update(pulled(), FRAME) + frames(&mut weapon, held(), 1.0);
+        assert!(
+            (10..=11).contains(&fired),
+            "fired {fired} rounds in a second"
+        );
+    }
+
+    #[test]
+    fn burst_fires_its_rounds_then_waits() {
+        let mut weapon = Weapon::new(stats("burst"));
+        let fired = weapon.update(pulled(), FRAME) + frames(&mut weapon, held(), 0.3);
+        assert_eq!(fired, 3);
+
+        weapon.update(released(), FRAME);
+        assert_eq!(weapon.update(pulled(), FRAME), 0, "still cooling down");
+        frames(&mut weapon, TriggerInput::default(), 0.4);
+        assert_eq!(weapon.update(pulled(), FRAME), 1);
+    }
+
+    #[test]
+    fn charge_fires_on_release_only_when_full() {
+        let mut weapon = Weapon::new(stats("railgun"));
+        frames(&mut weapon, held(), 0.4);
+        assert_eq!(weapon.update(released(), FRAME), 0);
+
+        frames(&mut weapon, held(), 0.9);
+        assert_eq!(weapon.update(released(), FRAME), 1);
+        assert_eq!(weapon.update(released(), FRAME), 0);
+    }
+
+    #[test]
+    fn empty_magazine_reloads_before_firing_again() {
+        let mut weapon = Weapon::new(stats("shotgun"));
+        for _ in 0..6 {
+            frames(&mut weapon, TriggerInput::default(), 1.0);
+            assert_eq!(weapon.update(pulled(), FRAME), 1);
+        }
+        assert_eq!(weapon.ammo_in_magazine, 0);
+        assert!(weapon.is_reloading());
+
+        assert_eq!(frames(&mut weapon, pulled(), 1.9), 0);
+        frames(&mut weapon, TriggerInput::default(), 0.2);
+        assert!(!weapon.is_reloading());
+        assert_eq!(weapon.ammo_in_magazine, 6);
+        assert_eq!(weapon.update(pulled(), FRAME), 1);
+    }
+
+    #[test]
+    fn manual_reload_only_when_magazine_is_not_full() {
+        let mut weapon = Weapon::new(stats("pistol"));
+        let reload = TriggerInput {
+            reload: true,
+            ..default()
+        };
+        weapon.update(reload, FRAME);
+        assert!(!weapon.is_reloading());
+
+        weapon.update(pulled(), FRAME);
+        weapon.update(reload, FRAME);
+        assert!(weapon.is_reloading());
+    }
+
+    #[test]
+    fn reloads_draw_from_reserve_until_it_runs_out() {
+        let mut weapon = Weapon::new(WeaponStats {
+            reserve_ammo: 5,
+            ..stats("pistol")
+        });
+        let reload = TriggerInput {
+            reload: true,
+            ..default()
+        };
+
+        for _ in 0..8 {
+            weapon.update(released(), 1.0);
+            weapon.update(pulled(), FRAME);
+        }
+        weapon.update(reload, FRAME);
+        frames(&mut weapon, TriggerInput::default(), 1.0);
+        assert_eq!(weapon.ammo_in_magazine, 5);
+        assert_eq!(weapon.reserve_ammo, Some(0));
+
+        weapon.update(pulled(), FRAME);
+        weapon.update(reload, FRAME);
+        assert!(!weapon.is_reloading(), "nothing left to reload with");
+    }
+
+    #[test]
+    fn starter_weapons_never_run_dry() {
+        let mut weapon = Weapon::starter(stats("pistol"));
+        for _ in 0..100 {
+            weapon.update(released(), 1.0);
+            weapon.update(pulled(), FRAME);
+        }
+        assert!(weapon.has_infinite_ammo());
+        assert!(weapon.ammo_in_magazine > 0 || weapon.is_reloading());
+    }
+
+    #[test]
+    fn pellets_stay_inside_the_spread() {
+        let weapon = Weapon::new(stats("shotgun"));
+        let mut rng = StdRng::seed_from_u64(7);
+        let half_spread = stats("shotgun").spread_degrees.to_radians() / 2.0;
+
+        let directions = weapon.shot_directions(Vec2::Y, &mut rng);
+        assert_eq!(directions.len(), 6);
+        for direction in directions {
+            assert!(Vec2::Y.angle_to(direction).abs() <= half_spread + 1e-5);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rusty_gungeon::GameState;
+use rusty_gungeon::archetype::Archetypes;
+use rusty_gungeon::arena::{ArenaGrid, TILE_SIZE};
+use rusty_gungeon::enemy::Enemy;
+use rusty_gungeon::floor::{FloorEntity, Stairs};
+use rusty_gungeon::headless::HeadlessApp;
+use rusty_gungeon::player::{Faction, Health, Player, Projectile, ProjectileBundle};
+use rusty_gungeon::simulation::SIMULATION_HZ;
+
+const ARENA_WIDTH: usize = 15;
+const ARENA_HEIGHT: usize = 11;
+
+fn health(game: &HeadlessApp, entity: Entity) -> f32 {
+    game.world()
+        .get::<Health>(entity)
+        .expect("entity should have health")
+        .current
+}
+
+fn shoot_at_player(game: &mut HeadlessApp, from: Vec2, damage: f32) {
+    let target = game.player_position();
+    let world = game.world_mut();
+    let archetype = world
+        .resource::<Archetypes>()
+        .projectile(Faction::Enemy)
+        .clone();
+    world.spawn(ProjectileBundle::new(
+        &archetype,
+        Faction::Enemy,
+        from,
+        (target - from).normalize(),
+        200.0,
+        damage,
+    ));
+}
+
+#[test]
+fn player_walks_until_a_wall_stops_it() {
+    let mut game = HeadlessApp::builder().seed(1).build();
+    let start = game.use_open_arena(ARENA_WIDTH, ARENA_HEIGHT);
+
+    game.press_key(KeyCode::KeyD);
+    game.run_for(0.25);
+    let moving = game.player_position();
+    assert!(moving.x > start.x, "player should move right, got {moving}");
+    assert_eq!(moving.y, start.y);
+
+    game.run_for(3.0);
+    let stopped = game.player_position();
+    let wall_edge = start.x + (ARENA_WIDTH / 2) as f32 * TILE_SIZE - TILE_SIZE / 2.0;
+    assert!(stopped.x > moving.x);
+    assert!(
+        stopped.x < wall_edge,
+        "player went through the wall to {stopped}"
+    );
+
+    game.release_key(KeyCode::KeyD);
+    game.run_for(0.5);
+    assert_eq!(game.player_position(), stopped);
+}
+
+#[test]
+fn shots_kill_an_enemy_and_it_despawns() {
+    let mut game = HeadlessApp::builder()
+        .seed(2)
+        .starting_weapon("pistol")
+        .build();
+    let center = game.use_open_arena(ARENA_WIDTH, ARENA_HEIGHT);
+    let enemy = game.spawn_enemy("grunt", center + Vec2::new(TILE_SIZE * 4.0, 0.0));
+    let enemy_health = health(&game, enemy);
+
+    let mut shots = 0;
+    while game.world().get_entity(enemy).is_ok() {
+        assert!(shots < 30, "enemy survived {shots} shots");
+        let target = game.position(enemy);
+        game.aim_at(target);
+        game.press_mouse(MouseButton::Left);
+        game.step();
+        game.release_mouse(MouseButton::Left);
+        game.run_for(0.2);
+        shots += 1;
+
+        if shots == 1 && game.world().get_entity(enemy).is_ok() {
+            assert!(health(&game, enemy) < enemy_health, "first shot missed");
+        }
+    }
+
+    assert_eq!(game.count::<With<Enemy>>(), 0);
+    game.run_for(2.0);
+    assert_eq!(game.count::<With<Projectile>>(), 0);
+    assert_eq!(game.count::<With<Stairs>>(), 1);
+}
+
+#[test]
+fn enemy_fire_hurts_the_player() {
+    let mut game = HeadlessApp::builder().seed(3).build();
+    let center = game.use_open_arena(ARENA_WIDTH, ARENA_HEIGHT);
+    let player = game.player();
+    let player_health = health(&game, player);
+
+    shoot_at_player(&mut game, center + Vec2::new(TILE_SIZE * 3.0, 0.0), 15.0);
+    game.run_for(0.5);
+
+    assert_eq!(health(&game, player), player_health - 15.0);
+    assert_eq!(game.count::<With<Projectile>>(), 0);
+}
+
+#[test]
+fn dying_ends_the_run_and_clears_it() {
+    let mut game = HeadlessApp::builder().seed(4).build();
+    let center = game.use_open_arena(ARENA_WIDTH, ARENA_HEIGHT);
+    let player = game.player();
+    game.world_mut().get_mut::<Health>(player).unwrap().current = 5.0;
+
+    shoot_at_player(&mut game, center + Vec2::new(TILE_SIZE * 3.0, 0.0), 10.0);
+    game.run_for(0.5);
+
+    assert_eq!(game.state(), GameState::GameOver);
+    assert_eq!(game.count::<With<Player>>(), 0);
+    assert_eq!(game.count::<With<FloorEntity>>(), 0);
+}
+
+#[test]
+fn same_seed_builds_the_same_floor() {
+    let layout = |seed| {
+        let mut game = HeadlessApp::builder().seed(seed).build();
+        let grid = game.world().resource::<ArenaGrid>().grid.clone();
+        let mut enemies: Vec<(i32, i32)> = game
+            .world_mut()
+            .query_filtered::<&Transform, With<Enemy>>()
+            .iter(game.world())
+            .map(|transform| {
+                (
+                    transform.translation.x as i32,
+                    transform.translation.y as i32,
+                )
+            })
+            .collect();
+        enemies.sort();
+        (grid, enemies, game.player_position())
+    };
+
+    assert_eq!(layout(5), layout(5));
+    assert_ne!(layout(5).0, layout(6).0);
+}
+
+#[test]
+fn same_inputs_play_out_the_same_at_any_frame_rate() {
+    let play = |frame: Duration| {
+        let mut game = HeadlessApp::builder()
+            .seed(8)
+            .starting_weapon("pistol")
+            .timestep(frame)
+            .build();
+        let center = game.use_open_arena(ARENA_WIDTH, ARENA_HEIGHT);
+        let enemy = game.spawn_enemy("grunt", center + Vec2::new(TILE_SIZE * 5.0, 0.0));
+        let enemy_health = health(&game, enemy);
+
+        game.press_key(KeyCode::KeyW);
+        game.run_steps(20);
+        game.release_key(KeyCode::KeyW);
+        for _ in 0..3 {
+            let target = game.position(enemy);
+            game.aim_at(target);
+            game.press_mouse(MouseButton::Left);
+            game.run_steps(2);
+            game.release_mouse(MouseButton::Left);
+            game.run_steps(10);
+        }
+        game.run_steps(30);
+        assert!(health(&game, enemy) < enemy_health, "the shots should land");
+
+        let player = game.player();
+        (
+            game.player_position(),
+            health(&game, player),
+            game.position(enemy),
+            health(&game, enemy),
+            game.count::<With<Projectile>>(),
+        )
+    };
+
+    let step = Time::<Fixed>::from_hz(SIMULATION_HZ).timestep();
+    let at_30_fps = play(step * 2);
+    assert_eq!(play(step / 4), at_30_fps);
+    assert_eq!(play(step), at_30_fps);
+}
+
+#[test]
+fn gamepad_moves_and_shoots_with_aim_assist() {
+    let mut game = HeadlessApp::builder()
+        .seed(9)
+        .starting_weapon("pistol")
+        .build();
+    let center = game.use_open_arena(ARENA_WIDTH, ARENA_HEIGHT);
+    let gamepad = game.connect_gamepad();
+
+    game.set_left_stick(gamepad, Vec2::new(0.0, -0.5));
+    game.run_steps(10);
+    let half_speed = center.distance(game.player_position());
+    game.set_left_stick(gamepad, Vec2::new(0.0, -1.0));
+    game.run_steps(10);
+    let full_speed = center.distance(game.player_position()) - half_speed;
+    assert!(game.player_position().y < center.y);
+    assert!(
+        (full_speed - half_speed * 2.0).abs() < 1.0,
+        "half a push should move half as fast: {half_speed} then {full_speed}"
+    );
+    game.set_left_stick(gamepad, Vec2::ZERO);
+    game.step();
+
+    // The stick points a little off the enemy; aim assist closes most of the gap.
+    let player = game.player_position();
+    let enemy = game.spawn_enemy("grunt", player + Vec2::new(TILE_SIZE * 4.0, 0.0));
+    let enemy_health = health(&game, enemy);
+    game.set_right_stick(gamepad, Vec2::from_angle(0.15));
+    game.step();
+    let player_entity = game.player();
+    let aim = (game
+        .world()
+        .get::<Transform>(player_entity)
+        .unwrap()
+        .rotation
+        * Vec3::X)
+        .truncate();
+    assert!(
+        Vec2::X.angle_to(aim).abs() < 0.15,
+        "aim wasn't assisted: {aim}"
+    );
+
+    game.press_gamepad(gamepad, GamepadButton::RightTrigger2);
+    game.run_steps(2);
+    game.release_gamepad(gamepad, GamepadButton::RightTrigger2);
+    game.run_for(0.5);
+    assert!(health(&game, enemy) < enemy_health, "the shot should land");
+}
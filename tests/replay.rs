@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use rusty_gungeon::enemy::Enemy;
+use rusty_gungeon::headless::HeadlessApp;
+use rusty_gungeon::replay::Replay;
+use rusty_gungeon::simulation::SIMULATION_HZ;
+
+const SEED: u64 = 11;
+
+fn nearest_enemy(game: &mut HeadlessApp) -> Option<Vec2> {
+    let player = game.player_position();
+    let enemies: Vec<Entity> = game
+        .world_mut()
+        .query_filtered::<Entity, With<Enemy>>()
+        .iter(game.world())
+        .collect();
+    enemies
+        .into_iter()
+        .map(|enemy| game.position(enemy))
+        .min_by(|a, b| a.distance(player).total_cmp(&b.distance(player)))
+}
+
+/// Walks a square, shooting at the nearest enemy on every side, with a dodge and a blank thrown
+/// in.
+fn play_script(game: &mut HeadlessApp) {
+    for (side, key) in [KeyCode::KeyD, KeyCode::KeyW, KeyCode::KeyA, KeyCode::KeyS]
+        .into_iter()
+        .enumerate()
+    {
+        game.press_key(key);
+        game.run_steps(16);
+        if let Some(target) = nearest_enemy(game) {
+            game.aim_at(target);
+        }
+        game.press_mouse(MouseButton::Left);
+        game.run_steps(4);
+        game.release_mouse(MouseButton::Left);
+        match side {
+            1 => game.tap_key(KeyCode::Space),
+            2 => game.tap_key(KeyCode::KeyQ),
+            _ => {}
+        }
+        game.run_steps(12);
+        game.release_key(key);
+    }
+    game.run_steps(20);
+}
+
+fn record() -> (Replay, u64) {
+    let step = Time::<Fixed>::from_hz(SIMULATION_HZ).timestep();
+    let mut game = HeadlessApp::builder()
+        .seed(SEED)
+        .timestep(step * 2)
+        .record()
+        .build();
+    play_script(&mut game);
+    let replay = game.recording().expect("the run is recorded").clone();
+    (replay, game.state_hash())
+}
+
+fn play_back(replay: Replay) -> HeadlessApp {
+    let step = Time::<Fixed>::from_hz(SIMULATION_HZ).timestep();
+    let ticks = replay.ticks;
+    let mut game = HeadlessApp::builder()
+        .timestep(step / 4)
+        .replay(replay)
+        .build();
+    game.run_steps(u64::from(ticks));
+    game
+}
+
+#[test]
+fn replay_ends_in_the_recorded_state() {
+    let (replay, hash) = record();
+    assert!(replay.ticks > 0);
+    assert_eq!(replay.final_hash, hash);
+
+    let mut game = play_back(replay);
+    let result = game
+        .replay_result()
+        .expect("the replay should have finished");
+    assert!(result.matched(), "{result:?}");
+    assert_eq!(game.state_hash(), hash);
+}
+
+#[test]
+fn replay_on_another_seed_diverges() {
+    let (mut replay, _) = record();
+    replay.seed += 1;
+
+    let game = play_back(replay);
+    let result = game
+        .replay_result()
+        .expect("the replay should have finished");
+    assert!(!result.matched(), "{result:?}");
+}